@@ -0,0 +1,12 @@
+/* Format strings of `binlog!` (see `src/binlog.rs`).
+
+   The section is kept in the ELF, for the host decoder, but not loaded
+   into the target (INFO). The address of a string, i.e., its offset in
+   the section, is the index sent over RTT. */
+SECTIONS
+{
+  .binlog 0 (INFO) :
+  {
+    KEEP(*(.binlog .binlog.*));
+  }
+}
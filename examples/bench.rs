@@ -0,0 +1,74 @@
+//! examples/bench.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use app::bench;
+use core::sync::atomic::{AtomicU32, Ordering};
+use cortex_m::asm;
+use panic_halt as _;
+use rtt_target::{rprintln, rtt_init_print};
+
+static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+#[rtic::app(device = stm32f4)]
+const APP: () = {
+    #[init]
+    fn init(mut cx: init::Context) {
+        rtt_init_print!();
+        // Initialize (enable) the monotonic timer (CYCCNT)
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+
+        let benches = [
+            bench!(timed_loop),
+            bench!(nop),
+            bench!("atomic_fetch_add", || {
+                COUNTER.fetch_add(1, Ordering::Relaxed);
+            }),
+        ];
+
+        // `init` runs with interrupts disabled, so no preemption
+        let mut samples = [0; 100];
+        bench::run_all(&benches, &mut samples, |stats| rprintln!("{}", stats));
+        rprintln!("bench done");
+    }
+
+    #[idle]
+    fn idle(_cx: idle::Context) -> ! {
+        loop {
+            continue;
+        }
+    }
+};
+
+#[inline(never)]
+fn timed_loop() {
+    for _ in 0..10000 {
+        asm::nop();
+    }
+}
+
+#[inline(never)]
+fn nop() {
+    asm::nop();
+}
+
+// Micro-benchmarks
+//
+// Benchmarks are registered by the `bench!` macro (see `src/bench.rs`),
+// and run 100 times each. The cost of the instrumentation (reading CYCCNT
+// twice) is calibrated first (see `src/measure.rs`) and subtracted.
+//
+// > cargo run --example bench --release --features nightly
+// bench timed_loop runs=100 min=... median=... max=... overhead=...
+// ...
+//
+// To store the results (and compare them to a previous commit), pipe the
+// output to the host collector:
+//
+// > cargo run --example bench --release --features nightly | (cd tools && cargo run --bin bench -- --compare bench/<commit>.json)
+//
+// The results are stored as `tools/bench/<commit>.json`.
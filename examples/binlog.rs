@@ -0,0 +1,98 @@
+//! examples/binlog.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use app::{
+    binlog,
+    measure::{self, Span},
+};
+use cortex_m::asm;
+use panic_halt as _;
+use rtt_target::{rprintln, rtt_init, set_print_channel};
+use stm32f4::stm32f411;
+
+#[rtic::app(device = stm32f411)]
+const APP: () = {
+    #[init]
+    fn init(mut cx: init::Context) {
+        let channels = rtt_init! {
+            up: {
+                0: {
+                    size: 1024
+                    name: "Terminal"
+                }
+                1: {
+                    size: 1024
+                    name: "binlog"
+                }
+            }
+        };
+        set_print_channel(channels.up.0);
+        binlog::init(channels.up.1);
+
+        // Initialize (enable) the monotonic timer (CYCCNT)
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+        measure::calibrate();
+    }
+
+    #[idle]
+    fn idle(_cx: idle::Context) -> ! {
+        let (task, rt, missed) = ("t1", 40_725u32, false);
+
+        for _ in 0..4 {
+            let span = Span::start();
+            rprintln!("{} response time {}, missed {}", task, rt, missed);
+            let text = span.stop();
+
+            let span = Span::start();
+            binlog!("{} response time {}, missed {}", task, rt, missed);
+            let binary = span.stop();
+
+            binlog!("rprintln! {} cycles, binlog! {} cycles", text.corrected, binary.corrected);
+        }
+        binlog!("{:#x} {:?} {} {}", 0xdead_beefu32, "quoted", -1i32, 'c');
+
+        asm::bkpt();
+        loop {
+            continue;
+        }
+    }
+};
+
+// Deferred formatting
+//
+// `rprintln!` formats the message on the target, which costs cycles (and
+// flash, for the formatting machinery and the strings). `binlog!` sends
+// only an index of the format string and the arguments, the string is
+// looked up and formatted by the host (see `src/binlog.rs`).
+//
+// Here the same message is logged both ways, and the cost of each is
+// logged (using `binlog!`).
+//
+// > cargo run --example binlog --release --features nightly
+//
+// With `openocd` running, expose RTT channel 1 on a TCP port:
+//
+// (gdb) monitor rtt setup 0x20000000 65536 "SEGGER RTT"
+// (gdb) monitor rtt start
+// (gdb) monitor rtt server start 9091 1
+//
+// and decode it:
+//
+// > cd tools
+// > cargo run --bin binlog -- --elf ../target/thumbv7em-none-eabi/release/examples/binlog --tcp localhost:9091
+//
+// How many cycles does each take? Why is the first iteration slower?
+//
+// Look for the format strings in the ELF:
+//
+// > cargo objdump --example binlog --release --features nightly -- --section-headers
+// > cargo objdump --example binlog --release --features nightly -- --full-contents --section .binlog
+//
+// Is the `.binlog` section loaded to flash? Compare the size (`cargo size`)
+// of the `.text` and `.rodata` sections when replacing all `rprintln!`
+// by `binlog!`.
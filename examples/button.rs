@@ -0,0 +1,83 @@
+//! examples/button.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use app::bsp::{Board, Button, Led, Vcp};
+use core::fmt::Write;
+use panic_halt as _;
+use rtt_target::{rprintln, rtt_init_print};
+
+#[rtic::app(device = stm32f4::stm32f411, peripherals = true)]
+const APP: () = {
+    struct Resources {
+        led: Led,
+        button: Button,
+        vcp: Vcp,
+
+        #[init(0)]
+        presses: u32,
+    }
+
+    #[init]
+    fn init(cx: init::Context) -> init::LateResources {
+        rtt_init_print!();
+        rprintln!("init");
+
+        let dp = cx.device;
+        let board = Board::new(&dp.RCC, &dp.GPIOA, &dp.SYSCFG, &dp.EXTI, dp.USART2);
+        init::LateResources {
+            led: board.led,
+            button: board.button,
+            vcp: board.vcp,
+        }
+    }
+
+    #[idle]
+    fn idle(_cx: idle::Context) -> ! {
+        rprintln!("idle, press the blue button (B1)");
+        loop {
+            continue;
+        }
+    }
+
+    // bound to the real button interrupt, not pended by software
+    #[task(binds = EXTI15_10, resources = [button, led, vcp, presses])]
+    fn button(cx: button::Context) {
+        cx.resources.button.clear_interrupt();
+        cx.resources.led.toggle();
+        *cx.resources.presses += 1;
+
+        rprintln!("press {}", cx.resources.presses);
+        writeln!(cx.resources.vcp, "press {}\r", cx.resources.presses).ok();
+    }
+};
+
+// The Nucleo board
+//
+// So far all tasks have been pended by software (`rtic::pend`). Here the
+// `button` task is bound to `EXTI15_10`, which is raised (on the falling
+// edge of PC13) when you press the blue user button B1. The task toggles
+// the green LED (LD2) and reports the number of presses over both RTT and
+// the virtual COM port (USART2, 115200 8N1) of the ST-LINK.
+//
+// See `src/bsp.rs` for the pin and peripheral configuration.
+//
+// > cargo run --example button --release --features nightly
+//
+// In another terminal, open the virtual COM port, e.g.:
+//
+// > screen /dev/ttyACM0 115200
+//
+// Press the button a few times. Does every press count once? If not, why?
+// (Hint, look up "contact bounce".)
+//
+// Comment out the `clear_interrupt()` call. What happens, and why?
+//
+// The handles (`Led`, `Button`, `Vcp`) cannot be copied, so each can only
+// be a resource of its own. `Led` and `Button` only touch their own pin and
+// EXTI line, GPIOA and EXTI are still available in `init` (`dp.GPIOA`,
+// `dp.EXTI`), e.g., for another pin. Try to create a second `Board` in
+// `init`. What does the compiler say, and why?
@@ -0,0 +1,218 @@
+//! examples/latency.rs
+
+// #![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use cortex_m::{
+    asm,
+    peripheral::{syst::SystClkSource, DWT, SYST},
+};
+use panic_halt as _;
+use stm32f4::stm32f411::{self, Interrupt};
+
+/// Repetitions of each case.
+const RUNS: usize = 8;
+
+/// The cases, in order of `LATENCY` (keep in sync with `tools/src/latency.rs`).
+const IDLE: usize = 0;
+const BASEPRI: usize = 1;
+const TAIL_CHAIN: usize = 2;
+const LATE_ARRIVAL: usize = 3;
+const WAIT_STATES: usize = 4; // 1, 2, 3 wait states, and 3 with caches
+const CASES: usize = 8;
+
+/// SysTick offsets (cycles from starting SysTick to its expiry) swept for
+/// late arrival, `exti3` is pended right after starting SysTick.
+const LATE_OFFSETS: core::ops::RangeInclusive<u32> = 1..=24;
+
+// SYST_CSR counter enable
+const SYST_CSR_ENABLE: u32 = 1;
+
+// FLASH_ACR bits
+const ACR_LATENCY: u32 = 0xf;
+const ACR_PRFTEN: u32 = 1 << 8;
+const ACR_ICEN: u32 = 1 << 9;
+const ACR_DCEN: u32 = 1 << 10;
+
+/// Min and max entry latency (in cycles) per case, read by `tools` (`latency`).
+#[no_mangle]
+static mut LATENCY: [[u32; 2]; CASES] = [[u32::MAX, 0]; CASES];
+
+// Cycle counter at the event (pend, unlock, return) and at task entry.
+static EVENT: AtomicU32 = AtomicU32::new(0);
+static ENTRY: AtomicU32 = AtomicU32::new(0);
+
+#[inline(always)]
+fn stamp(at: &AtomicU32) {
+    at.store(DWT::cycle_count(), Ordering::SeqCst);
+}
+
+/// Records the latency from `EVENT` to `ENTRY` for `case`.
+fn record(case: usize) {
+    let latency = ENTRY
+        .load(Ordering::SeqCst)
+        .wrapping_sub(EVENT.load(Ordering::SeqCst));
+    unsafe {
+        LATENCY[case][0] = LATENCY[case][0].min(latency);
+        LATENCY[case][1] = LATENCY[case][1].max(latency);
+    }
+}
+
+#[rtic::app(device = stm32f4::stm32f411, peripherals = true)]
+const APP: () = {
+    struct Resources {
+        flash: stm32f411::FLASH,
+        syst: SYST,
+
+        #[init(0)]
+        shared: u32,
+    }
+
+    #[init]
+    fn init(mut cx: init::Context) -> init::LateResources {
+        // Initialize (enable) the monotonic timer (CYCCNT)
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+        cx.core.SYST.set_clock_source(SystClkSource::Core);
+        cx.core.SYST.enable_interrupt();
+        init::LateResources {
+            flash: cx.device.FLASH,
+            syst: cx.core.SYST,
+        }
+    }
+
+    #[idle(resources = [flash, shared, syst])]
+    fn idle(mut cx: idle::Context) -> ! {
+        for _ in 0..RUNS {
+            // from idle (thread mode)
+            from_idle();
+            record(IDLE);
+
+            // from thread mode with BASEPRI raised, latency from the unlock
+            cx.resources.shared.lock(|_shared| {
+                rtic::pend(Interrupt::EXTI0);
+                stamp(&EVENT);
+            });
+            record(BASEPRI);
+
+            // tail-chaining from `exti1` to `exti2`, latency from the
+            // return of `exti1`
+            rtic::pend(Interrupt::EXTI1);
+            record(TAIL_CHAIN);
+
+            // late arrival, `systick` (priority 3) fires while entering
+            // `exti3` (priority 1). `exti3` preempts `idle` as soon as it is
+            // pended, so SysTick is started first, then stamped, then
+            // `exti3` pended, the expiry is `offset` cycles after the start
+            for offset in LATE_OFFSETS {
+                cx.resources.syst.set_reload(offset);
+                cx.resources.syst.clear_current();
+                cx.resources.syst.enable_counter();
+                stamp(&EVENT);
+                rtic::pend(Interrupt::EXTI3);
+                // latency from the expiry of SysTick (the stamp lags the
+                // start by the read of CYCCNT, a cycle or two)
+                EVENT.store(
+                    EVENT.load(Ordering::SeqCst).wrapping_add(offset),
+                    Ordering::SeqCst,
+                );
+                record(LATE_ARRIVAL);
+            }
+
+            // from idle, with flash wait states (and caches)
+            for (i, acr) in [1, 2, 3, 3 | ACR_PRFTEN | ACR_ICEN | ACR_DCEN]
+                .iter()
+                .enumerate()
+            {
+                set_acr(&cx.resources.flash, *acr);
+                from_idle();
+                record(WAIT_STATES + i);
+            }
+            set_acr(&cx.resources.flash, 0);
+        }
+
+        asm::bkpt();
+        loop {
+            continue;
+        }
+    }
+
+    #[task(binds = EXTI0, resources = [shared], priority = 1)]
+    fn exti0(_cx: exti0::Context) {
+        stamp(&ENTRY);
+    }
+
+    #[task(binds = EXTI1, priority = 2)]
+    fn exti1(_cx: exti1::Context) {
+        // `exti2` has the same priority, it runs when we return
+        rtic::pend(Interrupt::EXTI2);
+        stamp(&EVENT);
+    }
+
+    #[task(binds = EXTI2, priority = 2)]
+    fn exti2(_cx: exti2::Context) {
+        stamp(&ENTRY);
+    }
+
+    #[task(binds = EXTI3, priority = 1)]
+    fn exti3(_cx: exti3::Context) {}
+
+    #[task(binds = SysTick, priority = 3)]
+    fn systick(_cx: systick::Context) {
+        stamp(&ENTRY);
+        // one shot, `syst` is owned by `idle`
+        unsafe { (*SYST::PTR).csr.modify(|csr| csr & !SYST_CSR_ENABLE) };
+    }
+};
+
+#[inline(always)]
+fn from_idle() {
+    stamp(&EVENT);
+    rtic::pend(Interrupt::EXTI0);
+}
+
+fn set_acr(flash: &stm32f411::FLASH, acr: u32) {
+    flash.acr.modify(|r, w| unsafe {
+        w.bits(r.bits() & !(ACR_LATENCY | ACR_PRFTEN | ACR_ICEN | ACR_DCEN) | acr)
+    });
+}
+
+// Interrupt latency characterization
+//
+// `timing_task.rs` measured a single latency (11 cycles from `idle`).
+// Here we sweep a number of conditions, each repeated `RUNS` times:
+//
+// - idle:          `exti0` pended from `idle` (thread mode).
+// - basepri:       `exti0` pended while `idle` holds `shared` (BASEPRI raised),
+//                  measured from the unlock.
+// - tail-chain:    `exti2` pended by `exti1` (same priority), measured from
+//                  the last statement of `exti1`.
+// - late-arrival:  SysTick (priority 3) is started to expire 1..24 cycles
+//                  later, and `exti3` (priority 1) pended right after (a
+//                  CYCCNT read in between), measured from the expiry of
+//                  SysTick. For short offsets SysTick expires before
+//                  `exti3` is pended (an ordinary preemption of `idle`),
+//                  for longer ones during the stacking of `exti3`, and is
+//                  served first (late arrival), or after the entry of
+//                  `exti3` (a preemption of `exti3`).
+// - N wait states: as idle, with N flash wait states (and with prefetch and
+//                  caches enabled).
+//
+// Timestamps are taken by reading CYCCNT, so the figures include a few
+// cycles of instrumentation (see `examples/overhead.rs`).
+//
+// With `openocd` running:
+//
+// > cargo build --example latency --release --features nightly
+// > cd tools
+// > cargo run --bin latency -- --elf ../target/thumbv7em-none-eabi/release/examples/latency --load
+//
+// Explain the differences between the cases.
+//
+// Why does the late arrival case show a range of latencies?
+//
+// Why do the wait states affect the latency even though the vector table
+// fetch and the stacking are done in parallel?
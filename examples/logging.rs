@@ -0,0 +1,96 @@
+//! examples/logging.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use app::{
+    debug, error, info,
+    log::{self, Level},
+    measure::{self, Span},
+    trace, warn,
+};
+use cortex_m::asm;
+use panic_halt as _;
+use rtt_target::rtt_init_print;
+use stm32f4::stm32f411;
+
+mod sensor {
+    use app::{debug, trace};
+
+    pub fn read(sample: u32) -> u32 {
+        trace!("read sample {}", sample);
+        let value = sample * 3;
+        debug!("value {}", value);
+        value
+    }
+}
+
+#[rtic::app(device = stm32f411)]
+const APP: () = {
+    #[init]
+    fn init(mut cx: init::Context) {
+        rtt_init_print!();
+        // Initialize (enable) the monotonic timer (CYCCNT)
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+        measure::calibrate();
+        info!("init, max level compiled in {}", log::STATIC_MAX_LEVEL);
+    }
+
+    #[idle]
+    fn idle(_cx: idle::Context) -> ! {
+        // all levels (that are compiled in)
+        error!("error");
+        warn!("warn");
+        info!("info");
+        debug!("debug");
+        trace!("trace");
+        sensor::read(1);
+
+        // info and above, except for `sensor`
+        log::set_level(Some(Level::Info));
+        log::set_module_level("logging::sensor", Some(Level::Trace));
+        debug!("not shown");
+        sensor::read(2);
+
+        // the cost of a filtered, and of a printed statement
+        let span = Span::start();
+        debug!("filtered {}", 42);
+        let filtered = span.stop();
+        let span = Span::start();
+        info!("printed {}", 42);
+        let printed = span.stop();
+        warn!("filtered {} cycles, printed {} cycles", filtered.corrected, printed.corrected);
+
+        asm::bkpt();
+        loop {
+            continue;
+        }
+    }
+};
+
+// Log levels
+//
+// All output so far used `rprintln!` unconditionally. Here messages are
+// given a level (`error!`, `warn!`, `info!`, `debug!`, `trace!`), and
+// filtered at compile time (Cargo features) and at run-time (per module).
+// See `src/log.rs`.
+//
+// > cargo run --example logging --release --features nightly
+//
+// What is the cost of a statement filtered at run-time? Of a printed one?
+//
+// Now filter at compile time, only warnings and errors are compiled in:
+//
+// > cargo run --example logging --release --features nightly,log-warn
+//
+// What is the cost of the filtered statement now? Compare the code size
+// (`cargo size`) of the two builds.
+//
+// For timing measurements, logging can be stripped entirely (`log-off`).
+//
+// Try the semihosting backend (much slower, why?):
+//
+// > cargo run --example logging --release --features nightly,log-semihosting
@@ -0,0 +1,123 @@
+//! examples/message_passing.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use app::spawn::{SpawnStats, Stamped};
+use panic_halt as _;
+use rtt_target::{rprintln, rtt_init_print};
+use stm32f4::stm32f411;
+
+/// Messages sent by `producer` per activation (more than the capacity of `consumer`).
+const BURST: u32 = 6;
+
+/// Statistics for `consumer`, shared by all tasks.
+static STATS: SpawnStats = SpawnStats::new();
+
+/// The message type, a (sequence numbered) sample.
+#[derive(Clone, Copy, Debug)]
+pub struct Sample {
+    seq: u32,
+    value: u16,
+}
+
+#[rtic::app(device = stm32f411)]
+const APP: () = {
+    struct Resources {
+        #[init(0)]
+        dropped: u32,
+    }
+
+    #[init]
+    fn init(mut cx: init::Context) {
+        rtt_init_print!();
+        // Initialize (enable) the monotonic timer (CYCCNT)
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+        rprintln!("init");
+    }
+
+    #[idle(spawn = [consumer], resources = [dropped])]
+    fn idle(mut cx: idle::Context) -> ! {
+        // `consumer` preempts `idle` right away,
+        // the latency is the dispatch overhead only
+        let sample = Sample { seq: 0, value: 0 };
+        if STATS.spawn(cx.spawn.consumer(Stamped::new(sample))).is_err() {
+            rprintln!("spawn failed");
+        }
+        rprintln!("from idle: {}", STATS.snapshot());
+
+        // `producer` spawns a burst of messages, `consumer` runs once
+        // `producer` is done, and the queue fills up
+        for _ in 0..3 {
+            rtic::pend(stm32f411::Interrupt::EXTI0);
+        }
+        rprintln!("bursts: {}", STATS.snapshot());
+        rprintln!("dropped: {}", cx.resources.dropped.lock(|dropped| *dropped));
+
+        loop {
+            continue;
+        }
+    }
+
+    #[task(binds = EXTI0, priority = 2, spawn = [consumer], resources = [dropped])]
+    fn producer(cx: producer::Context) {
+        static mut SEQ: u32 = 1;
+
+        for i in 0..BURST {
+            let sample = Sample {
+                seq: *SEQ,
+                value: i as u16,
+            };
+            *SEQ += 1;
+            // A full queue hands the message back, handle it instead of `unwrap()`
+            if let Err(msg) = STATS.spawn(cx.spawn.consumer(Stamped::new(sample))) {
+                *cx.resources.dropped += 1;
+                rprintln!("queue full, dropped #{}", msg.msg.seq);
+            }
+        }
+    }
+
+    #[task(capacity = 4, priority = 1)]
+    fn consumer(_cx: consumer::Context, sample: Stamped<Sample>) {
+        let latency = STATS.receive(&sample);
+        rprintln!(
+            "consumer #{} value {} latency {}",
+            sample.msg.seq,
+            sample.msg.value,
+            latency
+        );
+    }
+
+    // Free interrupt used to dispatch the software task `consumer`.
+    extern "C" {
+        fn EXTI1();
+    }
+};
+
+// Software tasks and message passing
+//
+// `consumer` is a software task, it takes a message (`Stamped<Sample>`)
+// and is dispatched by RTIC from a free interrupt (`EXTI1`).
+//
+// Each software task has a message queue, its length is given by the
+// `capacity` (default 1). `spawn` returns `Err(msg)` if the queue is full,
+// handing back the message. Here we count such spawn failures (see
+// `src/spawn.rs`) and keep track of the dropped messages.
+//
+// Each message is stamped (CYCCNT) at spawn, so `consumer` can measure the
+// spawn to start latency.
+//
+// > cargo run --example message_passing --release --features nightly
+//
+// What is the latency for the message spawned from `idle`?
+//
+// How many messages are dropped for each burst, and why?
+//
+// Why is the latency of the messages from `producer` so much larger?
+// (Hint, what is the priority of `producer` compared to `consumer`.)
+//
+// Change the `capacity` of `consumer` to `BURST`, and re-run.
+// What is the cost (in RAM) for a larger capacity?
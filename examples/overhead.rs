@@ -0,0 +1,66 @@
+//! examples/overhead.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use app::measure::{self, Mode, Span};
+use cortex_m::asm;
+use panic_halt as _;
+use rtt_target::{rprintln, rtt_init_print};
+
+#[rtic::app(device = stm32f4)]
+const APP: () = {
+    #[init]
+    fn init(mut cx: init::Context) {
+        rtt_init_print!();
+        // Initialize (enable) the monotonic timer (CYCCNT)
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+
+        // Measure the cost of the instrumentation itself
+        let overhead = measure::calibrate();
+        rprintln!("overhead {}", overhead);
+
+        measure::set_mode(Mode::Both);
+
+        let span = Span::start();
+        asm::delay(10_000);
+        rprintln!("delay(10_000) {}", span.stop());
+
+        let ((), m) = measure::measure(|| asm::delay(1_000));
+        rprintln!("delay(1_000) {}", m);
+
+        let (_, m) = measure::measure(|| ());
+        rprintln!("empty {}", m);
+    }
+
+    #[idle]
+    fn idle(_cx: idle::Context) -> ! {
+        loop {
+            continue;
+        }
+    }
+};
+
+// Instrumentation overhead
+//
+// Reading the cycle counter is not free, a measured region always appears
+// a few cycles longer than the code it encloses. (This is one of the reasons
+// why the response times in `timing_exam.rs` are larger than the delays.)
+//
+// `measure::calibrate()` measures an empty region at startup. The overhead
+// is then subtracted from each measurement (`Measurement::corrected`), while
+// the raw value is kept (`Measurement::raw`).
+//
+// `measure::set_mode` selects what is printed, `Mode::Raw`, `Mode::Corrected`
+// (default) or `Mode::Both`.
+//
+// > cargo run --example overhead --release --features nightly
+//
+// What is the reported overhead?
+//
+// What is the corrected measurement of the empty region?
+//
+// Compare debug and release builds, why is the overhead different?
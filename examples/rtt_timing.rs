@@ -8,6 +8,10 @@
 use panic_halt as _;
 use cortex_m::{asm, peripheral::DWT};
 
+// Cycles measured for `timed_loop`, read by the regression tests (`tools/`).
+#[no_mangle]
+static mut TIMED_LOOP_CYCLES: u32 = 0;
+
 #[rtic::app(device = stm32f4)]
 const APP: () = {
     #[init]
@@ -20,7 +24,10 @@ const APP: () = {
         }
 
         let (start, end) = timed_loop();
-        let _diff = end.wrapping_sub(start);
+        let diff = end.wrapping_sub(start);
+        unsafe {
+            TIMED_LOOP_CYCLES = diff;
+        }
     }
 
     #[idle]
@@ -0,0 +1,121 @@
+//! examples/serial.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use app::{
+    bsp::Board,
+    usart::{Error, Usart},
+};
+use panic_halt as _;
+use rtt_target::{rprintln, rtt_init_print};
+use stm32f4::stm32f411::USART2;
+
+/// Transmit buffer size.
+const TX_SIZE: usize = 64;
+
+type Serial = Usart<USART2, TX_SIZE>;
+
+#[rtic::app(device = stm32f4::stm32f411, peripherals = true)]
+const APP: () = {
+    struct Resources {
+        serial: Serial,
+    }
+
+    #[init]
+    fn init(cx: init::Context) -> init::LateResources {
+        rtt_init_print!();
+        rprintln!("init");
+
+        let dp = cx.device;
+        let board = Board::new(&dp.RCC, &dp.GPIOA, &dp.SYSCFG, &dp.EXTI, dp.USART2);
+        let mut serial = Usart::new(board.vcp.free());
+        serial.write(b"echo, type something\r\n");
+        init::LateResources { serial }
+    }
+
+    #[idle]
+    fn idle(_cx: idle::Context) -> ! {
+        loop {
+            continue;
+        }
+    }
+
+    // pended by the environment, on arrival of serial data (RXNE), and
+    // when the transmitter is ready for the next byte (TXE)
+    #[task(binds = USART2, resources = [serial], priority = 2, spawn = [received, error])]
+    fn usart2(cx: usart2::Context) {
+        match cx.resources.serial.on_interrupt() {
+            Some(Ok(byte)) => {
+                if cx.spawn.received(byte).is_err() {
+                    rprintln!("dropped {}", byte);
+                }
+            }
+            Some(Err(e)) => {
+                // overrun and noise still deliver a byte
+                if let Some(byte) = e.byte() {
+                    if cx.spawn.received(byte).is_err() {
+                        rprintln!("dropped {}", byte);
+                    }
+                }
+                cx.spawn.error(e).ok();
+            }
+            None => {}
+        }
+    }
+
+    #[task(capacity = 16, resources = [serial], priority = 1)]
+    fn received(mut cx: received::Context, byte: u8) {
+        rprintln!("received {}", byte);
+        cx.resources.serial.lock(|serial| {
+            if byte == b'\r' {
+                serial.write(b"\r\n");
+            } else {
+                serial.write(&[byte]);
+            }
+        });
+    }
+
+    #[task(capacity = 4, resources = [serial], priority = 1)]
+    fn error(mut cx: error::Context, e: Error) {
+        let errors = cx.resources.serial.lock(|serial| serial.errors());
+        rprintln!("{:?}, total {}", e, errors);
+    }
+
+    // Free interrupt used to dispatch the software tasks.
+    extern "C" {
+        fn EXTI1();
+    }
+};
+
+// Serial communication
+//
+// The `usart2` task is bound to the USART2 interrupt, it is pended by the
+// environment (the arrival of serial data), and by the transmitter when
+// ready for the next byte. See `src/usart.rs` for the driver.
+//
+// Received bytes are passed to the software task `received`, which echoes
+// them. Receive errors are reported by `error`, along with the counts of
+// all receive errors (on overrun and noise errors the byte is still
+// received, and echoed).
+//
+// > cargo run --example serial --release --features nightly
+//
+// In another terminal, open the virtual COM port, e.g.:
+//
+// > screen /dev/ttyACM0 115200
+//
+// Type something, it is echoed back.
+//
+// Change the baud rate of your terminal (e.g., to 57600), and type again.
+// What errors are reported?
+//
+// Paste a long text into the terminal. Are bytes dropped, and if so
+// where? (Hint, look at the `capacity` of `received`, and the size of the
+// transmit buffer.)
+//
+// Why is `usart2` given a higher priority than `received`? What would
+// happen to the receiver if `received` ran for a long time at the same
+// priority?
@@ -0,0 +1,108 @@
+//! examples/serial_dma.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use app::{
+    bsp::Board,
+    dma_rx::{DmaRx, Frame},
+};
+use panic_halt as _;
+use rtt_target::{rprintln, rtt_init_print};
+
+/// DMA (circular) buffer size, must be even.
+const BUFFER_SIZE: usize = 64;
+
+/// Maximum frame size.
+const FRAME_SIZE: usize = 32;
+
+type Rx = DmaRx<BUFFER_SIZE, FRAME_SIZE>;
+
+#[rtic::app(device = stm32f4::stm32f411, peripherals = true)]
+const APP: () = {
+    struct Resources {
+        rx: Rx,
+    }
+
+    #[init]
+    fn init(cx: init::Context) -> init::LateResources {
+        static mut BUFFER: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
+
+        rtt_init_print!();
+        rprintln!("init");
+
+        let dp = cx.device;
+        let board = Board::new(&dp.RCC, &dp.GPIOA, &dp.SYSCFG, &dp.EXTI, dp.USART2);
+        let rx = DmaRx::new(&dp.RCC, board.vcp.free(), dp.DMA1, BUFFER);
+        init::LateResources { rx }
+    }
+
+    #[idle]
+    fn idle(_cx: idle::Context) -> ! {
+        loop {
+            continue;
+        }
+    }
+
+    // the line went idle, a frame is complete
+    #[task(binds = USART2, resources = [rx], priority = 2, spawn = [frame])]
+    fn usart2(cx: usart2::Context) {
+        if let Some(frame) = cx.resources.rx.poll() {
+            if cx.spawn.frame(frame).is_err() {
+                rprintln!("frame dropped");
+            }
+        }
+    }
+
+    // half or complete transfer, collect the data before it is overwritten
+    #[task(binds = DMA1_STREAM5, resources = [rx], priority = 2, spawn = [frame])]
+    fn dma1_stream5(cx: dma1_stream5::Context) {
+        if let Some(frame) = cx.resources.rx.poll() {
+            if cx.spawn.frame(frame).is_err() {
+                rprintln!("frame dropped");
+            }
+        }
+    }
+
+    #[task(capacity = 4, resources = [rx], priority = 1)]
+    fn frame(mut cx: frame::Context, frame: Frame<FRAME_SIZE>) {
+        let stats = cx.resources.rx.lock(|rx| rx.stats());
+        rprintln!(
+            "frame {:?}{}",
+            core::str::from_utf8(frame.as_bytes()),
+            if frame.truncated() { " (truncated)" } else { "" }
+        );
+        rprintln!("{}", stats);
+    }
+
+    // Free interrupt used to dispatch the software task `frame`.
+    extern "C" {
+        fn EXTI1();
+    }
+};
+
+// DMA based reception
+//
+// In `serial.rs` each received byte raises an interrupt. At high rates (or
+// with many serial ports) that adds up. Here the DMA copies the received
+// bytes into a circular buffer, and the CPU is interrupted only when the
+// line goes idle (a frame is complete), and at the half and end of the
+// buffer (so data is collected before it is overwritten). See
+// `src/dma_rx.rs` for the driver.
+//
+// > cargo run --example serial_dma --release --features nightly
+//
+// In another terminal, send some frames, e.g.:
+//
+// > echo -n "hello" > /dev/ttyACM0
+//
+// How many interrupts are taken for a 5 byte frame? For a 40 byte frame?
+// Compare to `serial.rs`.
+//
+// Send a frame longer than `FRAME_SIZE`. What happens?
+//
+// Why must the DMA task (`dma1_stream5`) be served within the time it
+// takes to receive half the buffer? Add a long running task at a higher
+// priority and provoke an overflow.
@@ -0,0 +1,277 @@
+//! examples/shell.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use app::{
+    bsp::{self, Board},
+    lock_profile::{Profiled, ProfiledLock},
+    measure,
+    shell::{self, Handler, LineBuffer},
+    spsc::{Consumer, Producer, Queue},
+    stack,
+    task_set::{self, EXAM},
+    task_stats::ResponseTime,
+    uptime::Uptime,
+    usart::Usart,
+};
+use core::fmt;
+use cortex_m::peripheral::DWT;
+use panic_halt as _;
+use rtic::{cyccnt::U32Ext, Mutex};
+use stm32f4::stm32f411::USART2;
+
+type Serial = Usart<USART2, 256>;
+
+/// Received bytes, from `usart2` to `idle`.
+const RX_QUEUE: usize = 32;
+
+static T1: ResponseTime = ResponseTime::new("t1", EXAM[0].deadline);
+static T2: ResponseTime = ResponseTime::new("t2", EXAM[1].deadline);
+static T3: ResponseTime = ResponseTime::new("t3", EXAM[2].deadline);
+
+#[rtic::app(device = stm32f4::stm32f411, peripherals = true, monotonic = rtic::cyccnt::CYCCNT)]
+const APP: () = {
+    struct Resources {
+        serial: Serial,
+        producer: Producer<'static, u8, RX_QUEUE>,
+        consumer: Consumer<'static, u8, RX_QUEUE>,
+
+        #[init(Profiled::new(0))]
+        r1: Profiled<u64>,
+        #[init(Profiled::new(0))]
+        r2: Profiled<u64>,
+    }
+
+    #[init(schedule = [t1, t2, t3])]
+    fn init(mut cx: init::Context) -> init::LateResources {
+        static mut Q: Queue<u8, RX_QUEUE> = Queue::new();
+
+        stack::paint();
+        // Initialize (enable) the monotonic timer (CYCCNT)
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+        // instrumentation overhead, subtracted from the hold times
+        measure::calibrate();
+
+        let dp = cx.device;
+        let board = Board::new(&dp.RCC, &dp.GPIOA, &dp.SYSCFG, &dp.EXTI, dp.USART2);
+        let mut serial = Usart::new(board.vcp.free());
+        serial.write(b"\r\nshell, try `help`\r\n> ");
+
+        cx.schedule.t1(cx.start + EXAM[0].period.cycles()).unwrap();
+        cx.schedule.t2(cx.start + EXAM[1].period.cycles()).unwrap();
+        cx.schedule.t3(cx.start + EXAM[2].period.cycles()).unwrap();
+
+        let (producer, consumer) = Q.split();
+        init::LateResources {
+            serial,
+            producer,
+            consumer,
+        }
+    }
+
+    // the shell runs in the background, it only preempts the task set when
+    // locking `serial` (briefly) or `r1`/`r2` (to read the statistics)
+    #[idle(resources = [serial, consumer, r1, r2])]
+    fn idle(cx: idle::Context) -> ! {
+        let mut res = cx.resources;
+        let mut line = LineBuffer::<64>::new();
+        let mut uptime = Uptime::new(DWT::cycle_count());
+
+        loop {
+            uptime.update(DWT::cycle_count());
+            if let Some(byte) = res.consumer.dequeue() {
+                // echo
+                res.serial.lock(|serial| serial.write(&[byte]));
+                if let Some(line) = line.feed(byte) {
+                    let mut out = Out(&mut res.serial);
+                    let mut app = App {
+                        r1: &mut res.r1,
+                        r2: &mut res.r2,
+                        uptime: &mut uptime,
+                    };
+                    fmt::Write::write_str(&mut out, "\r\n").ok();
+                    shell::dispatch(line, &mut app, &mut out).ok();
+                    fmt::Write::write_str(&mut out, "> ").ok();
+                }
+            }
+        }
+    }
+
+    #[task(binds = USART2, resources = [serial, producer], priority = 4)]
+    fn usart2(cx: usart2::Context) {
+        // overrun and noise still deliver a byte, framing errors do not
+        let byte = match cx.resources.serial.on_interrupt() {
+            Some(Ok(byte)) => Some(byte),
+            Some(Err(e)) => e.byte(),
+            None => None,
+        };
+        if let Some(byte) = byte {
+            // dropped if the shell falls behind
+            cx.resources.producer.enqueue(byte).ok();
+        }
+    }
+
+    #[task(schedule = [t1], priority = 1)]
+    fn t1(cx: t1::Context) {
+        cx.schedule
+            .t1(cx.scheduled + EXAM[0].period.cycles())
+            .unwrap();
+        cortex_m::asm::delay(9_500);
+        T1.record(cx.scheduled.elapsed().as_cycles());
+    }
+
+    #[task(schedule = [t2], resources = [r1, r2], priority = 2)]
+    fn t2(cx: t2::Context) {
+        cx.schedule
+            .t2(cx.scheduled + EXAM[1].period.cycles())
+            .unwrap();
+        cortex_m::asm::delay(9_500);
+
+        let mut r2 = cx.resources.r2;
+        cx.resources.r1.section(|_r1| {
+            cortex_m::asm::delay(2_000);
+            r2.profiled_lock(|_r2| cortex_m::asm::delay(4_000));
+            cortex_m::asm::delay(4_000);
+        });
+        cortex_m::asm::delay(2_000);
+        cx.resources.r1.section(|_r1| cortex_m::asm::delay(6_000));
+        cortex_m::asm::delay(2_000);
+
+        T2.record(cx.scheduled.elapsed().as_cycles());
+    }
+
+    #[task(schedule = [t3], resources = [r2], priority = 3)]
+    fn t3(cx: t3::Context) {
+        cx.schedule
+            .t3(cx.scheduled + EXAM[2].period.cycles())
+            .unwrap();
+        cortex_m::asm::delay(9_500);
+        cx.resources.r2.section(|_r2| cortex_m::asm::delay(10_000));
+        cortex_m::asm::delay(10_000);
+        T3.record(cx.scheduled.elapsed().as_cycles());
+    }
+
+    // Free interrupts used to dispatch the software tasks.
+    extern "C" {
+        fn EXTI0();
+        fn EXTI1();
+        fn EXTI2();
+    }
+};
+
+/// Shell output, queued for transmission (waiting while the buffer is full).
+struct Out<'a, S>(&'a mut S);
+
+impl<S: Mutex<T = Serial>> fmt::Write for Out<'_, S> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut bytes = s.as_bytes();
+        while !bytes.is_empty() {
+            let n = self.0.lock(|serial| serial.write(bytes));
+            bytes = &bytes[n..];
+        }
+        Ok(())
+    }
+}
+
+/// The shell commands, on the application.
+struct App<'a, R1, R2> {
+    r1: &'a mut R1,
+    r2: &'a mut R2,
+    uptime: &'a mut Uptime,
+}
+
+impl<R1, R2> Handler for App<'_, R1, R2>
+where
+    R1: Mutex<T = Profiled<u64>>,
+    R2: Mutex<T = Profiled<u64>>,
+{
+    fn stats(&mut self, out: &mut dyn fmt::Write) -> fmt::Result {
+        for rt in [&T1, &T2, &T3].iter() {
+            write!(out, "{}\r\n", rt)?;
+        }
+        let r1 = self.r1.lock(|r1| *r1.stats());
+        let r2 = self.r2.lock(|r2| *r2.stats());
+        write!(out, "r1 {}\r\nr2 {}\r\n", r1, r2)?;
+        write!(out, "stack {} bytes used\r\n", stack::usage())
+    }
+
+    fn reset_stats(&mut self) {
+        T1.reset();
+        T2.reset();
+        T3.reset();
+        self.r1.lock(|r1| r1.reset_stats());
+        self.r2.lock(|r2| r2.reset_stats());
+    }
+
+    fn tasks(&mut self, out: &mut dyn fmt::Write) -> fmt::Result {
+        write!(out, "task prio   period    wcet\r\n")?;
+        for t in EXAM.iter() {
+            write!(
+                out,
+                "{:<4} {:>4} {:>8} {:>7}\r\n",
+                t.name, t.priority, t.period, t.wcet
+            )?;
+        }
+        let u = task_set::utilization_ppm(&EXAM);
+        write!(
+            out,
+            "utilization {}.{}%, hyperperiod {}\r\n",
+            u / 10_000,
+            u / 1_000 % 10,
+            task_set::hyperperiod(&EXAM)
+        )
+    }
+
+    fn uptime(&mut self) -> u64 {
+        self.uptime.update(DWT::cycle_count())
+    }
+
+    fn clock_hz(&self) -> u32 {
+        bsp::CLOCK_HZ
+    }
+
+    fn mem(&mut self, out: &mut dyn fmt::Write) -> fmt::Result {
+        let (size, used) = (stack::size(), stack::usage());
+        write!(out, "static {} bytes\r\n", stack::static_size())?;
+        write!(
+            out,
+            "stack {} of {} bytes used, {} free\r\n",
+            used,
+            size,
+            size - used
+        )
+    }
+}
+
+// A command shell
+//
+// Reading `T1_MAX_RP` in `timing_exam.rs` requires a debugger. Here the
+// same task set runs along with a command shell over the virtual COM port,
+// see `src/shell.rs`, `src/task_stats.rs` and `src/stack.rs`.
+//
+// > cargo run --example shell --release --features nightly
+//
+// In another terminal:
+//
+// > screen /dev/ttyACM0 115200
+//
+// > help
+// > tasks
+// > stats
+// > reset-stats
+// > uptime
+// > mem
+//
+// Compare the response times reported by `stats` to your analysis.
+//
+// The shell runs in `idle`, still it affects the task set. How? (Hint,
+// what are the ceilings of `serial`, `r1` and `r2`?) What is the blocking
+// added to `t3` by the `stats` command?
+//
+// The stack usage is found by painting the stack in `init` and looking
+// for the deepest overwritten word. Why is this a measurement, not a
+// bound? How could you obtain a bound?
@@ -0,0 +1,142 @@
+//! examples/spsc.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use app::{
+    bench,
+    measure::{self, Span},
+    spsc::{Consumer, Producer, Queue},
+};
+use cortex_m::asm;
+use panic_halt as _;
+use rtt_target::{rprintln, rtt_init_print};
+use stm32f4::stm32f411;
+
+/// Number of measurements of each operation.
+const RUNS: usize = 64;
+
+/// Queue length (holds up to `N - 1` elements).
+const N: usize = 8;
+
+#[rtic::app(device = stm32f411)]
+const APP: () = {
+    struct Resources {
+        producer: Producer<'static, u64, N>,
+        consumer: Consumer<'static, u64, N>,
+
+        #[init(0)]
+        shared: u64, // non atomic data, as in `timing_resources.rs`
+
+        // raw cycles (overhead included) measured by `exti0`
+        #[init([0; RUNS])]
+        enqueue: [u32; RUNS],
+        #[init([0; RUNS])]
+        direct: [u32; RUNS],
+    }
+
+    #[init]
+    fn init(mut cx: init::Context) -> init::LateResources {
+        static mut Q: Queue<u64, N> = Queue::new();
+
+        rtt_init_print!();
+        // Initialize (enable) the monotonic timer (CYCCNT)
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+        measure::calibrate();
+
+        let (producer, consumer) = Q.split();
+        init::LateResources { producer, consumer }
+    }
+
+    #[idle(resources = [consumer, shared, enqueue, direct])]
+    fn idle(mut cx: idle::Context) -> ! {
+        let mut dequeue = [0; RUNS];
+        let mut lock = [0; RUNS];
+        let mut received = 0;
+
+        for i in 0..RUNS {
+            // `exti0` produces a value
+            rtic::pend(stm32f411::Interrupt::EXTI0);
+
+            // no lock needed, `idle` is the only consumer
+            let span = Span::start();
+            let value = cx.resources.consumer.dequeue();
+            dequeue[i] = span.elapsed();
+            received += value.is_some() as usize;
+
+            // `shared` is shared with `exti0`, the lock raises BASEPRI
+            let span = Span::start();
+            cx.resources.shared.lock(|shared| *shared += 1);
+            lock[i] = span.elapsed();
+        }
+
+        let mut enqueue = cx.resources.enqueue.lock(|enqueue| *enqueue);
+        let mut direct = cx.resources.direct.lock(|direct| *direct);
+        rprintln!("received {} of {}", received, RUNS);
+        rprintln!("{}", bench::stats("spsc_enqueue", &mut enqueue));
+        rprintln!("{}", bench::stats("spsc_dequeue", &mut dequeue));
+        rprintln!("{}", bench::stats("lock_u64", &mut lock));
+        rprintln!("{}", bench::stats("direct_u64", &mut direct));
+        rprintln!("bench done");
+
+        asm::bkpt();
+        loop {
+            continue;
+        }
+    }
+
+    #[task(binds = EXTI0, resources = [producer, shared, enqueue, direct], priority = 2)]
+    fn exti0(cx: exti0::Context) {
+        static mut RUN: usize = 0;
+        let run = *RUN % RUNS;
+        *RUN += 1;
+
+        let span = Span::start();
+        let r = cx.resources.producer.enqueue(run as u64);
+        cx.resources.enqueue[run] = span.elapsed();
+        if r.is_err() {
+            rprintln!("queue full");
+        }
+
+        // `exti0` runs at the ceiling of `shared`, direct access
+        let span = Span::start();
+        *cx.resources.shared += 1;
+        cx.resources.direct[run] = span.elapsed();
+    }
+};
+
+// Lock-free message passing
+//
+// In `timing_resources.rs` the `shared` resource is protected by a lock
+// (raising BASEPRI). Here the same kind of data is passed from `exti0`
+// (priority 2) to `idle` through a single-producer single-consumer queue
+// (see `src/spsc.rs`). The `Producer` and `Consumer` ends are separate
+// resources, each owned by a single task, so no lock is needed.
+//
+// The cost of each operation is measured (with the instrumentation
+// overhead subtracted) and reported in the same format as `bench.rs`:
+//
+// - spsc_enqueue:  enqueue in `exti0`.
+// - spsc_dequeue:  dequeue in `idle`.
+// - lock_u64:      `shared.lock(|shared| *shared += 1)` in `idle`.
+// - direct_u64:    `*shared += 1` in `exti0` (at the ceiling, no lock).
+//
+// > cargo run --example spsc --release --features nightly
+//
+// or, to collect the results on the host:
+//
+// > cargo run --example spsc --release --features nightly | (cd tools && cargo run --bin bench)
+//
+// Compare the figures. Which is cheaper, locking or the queue?
+//
+// The lock costs BASEPRI manipulation only, while the queue needs atomic
+// loads/stores and index arithmetic. On the other hand, the lock blocks
+// `exti0` (for the duration of the critical section), while the queue
+// never does. Why does that matter for the response time of `exti0`?
+//
+// Look at the generated code (`cargo objdump`) for `exti0`, is there any
+// `dmb` (memory barrier) emitted for the atomics? Why is this sufficient
+// on a single core Cortex-M?
@@ -0,0 +1,122 @@
+//! examples/target_test.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use app::testing;
+use core::{
+    panic::PanicInfo,
+    sync::atomic::{AtomicU32, Ordering},
+};
+use cortex_m::peripheral::DWT;
+use rtt_target::rtt_init_print;
+use stm32f4::stm32f411::{self, Interrupt};
+
+/// Cycle count at the entry of `exti0`.
+static ENTRY: AtomicU32 = AtomicU32::new(0);
+
+#[rtic::app(device = stm32f411, monotonic = rtic::cyccnt::CYCCNT)]
+const APP: () = {
+    #[init]
+    fn init(mut cx: init::Context) {
+        rtt_init_print!();
+        // Initialize (enable) the monotonic timer (CYCCNT)
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+        app::measure::calibrate();
+    }
+
+    // the tests run in `idle`, with the tasks live
+    #[idle]
+    fn idle(_cx: idle::Context) -> ! {
+        testing::run(tests::TESTS)
+    }
+
+    #[task(binds = EXTI0, priority = 1)]
+    fn exti0(_cx: exti0::Context) {
+        ENTRY.store(DWT::cycle_count(), Ordering::Relaxed);
+    }
+};
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    testing::panicked(info)
+}
+
+#[app::tests]
+mod tests {
+    use super::*;
+    use app::{
+        spsc::Queue,
+        task_stats::ResponseTime,
+        usart::{mock::Mock, Error, Usart},
+    };
+
+    #[test]
+    fn spsc_fifo() {
+        let mut q: Queue<u8, 4> = Queue::new();
+        let (mut p, mut c) = q.split();
+        for i in 0..3 {
+            assert_eq!(p.enqueue(i), Ok(()));
+        }
+        // one slot is kept free
+        assert_eq!(p.enqueue(3), Err(3));
+        for i in 0..3 {
+            assert_eq!(c.dequeue(), Some(i));
+        }
+        assert_eq!(c.dequeue(), None);
+    }
+
+    // the unread byte is kept, the next one is lost
+    #[test]
+    fn usart_overrun() {
+        let mut serial: Usart<Mock, 8> = Usart::new(Mock::new());
+        serial.registers_mut().receive(b'a');
+        serial.registers_mut().receive(b'b');
+        assert_eq!(serial.on_interrupt(), Some(Err(Error::Overrun(b'a'))));
+        assert_eq!(serial.errors().overrun, 1);
+    }
+
+    #[test]
+    fn deadline_miss() {
+        let rt = ResponseTime::new("t", 100);
+        assert!(!rt.record(100));
+        assert!(rt.record(101));
+        assert_eq!((rt.count(), rt.max(), rt.misses()), (2, 101, 1));
+    }
+
+    // from pend in `idle` to the first instruction of `exti0`
+    #[test]
+    fn interrupt_latency() {
+        ENTRY.store(0, Ordering::Relaxed);
+        let start = DWT::cycle_count();
+        rtic::pend(Interrupt::EXTI0);
+        let latency = ENTRY.load(Ordering::Relaxed).wrapping_sub(start);
+        assert!(latency < 100, "latency {} cycles", latency);
+    }
+}
+
+// On-target tests
+//
+// Tests are plain functions in a module marked `#[app::tests]`, run from
+// `idle`, see `src/testing.rs`. The results are reported in the format of
+// `cargo test`, over RTT:
+//
+// > cargo run --example target_test --release --features nightly
+//
+// or over semihosting, ending the session with the test status (the exit
+// status of openocd if run without gdb, see `examples/test_report.rs`):
+//
+// > cargo run --example target_test --release --features nightly,test-semihosting
+//
+// The host runner (`target_test` in `tools`) aggregates the results from a
+// capture (or the openocd RTT server), and exits with the test status.
+//
+// Make `interrupt_latency` fail by raising BASEPRI (`cortex_m::register::
+// basepri::write`) before the pend. Why does the test fail? (Hint, when
+// is the interrupt taken?)
+//
+// A failing `assert!` ends the test run, while a test returning an `Err`
+// does not. Why? What would it take to continue after a panic?
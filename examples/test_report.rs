@@ -0,0 +1,162 @@
+//! examples/test_report.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use app::{
+    lock_profile::{Profiled, ProfiledLock},
+    measure,
+    semihosting::{self, File},
+    task_set::{self, EXAM},
+    task_stats::ResponseTime,
+};
+use core::fmt::Write;
+use cortex_m::peripheral::DWT;
+use panic_halt as _;
+use rtic::cyccnt::U32Ext;
+use stm32f4::stm32f411;
+
+/// Run time, 50 hyperperiods of the task set (in clock cycles).
+const RUN_CYCLES: u32 = 50 * task_set::hyperperiod(&EXAM) as u32;
+
+/// Results file, relative to the working directory of the debugger.
+const REPORT: &str = "response_times.md";
+
+static T1: ResponseTime = ResponseTime::new(EXAM[0].name, EXAM[0].deadline);
+static T2: ResponseTime = ResponseTime::new(EXAM[1].name, EXAM[1].deadline);
+static T3: ResponseTime = ResponseTime::new(EXAM[2].name, EXAM[2].deadline);
+
+#[rtic::app(device = stm32f411, monotonic = rtic::cyccnt::CYCCNT)]
+const APP: () = {
+    struct Resources {
+        #[init(Profiled::new(0))]
+        r1: Profiled<u64>,
+        #[init(Profiled::new(0))]
+        r2: Profiled<u64>,
+    }
+
+    #[init(schedule = [t1, t2, t3])]
+    fn init(mut cx: init::Context) {
+        // Initialize (enable) the monotonic timer (CYCCNT)
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+        // instrumentation overhead, subtracted from the hold times
+        measure::calibrate();
+
+        cx.schedule.t1(cx.start + EXAM[0].period.cycles()).unwrap();
+        cx.schedule.t2(cx.start + EXAM[1].period.cycles()).unwrap();
+        cx.schedule.t3(cx.start + EXAM[2].period.cycles()).unwrap();
+    }
+
+    #[idle]
+    fn idle(_cx: idle::Context) -> ! {
+        while DWT::cycle_count() < RUN_CYCLES {}
+
+        // the task set is stopped before reporting, semihosting halts the
+        // core (and the cycle counter) for each call
+        cortex_m::interrupt::disable();
+
+        let passed = [&T1, &T2, &T3]
+            .iter()
+            .all(|rt| rt.count() > 0 && rt.misses() == 0);
+        if report(passed).is_err() {
+            semihosting::exit(false);
+        }
+        semihosting::exit(passed);
+    }
+
+    #[task(schedule = [t1], priority = 1)]
+    fn t1(cx: t1::Context) {
+        cx.schedule.t1(cx.scheduled + EXAM[0].period.cycles()).unwrap();
+        cortex_m::asm::delay(9_500);
+        T1.record(cx.scheduled.elapsed().as_cycles());
+    }
+
+    #[task(schedule = [t2], resources = [r1, r2], priority = 2)]
+    fn t2(cx: t2::Context) {
+        cx.schedule.t2(cx.scheduled + EXAM[1].period.cycles()).unwrap();
+        cortex_m::asm::delay(9_500);
+
+        let mut r2 = cx.resources.r2;
+        cx.resources.r1.section(|_r1| {
+            cortex_m::asm::delay(2_000);
+            r2.profiled_lock(|_r2| cortex_m::asm::delay(4_000));
+            cortex_m::asm::delay(4_000);
+        });
+        cortex_m::asm::delay(2_000);
+        cx.resources.r1.section(|_r1| cortex_m::asm::delay(6_000));
+        cortex_m::asm::delay(2_000);
+
+        T2.record(cx.scheduled.elapsed().as_cycles());
+    }
+
+    #[task(schedule = [t3], resources = [r2], priority = 3)]
+    fn t3(cx: t3::Context) {
+        cx.schedule.t3(cx.scheduled + EXAM[2].period.cycles()).unwrap();
+        cortex_m::asm::delay(9_500);
+        cx.resources.r2.section(|_r2| cortex_m::asm::delay(10_000));
+        cortex_m::asm::delay(10_000);
+        T3.record(cx.scheduled.elapsed().as_cycles());
+    }
+
+    // Free interrupts used to dispatch the software tasks.
+    extern "C" {
+        fn EXTI0();
+        fn EXTI1();
+        fn EXTI2();
+    }
+};
+
+/// Writes the response time table to the host.
+fn report(passed: bool) -> Result<(), semihosting::Error> {
+    let mut file = File::create(REPORT)?;
+    writeln!(file, "| task | count | min | max | deadline | misses |")?;
+    writeln!(file, "|------|-------|-----|-----|----------|--------|")?;
+    for rt in [&T1, &T2, &T3].iter() {
+        writeln!(
+            file,
+            "| {} | {} | {} | {} | {} | {} |",
+            rt.name,
+            rt.count(),
+            rt.min(),
+            rt.max(),
+            rt.deadline,
+            rt.misses()
+        )?;
+    }
+    writeln!(file, "\n{}", if passed { "passed" } else { "FAILED" })?;
+    Ok(())
+}
+
+// Test results on the host
+//
+// The `timing_exam.rs` task set runs for 50 hyperperiods, then the response
+// times are written to `response_times.md` on the host (in the directory
+// where openocd was started), and the session ends with the test status:
+// 0 if all tasks ran and met their deadlines, 1 otherwise. See
+// `src/semihosting.rs`.
+//
+// > openocd -f openocd.cfg
+//
+// In another terminal:
+//
+// > cargo run --example test_report --release --features nightly
+//
+// (using the `openocd.gdb` runner, which enables semihosting.) With gdb
+// attached, openocd only prints the status and keeps running, gdb sees the
+// core halted. To script the test, build it, stop openocd (and gdb), and
+// run openocd alone, it then exits with the status of the test (`timeout`
+// ends a run that never reports, with status 124):
+//
+// > cargo build --example test_report --release --features nightly
+// > timeout 60 openocd -f openocd.cfg -c "init; arm semihosting enable" -c "program target/thumbv7em-none-eabi/release/examples/test_report verify reset"; echo $?
+//
+// Compare the response times in `response_times.md` to your analysis.
+//
+// Increase the emulated workload of `t3` until a deadline is missed, and
+// check the exit status.
+//
+// Why are the interrupts disabled before writing the report? What would
+// happen to the response times otherwise?
@@ -0,0 +1,369 @@
+//! examples/threads.rs
+
+// #![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use app::{
+    bench,
+    measure::{self, Span},
+};
+use core::{
+    arch::global_asm,
+    cell::{Cell, UnsafeCell},
+    mem, ptr,
+    sync::atomic::{AtomicU32, Ordering},
+};
+use cortex_m::{
+    asm, interrupt,
+    peripheral::{scb::SystemHandler, DWT, SCB},
+};
+use cortex_m_rt::entry;
+use panic_halt as _;
+use rtt_target::{rprintln, rtt_init_print};
+
+/// Number of measurements of each operation.
+const RUNS: usize = 64;
+
+// A minimal preemptive kernel, just enough to mimic a typical threaded RTOS
+// (e.g., FreeRTOS): fixed priority threads with their own stacks, context
+// switching in `PendSV`, a binary signal per thread, and a blocking mutex
+// with priority inheritance.
+
+const THREADS: usize = 3;
+const STACK_WORDS: usize = 256;
+
+// thread ids, the priority equals the id (higher is more urgent)
+const IDLE: usize = 0;
+const LOW: usize = 1;
+const HIGH: usize = 2;
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Ready,
+    /// Waiting for a signal.
+    Waiting,
+    /// Waiting for a mutex.
+    Blocked,
+}
+
+#[derive(Clone, Copy)]
+struct Thread {
+    /// Saved stack pointer (when not running).
+    sp: u32,
+    state: State,
+    /// Assigned priority.
+    base: usize,
+    /// Effective priority (raised by priority inheritance).
+    prio: usize,
+    signaled: bool,
+}
+
+#[repr(align(8))]
+struct Stack([u32; STACK_WORDS]);
+
+static mut TASKS: [Thread; THREADS] = [Thread {
+    sp: 0,
+    state: State::Ready,
+    base: 0,
+    prio: 0,
+    signaled: false,
+}; THREADS];
+static mut STACKS: [Stack; THREADS] = [
+    Stack([0; STACK_WORDS]),
+    Stack([0; STACK_WORDS]),
+    Stack([0; STACK_WORDS]),
+];
+// receives the (discarded) context of `main` at the first switch
+static mut BOOT_STACK: Stack = Stack([0; STACK_WORDS]);
+static mut CURRENT: usize = IDLE;
+static mut STARTED: bool = false;
+
+// Saves r4-r11 of the running thread on its (process) stack, lets
+// `switch_context` pick the next thread, and restores its r4-r11. The
+// rest of the context (r0-r3, r12, lr, pc, xpsr) is stacked by hardware.
+global_asm!(
+    ".section .text.PendSV,\"ax\",%progbits",
+    ".global PendSV",
+    ".type PendSV,%function",
+    ".thumb_func",
+    "PendSV:",
+    "    mrs     r0, psp",
+    "    stmdb   r0!, {{r4-r11}}",
+    "    push    {{r0, lr}}",
+    "    bl      switch_context",
+    "    pop     {{r1, lr}}",
+    "    ldmia   r0!, {{r4-r11}}",
+    "    msr     psp, r0",
+    // return to thread mode, using the process stack
+    "    orr     lr, lr, #4",
+    "    bx      lr",
+    "",
+    ".section .text.set_psp,\"ax\",%progbits",
+    ".global set_psp",
+    ".type set_psp,%function",
+    ".thumb_func",
+    "set_psp:",
+    "    msr     psp, r0",
+    "    bx      lr",
+);
+
+extern "C" {
+    fn set_psp(sp: u32);
+}
+
+/// Called by `PendSV`, stores the stack pointer of the preempted thread
+/// and returns the stack pointer of the thread to resume.
+#[no_mangle]
+unsafe extern "C" fn switch_context(sp: u32) -> u32 {
+    if STARTED {
+        TASKS[CURRENT].sp = sp;
+    }
+    STARTED = true;
+    CURRENT = highest_ready();
+    TASKS[CURRENT].sp
+}
+
+/// The highest priority ready thread (`idle` is always ready).
+unsafe fn highest_ready() -> usize {
+    let mut next = IDLE;
+    for id in 0..THREADS {
+        if TASKS[id].state == State::Ready && TASKS[id].prio > TASKS[next].prio {
+            next = id;
+        }
+    }
+    next
+}
+
+/// Requests a context switch if a higher priority thread is ready. The
+/// switch takes place as soon as interrupts are enabled (`PendSV` has the
+/// lowest priority, so it never preempts an interrupt handler).
+unsafe fn schedule() {
+    if highest_ready() != CURRENT {
+        SCB::set_pendsv();
+    }
+}
+
+unsafe fn create(id: usize, entry: fn() -> !) {
+    // initial frame: r4-r11, then r0-r3, r12, lr, pc, xpsr (as if the
+    // thread was preempted right before `entry`)
+    let frame = STACK_WORDS - 16;
+    STACKS[id].0[frame + 14] = entry as usize as u32 & !1; // pc
+    STACKS[id].0[frame + 15] = 1 << 24; // xpsr, thumb state
+    TASKS[id] = Thread {
+        sp: ptr::addr_of!(STACKS[id].0[frame]) as u32,
+        state: State::Ready,
+        base: id,
+        prio: id,
+        signaled: false,
+    };
+}
+
+/// Signals thread `id`, making it ready (preempting the caller if more
+/// urgent).
+fn signal(id: usize) {
+    interrupt::free(|_| unsafe {
+        if TASKS[id].state == State::Waiting {
+            TASKS[id].state = State::Ready;
+            schedule();
+        } else {
+            TASKS[id].signaled = true;
+        }
+    });
+}
+
+/// Waits for a signal to the running thread.
+fn wait() {
+    interrupt::free(|_| unsafe {
+        if TASKS[CURRENT].signaled {
+            TASKS[CURRENT].signaled = false;
+        } else {
+            TASKS[CURRENT].state = State::Waiting;
+            schedule();
+        }
+    });
+}
+
+/// A blocking mutex with priority inheritance.
+struct Mutex<T> {
+    owner: Cell<Option<usize>>,
+    data: UnsafeCell<T>,
+}
+
+// NOTE(unsafe) `owner` is accessed in critical sections only, and `data`
+// by the owner only
+unsafe impl<T> Sync for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    const fn new(data: T) -> Self {
+        Mutex {
+            owner: Cell::new(None),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    fn lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        while !interrupt::free(|_| unsafe {
+            match self.owner.get() {
+                None => {
+                    self.owner.set(Some(CURRENT));
+                    true
+                }
+                Some(owner) => {
+                    // block, and lend our priority to the owner
+                    TASKS[CURRENT].state = State::Blocked;
+                    TASKS[owner].prio = TASKS[owner].prio.max(TASKS[CURRENT].prio);
+                    schedule();
+                    false
+                }
+            }
+        }) {
+            // we get here once woken by `unlock`, try again
+        }
+
+        let r = f(unsafe { &mut *self.data.get() });
+
+        interrupt::free(|_| unsafe {
+            self.owner.set(None);
+            TASKS[CURRENT].prio = TASKS[CURRENT].base;
+            // a single mutex in this example, wake all blocked threads
+            for id in 0..THREADS {
+                if TASKS[id].state == State::Blocked {
+                    TASKS[id].state = State::Ready;
+                }
+            }
+            schedule();
+        });
+        r
+    }
+}
+
+// The application, the same scenario as `timing_resources.rs`:
+// `low` (as `exti1`) locks `SHARED` and signals `high` (as `exti0`), which
+// preempts `low` and tries to lock `SHARED` as well.
+
+static SHARED: Mutex<u64> = Mutex::new(0); // non atomic data
+
+// Cycle counter at the signal, and measurements made by `high`.
+static EVENT: AtomicU32 = AtomicU32::new(0);
+static SWITCH: AtomicU32 = AtomicU32::new(0);
+static CONTENDED: AtomicU32 = AtomicU32::new(0);
+
+#[entry]
+fn main() -> ! {
+    let mut cp = cortex_m::Peripherals::take().unwrap();
+    rtt_init_print!();
+    // Initialize (enable) the monotonic timer (CYCCNT)
+    cp.DCB.enable_trace();
+    cp.DWT.enable_cycle_counter();
+    measure::calibrate();
+
+    unsafe {
+        cp.SCB.set_priority(SystemHandler::PendSV, 0xff);
+        create(IDLE, idle);
+        create(LOW, low);
+        create(HIGH, high);
+        set_psp(ptr::addr_of!(BOOT_STACK) as u32 + mem::size_of::<Stack>() as u32);
+    }
+    // switch to the highest priority thread, never to return
+    SCB::set_pendsv();
+    loop {
+        continue;
+    }
+}
+
+fn idle() -> ! {
+    loop {
+        continue;
+    }
+}
+
+fn high() -> ! {
+    loop {
+        wait();
+        SWITCH.store(
+            DWT::cycle_count().wrapping_sub(EVENT.load(Ordering::SeqCst)),
+            Ordering::SeqCst,
+        );
+        let span = Span::start();
+        SHARED.lock(|shared| {
+            CONTENDED.store(span.elapsed(), Ordering::SeqCst);
+            *shared += 1;
+        });
+    }
+}
+
+fn low() -> ! {
+    let mut switch = [0; RUNS];
+    let mut uncontended = [0; RUNS];
+    let mut contended = [0; RUNS];
+    let mut total = [0; RUNS];
+
+    for i in 0..RUNS {
+        let span = Span::start();
+        SHARED.lock(|shared| *shared += 1);
+        uncontended[i] = span.elapsed();
+
+        let span = Span::start();
+        SHARED.lock(|shared| {
+            EVENT.store(DWT::cycle_count(), Ordering::SeqCst);
+            signal(HIGH);
+            *shared += 1;
+        });
+        total[i] = span.elapsed();
+
+        switch[i] = SWITCH.load(Ordering::SeqCst);
+        contended[i] = CONTENDED.load(Ordering::SeqCst);
+    }
+
+    rprintln!("shared {}", SHARED.lock(|shared| *shared));
+    rprintln!("{}", bench::stats("thread_switch", &mut switch));
+    rprintln!("{}", bench::stats("mutex_uncontended", &mut uncontended));
+    rprintln!("{}", bench::stats("mutex_contended", &mut contended));
+    rprintln!("{}", bench::stats("threaded_total", &mut total));
+    rprintln!("bench done");
+
+    asm::bkpt();
+    loop {
+        wait();
+    }
+}
+
+// A threaded counterpart to `timing_resources.rs`
+//
+// `timing_resources.rs` measured the total time (52 cycles) for `exti1` to
+// lock `shared`, pend `exti0`, be preempted, and for both tasks to update
+// `shared`. Here the same scenario is implemented with threads and a
+// mutex, as you would in a typical threaded RTOS:
+//
+// - `low` locks `SHARED` and signals `high`.
+// - `high` preempts `low` (a context switch), tries to lock `SHARED` and
+//   blocks, `low` inherits the priority of `high` (another context switch).
+// - `low` updates `SHARED` and unlocks, `high` is woken (a third context
+//   switch), locks `SHARED` and updates it.
+//
+// Reported (in the `bench` format, instrumentation overhead subtracted):
+//
+// - thread_switch:      from the signal until `high` runs.
+// - mutex_uncontended:  lock/unlock of `SHARED` with no contention.
+// - mutex_contended:    from `high` attempting the lock until it is granted.
+// - threaded_total:     the complete scenario, compare to the 52 cycles.
+//
+// > cargo run --example threads --release --features nightly
+//
+// or, to collect the results on the host:
+//
+// > cargo run --example threads --release --features nightly | (cd tools && cargo run --bin bench)
+//
+// Compare `threaded_total` to the RTIC figure. Where does the time go?
+//
+// With SRP (RTIC), `exti0` is never started while `exti1` holds `shared`
+// (the lock raises the system ceiling). Thus `exti0` never blocks, no
+// priority inheritance is needed, and all tasks can share a single stack.
+// How many stacks does the threaded version need, and how large must
+// they be?
+//
+// Remove the priority inheritance (the `max` in `Mutex::lock`), add a
+// medium priority thread, and construct a priority inversion. How long
+// can `high` be blocked now?
@@ -0,0 +1,205 @@
+//! examples/timing_control.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use app::{
+    control::Control,
+    lock_profile::{Profiled, ProfiledLock},
+    measure,
+    shell::LineBuffer,
+    task_set::EXAM,
+    task_stats::ResponseTime,
+};
+use core::fmt;
+use panic_halt as _;
+use rtic::cyccnt::U32Ext;
+use rtt_target::{rprint, rprintln, rtt_init, set_print_channel, DownChannel};
+use stm32f4::stm32f411;
+
+// task indices in `CONTROL` (and `EXAM`)
+const T1: usize = 0;
+const T2: usize = 1;
+const T3: usize = 2;
+
+static CONTROL: Control<3> = Control::new(["t1", "t2", "t3"]);
+
+static T1_RT: ResponseTime = ResponseTime::new("t1", EXAM[T1].deadline);
+static T2_RT: ResponseTime = ResponseTime::new("t2", EXAM[T2].deadline);
+static T3_RT: ResponseTime = ResponseTime::new("t3", EXAM[T3].deadline);
+
+#[rtic::app(device = stm32f411, monotonic = rtic::cyccnt::CYCCNT)]
+const APP: () = {
+    struct Resources {
+        down: DownChannel,
+
+        #[init(Profiled::new(0))]
+        r1: Profiled<u64>,
+        #[init(Profiled::new(0))]
+        r2: Profiled<u64>,
+    }
+
+    #[init(schedule = [t1, t2, t3])]
+    fn init(mut cx: init::Context) -> init::LateResources {
+        let channels = rtt_init! {
+            up: {
+                0: {
+                    size: 1024
+                    name: "Terminal"
+                }
+            }
+            down: {
+                0: {
+                    size: 64
+                    name: "Terminal"
+                }
+            }
+        };
+        set_print_channel(channels.up.0);
+        rprintln!("init, try `help`");
+
+        // Initialize (enable) the monotonic timer (CYCCNT)
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+        // instrumentation overhead, subtracted from the hold times
+        measure::calibrate();
+        cx.schedule.t1(cx.start + EXAM[T1].period.cycles()).unwrap();
+        cx.schedule.t2(cx.start + EXAM[T2].period.cycles()).unwrap();
+        cx.schedule.t3(cx.start + EXAM[T3].period.cycles()).unwrap();
+
+        init::LateResources {
+            down: channels.down.0,
+        }
+    }
+
+    // commands are handled in the background
+    #[idle(resources = [down])]
+    fn idle(cx: idle::Context) -> ! {
+        let mut line = LineBuffer::<64>::new();
+        let mut buf = [0; 16];
+        loop {
+            let n = cx.resources.down.read(&mut buf);
+            for byte in buf[..n].iter() {
+                if let Some(line) = line.feed(*byte) {
+                    CONTROL.execute(line, &mut Rtt).ok();
+                }
+            }
+        }
+    }
+
+    #[task(schedule = [t1], priority = 1)]
+    fn t1(cx: t1::Context) {
+        cx.schedule
+            .t1(cx.scheduled + EXAM[T1].period.cycles())
+            .unwrap();
+        workload(T1, 9_500);
+        complete(&T1_RT, cx.scheduled.elapsed().as_cycles());
+    }
+
+    #[task(schedule = [t2], resources = [r1, r2], priority = 2)]
+    fn t2(cx: t2::Context) {
+        cx.schedule
+            .t2(cx.scheduled + EXAM[T2].period.cycles())
+            .unwrap();
+        workload(T2, 9_500);
+
+        let mut r2 = cx.resources.r2;
+        cx.resources.r1.section(|_r1| {
+            cortex_m::asm::delay(2_000);
+            r2.profiled_lock(|_r2| cortex_m::asm::delay(4_000));
+            cortex_m::asm::delay(4_000);
+        });
+        cortex_m::asm::delay(2_000);
+        cx.resources.r1.section(|_r1| cortex_m::asm::delay(6_000));
+        cortex_m::asm::delay(2_000);
+
+        complete(&T2_RT, cx.scheduled.elapsed().as_cycles());
+    }
+
+    #[task(schedule = [t3], resources = [r2], priority = 3)]
+    fn t3(cx: t3::Context) {
+        cx.schedule
+            .t3(cx.scheduled + EXAM[T3].period.cycles())
+            .unwrap();
+        workload(T3, 9_500);
+        cx.resources.r2.section(|_r2| cortex_m::asm::delay(10_000));
+        cortex_m::asm::delay(10_000);
+        complete(&T3_RT, cx.scheduled.elapsed().as_cycles());
+    }
+
+    // Free interrupts used to dispatch the software tasks.
+    extern "C" {
+        fn EXTI0();
+        fn EXTI1();
+        fn EXTI2();
+    }
+};
+
+/// Emulates the workload of `task` outside critical sections (`built_in`
+/// cycles unless changed by `load`), overrunning the deadline if a `miss`
+/// test is pending.
+fn workload(task: usize, built_in: u32) {
+    cortex_m::asm::delay(CONTROL.workload(task, built_in));
+    if CONTROL.take_miss(task) {
+        let deadline = [&T1_RT, &T2_RT, &T3_RT][task].deadline;
+        cortex_m::asm::delay(deadline);
+    }
+}
+
+fn complete(rt: &ResponseTime, response_time: u32) {
+    let missed = rt.record(response_time);
+    if CONTROL.tracing() || missed {
+        rprintln!(
+            "{} {}{}",
+            rt.name,
+            response_time,
+            if missed { " deadline missed" } else { "" }
+        );
+    }
+}
+
+/// Command output, over the RTT up channel.
+struct Rtt;
+
+impl fmt::Write for Rtt {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        rprint!("{}", s);
+        Ok(())
+    }
+}
+
+// Run-time control
+//
+// The `timing_exam.rs` task set, controlled from the host over an RTT
+// down channel, so experiments can be run without reflashing. See
+// `src/control.rs` for the commands:
+//
+// - `load t1 12000`, emulated workload of `t1` (outside of critical
+//   sections), `load t1 default` restores the built-in 9_500 cycles.
+// - `trace on|off`, print the response time of each completed task.
+// - `miss t3`, the next instance of `t3` overruns by its deadline.
+// - `status`, the current settings.
+//
+// Deadline misses are always reported.
+//
+// > cargo run --example timing_control --release --features nightly
+//
+// With `openocd` running, expose RTT channel 0 (up and down) on a TCP port:
+//
+// (gdb) monitor rtt setup 0x20000000 65536 "SEGGER RTT"
+// (gdb) monitor rtt start
+// (gdb) monitor rtt server start 9090 0
+//
+// and connect a terminal, e.g., `nc localhost 9090`. What you type is sent
+// to the down channel.
+//
+// Find the largest workload of `t1` for which all deadlines are met.
+// Compare to the slack predicted by your analysis.
+//
+// Increase the workload of `t3`. Which task misses its deadline first,
+// and why?
+//
+// `trace on` prints from within the tasks. How does that affect the
+// response times? (Compare with `trace off`.)
@@ -5,6 +5,7 @@
 #![no_main]
 #![no_std]
 
+use app::task_set::EXAM;
 use cortex_m::{asm, peripheral::DWT};
 use panic_halt as _;
 use rtic::cyccnt::{Duration, Instant, U32Ext};
@@ -31,16 +32,17 @@ const APP: () = {
         // Initialize (enable) the monotonic timer (CYCCNT)
         cx.core.DCB.enable_trace();
         cx.core.DWT.enable_cycle_counter();
-        cx.schedule.t1(cx.start + 100_000.cycles()).unwrap();
-        cx.schedule.t2(cx.start + 200_000.cycles()).unwrap();
-        cx.schedule.t3(cx.start + 50_000.cycles()).unwrap();
+        // periods (and deadlines) of `task_set::EXAM`, t1, t2, t3
+        cx.schedule.t1(cx.start + EXAM[0].period.cycles()).unwrap();
+        cx.schedule.t2(cx.start + EXAM[1].period.cycles()).unwrap();
+        cx.schedule.t3(cx.start + EXAM[2].period.cycles()).unwrap();
     }
 
     // Deadline 100, Inter-arrival 100
     #[inline(never)]
     #[task(schedule = [t1], priority = 1)]
     fn t1(cx: t1::Context) {
-        cx.schedule.t1(cx.scheduled + 100_000.cycles()).unwrap();
+        cx.schedule.t1(cx.scheduled + EXAM[0].period.cycles()).unwrap();
         
         // emulates timing behavior of t1
         cortex_m::asm::delay(9_500);
@@ -55,7 +57,7 @@ const APP: () = {
                 T1_MAX_RP = diff;
                 asm::bkpt();
             }
-            if T1_MAX_RP > 100000 {
+            if T1_MAX_RP > EXAM[0].deadline {
                 asm::bkpt();
             }
         }
@@ -65,7 +67,7 @@ const APP: () = {
     #[inline(never)]
     #[task(schedule = [t2], resources = [R1, R2], priority = 2)]
     fn t2(mut cx: t2::Context) {
-        cx.schedule.t2(cx.scheduled + 200_000.cycles()).unwrap();
+        cx.schedule.t2(cx.scheduled + EXAM[1].period.cycles()).unwrap();
 
         // 1) your code here to emulate timing behavior of t2
         cortex_m::asm::delay(9_500); // 0-10
@@ -93,7 +95,7 @@ const APP: () = {
                 T2_MAX_RP = diff;
                 asm::bkpt();
             }
-            if T2_MAX_RP > 200000 {
+            if T2_MAX_RP > EXAM[1].deadline {
                 asm::bkpt();
             }
         }
@@ -103,7 +105,7 @@ const APP: () = {
     #[inline(never)]
     #[task(schedule = [t3], resources = [R2], priority = 3)]
     fn t3(cx: t3::Context) {
-        cx.schedule.t3(cx.scheduled + 50_000.cycles()).unwrap();
+        cx.schedule.t3(cx.scheduled + EXAM[2].period.cycles()).unwrap();
 
         // 1) your code here to emulate timing behavior of t3
         cortex_m::asm::delay(9_500); // 0-10
@@ -122,7 +124,7 @@ const APP: () = {
                 T3_MAX_RP = diff;
                 asm::bkpt();
             }
-            if T3_MAX_RP > 50000 {
+            if T3_MAX_RP > EXAM[2].deadline {
                 //panic!("T1 deadline missed");
                 asm::bkpt();
             }
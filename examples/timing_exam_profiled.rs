@@ -0,0 +1,107 @@
+//! examples/timing_exam_profiled.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use app::{
+    lock_profile::{Profiled, ProfiledLock},
+    measure,
+    task_set::EXAM,
+};
+use panic_halt as _;
+use rtic::cyccnt::U32Ext;
+use stm32f4::stm32f411;
+
+#[rtic::app(device = stm32f411, monotonic = rtic::cyccnt::CYCCNT)]
+const APP: () = {
+    struct Resources {
+        #[init(Profiled::new(0))]
+        R1: Profiled<u64>, // non atomic data
+        #[init(Profiled::new(0))]
+        R2: Profiled<u64>, // non atomic data
+    }
+
+    #[init(schedule = [t1, t2, t3])]
+    fn init(mut cx: init::Context) {
+        // Initialize (enable) the monotonic timer (CYCCNT)
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+        // instrumentation overhead, subtracted from the hold times
+        measure::calibrate();
+        // periods (and deadlines) of `task_set::EXAM`, t1, t2, t3
+        cx.schedule.t1(cx.start + EXAM[0].period.cycles()).unwrap();
+        cx.schedule.t2(cx.start + EXAM[1].period.cycles()).unwrap();
+        cx.schedule.t3(cx.start + EXAM[2].period.cycles()).unwrap();
+    }
+
+    #[task(schedule = [t1], priority = 1)]
+    fn t1(cx: t1::Context) {
+        cx.schedule.t1(cx.scheduled + EXAM[0].period.cycles()).unwrap();
+        cortex_m::asm::delay(9_500);
+    }
+
+    #[task(schedule = [t2], resources = [R1, R2], priority = 2)]
+    fn t2(mut cx: t2::Context) {
+        cx.schedule.t2(cx.scheduled + EXAM[1].period.cycles()).unwrap();
+        cortex_m::asm::delay(9_500); // 0-10
+
+        let r2 = &mut cx.resources.R2;
+        cx.resources.R1.section(|_R1| {
+            cortex_m::asm::delay(2_000); // 10-12
+            r2.profiled_lock(|_R2| {
+                cortex_m::asm::delay(4_000); // 12-16
+            });
+            cortex_m::asm::delay(4_000); // 16-20
+        });
+
+        cortex_m::asm::delay(2_000); // 20-22
+        cx.resources.R1.section(|_R1| {
+            cortex_m::asm::delay(6_000); // 22-28
+        });
+        cortex_m::asm::delay(2_000); // 28-30
+    }
+
+    #[task(schedule = [t3], resources = [R2], priority = 3)]
+    fn t3(cx: t3::Context) {
+        cx.schedule.t3(cx.scheduled + EXAM[2].period.cycles()).unwrap();
+        cortex_m::asm::delay(9_500); // 0-10
+        cx.resources.R2.section(|_R2| {
+            cortex_m::asm::delay(10_000); // 10-20
+        });
+        cortex_m::asm::delay(10_000); // 20-30
+    }
+
+    extern "C" {
+        fn EXTI0();
+        fn EXTI1();
+        fn EXTI2();
+    }
+};
+
+// Lock profiling
+//
+// The task set of `timing_exam.rs`, with the resources `R1` and `R2`
+// wrapped in `Profiled<u64>`, so each critical section is timed (see
+// `src/lock_profile.rs`). The sections of `R1`, only "locked" in
+// `timing_exam.rs`, are real ones here. The instrumentation adds to the
+// response times, so measure those on `timing_exam.rs`.
+//
+// > cargo run --example timing_exam_profiled --release --features nightly
+//
+// Let it run for a while, interrupt (Ctrl-C), and inspect:
+//
+// (gdb) p timing_exam_profiled::APP::R1.stats
+// (gdb) p timing_exam_profiled::APP::R2.stats
+//
+// Compare `max_hold` to the blocking terms of your analysis
+// (R1 10_000 cycles, R2 4_000 cycles for `t2`, R2 10_000 cycles for `t3`).
+// The SRP blocking of `t3` is bounded by the `R2` hold time of `t2`,
+// check `max_blocked` of `R2` to see if `t3` was actually blocked. Blocking
+// is only detected with the `lock-blocking` feature:
+//
+// > cargo run --example timing_exam_profiled --release --features nightly,lock-blocking
+//
+// The detection scans the NVIC after each critical section, which is not
+// part of the hold times, but adds to the response times of `t2` and `t3`.
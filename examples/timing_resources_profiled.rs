@@ -0,0 +1,72 @@
+//! examples/timing_resources_profiled.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use app::{
+    lock_profile::{Profiled, ProfiledLock},
+    measure,
+};
+use cortex_m::asm;
+use panic_halt as _;
+use stm32f4::stm32f411;
+
+#[rtic::app(device = stm32f411)]
+const APP: () = {
+    struct Resources {
+        #[init(Profiled::new(0))]
+        shared: Profiled<u64>, // non atomic data
+    }
+
+    #[init]
+    fn init(mut cx: init::Context) {
+        // Initialize (enable) the monotonic timer (CYCCNT)
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+        // instrumentation overhead, subtracted from the hold times
+        measure::calibrate();
+        rtic::pend(stm32f411::Interrupt::EXTI1);
+    }
+
+    #[task(binds = EXTI0, resources = [shared], priority = 2)]
+    fn exti0(cx: exti0::Context) {
+        cx.resources.shared.section(|shared| *shared += 1);
+    }
+
+    #[task(binds = EXTI1, resources = [shared], priority = 1)]
+    fn exti1(mut cx: exti1::Context) {
+        rtic::pend(stm32f411::Interrupt::EXTI0);
+        cx.resources.shared.profiled_lock(|shared| {
+            *shared += 1;
+        });
+        asm::bkpt();
+    }
+};
+
+// Lock profiling
+//
+// The scenario of `timing_resources.rs`, with the `shared` resource
+// wrapped in a `Profiled<u64>` (see `src/lock_profile.rs`). Each critical
+// section (`profiled_lock` or `section`) is timed by the cycle counter, and
+// the statistics are kept along with the resource. The instrumentation
+// adds to the cycle counts of `timing_resources.rs`, so measure those on
+// the original.
+//
+// > cargo run --example timing_resources_profiled --release --features nightly
+//
+// Continue to the breakpoint in `exti1` and inspect the statistics:
+//
+// (gdb) p timing_resources_profiled::APP::shared.stats
+//
+// `max_hold` is the longest time the lock was held, i.e., the blocking term
+// that the SRP analysis attributes to `shared` for higher priority tasks.
+// `max_blocked` is non-zero only if a higher priority task was actually
+// pending at the end of a critical section. This is detected only with the
+// `lock-blocking` feature (an NVIC scan after each critical section):
+//
+// > cargo run --example timing_resources_profiled --release --features nightly,lock-blocking
+//
+// Move the `rtic::pend(stm32f411::Interrupt::EXTI0)` into the lock closure
+// and re-run. What do you get for `blocked_count` and `max_blocked`?
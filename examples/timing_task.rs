@@ -122,3 +122,20 @@ const APP: () = {
 // (In the general case, as we will see later restoring BASEPRI
 // is actually necessary so its just this corner case that is
 // sub-optimal.)
+//
+// ------------------------------------------------------------------------
+// Automated measurements
+//
+// Instead of stepping through the breakpoints by hand, the `harness`
+// (see `tools/`) can do it for you. With `openocd` running:
+//
+// > cargo build --example timing_task --release --features nightly
+// > cd tools
+// > cargo run --bin harness -- --elf ../target/thumbv7em-none-eabi/release/examples/timing_task --load -n 3 --label start,entry,return
+//
+// label    pc         location                      cyccnt      delta
+// start    0x0800024c timing_task::idle+0x1a             0          0
+// entry    0x08000232 EXTI0+0x0                         11         11
+// return   0x08000252 timing_task::idle+0x20            23         12
+//
+// Add `--json timing_task.json` to store the table, e.g., to compare builds.
@@ -0,0 +1,98 @@
+//! lib.rs
+//!
+//! Procedural macros of the `app` support library, re-exported by `app`.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Error, Item, ItemMod, LitStr};
+
+/// Collects the `#[test]` functions of a module into on-target tests.
+///
+/// ```ignore
+/// #[app::tests]
+/// mod tests {
+///     #[test]
+///     fn wraps() {
+///         assert_eq!(u32::MAX.wrapping_add(1), 0);
+///     }
+///
+///     #[test]
+///     #[ignore]
+///     fn slow() -> Result<(), &'static str> {
+///         Err("not yet")
+///     }
+/// }
+///
+/// // in `idle`
+/// app::testing::run(tests::TESTS);
+/// ```
+///
+/// Test functions take no arguments, and return `()` or a `Result` (see
+/// `app::testing::Outcome`). The attribute adds `pub const TESTS:
+/// &[app::testing::Test]` to the module, in declaration order.
+#[proc_macro_attribute]
+pub fn tests(args: TokenStream, input: TokenStream) -> TokenStream {
+    if !args.is_empty() {
+        return Error::new(Span::call_site(), "`#[tests]` takes no arguments")
+            .to_compile_error()
+            .into();
+    }
+    let module = parse_macro_input!(input as ItemMod);
+    match expand(module) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(mut module: ItemMod) -> Result<proc_macro2::TokenStream, Error> {
+    let module_ident = module.ident.clone();
+    let (_, items) = module
+        .content
+        .as_mut()
+        .ok_or_else(|| Error::new_spanned(&module_ident, "`#[tests]` requires an inline module"))?;
+
+    let mut tests = Vec::new();
+    for item in items.iter_mut() {
+        let f = match item {
+            Item::Fn(f) => f,
+            _ => continue,
+        };
+        let before = f.attrs.len();
+        f.attrs.retain(|attr| !attr.path.is_ident("test"));
+        if f.attrs.len() == before {
+            continue;
+        }
+        let ignore = f.attrs.iter().any(|attr| attr.path.is_ident("ignore"));
+        f.attrs.retain(|attr| !attr.path.is_ident("ignore"));
+
+        let sig = &f.sig;
+        if !sig.inputs.is_empty()
+            || !sig.generics.params.is_empty()
+            || sig.asyncness.is_some()
+            || sig.variadic.is_some()
+        {
+            return Err(Error::new_spanned(
+                sig,
+                "test functions take no arguments and are neither generic nor `async`",
+            ));
+        }
+        let ident = &sig.ident;
+        let name = LitStr::new(&format!("{}::{}", module_ident, ident), ident.span());
+        tests.push(quote! {
+            ::app::testing::Test {
+                name: #name,
+                ignore: #ignore,
+                run: |out| ::app::testing::Outcome::report(#ident(), out),
+            }
+        });
+    }
+
+    items.push(syn::parse_quote! {
+        /// The tests of this module, collected by `#[tests]`.
+        pub const TESTS: &[::app::testing::Test] = &[#(#tests),*];
+    });
+    Ok(quote!(#module))
+}
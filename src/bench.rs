@@ -0,0 +1,127 @@
+//! bench.rs
+//!
+//! Cycle accurate micro-benchmarks.
+//!
+//! Benchmarks are plain functions (`fn()`), registered by the `bench!`
+//! macro. Each benchmark is run a number of times, timed by the cycle
+//! counter (CYCCNT). The instrumentation overhead, i.e., reading the cycle
+//! counter twice, is calibrated up front (`measure::calibrate`) and
+//! subtracted, so the figures reflect the function body (and its call).
+//!
+//! Results are reported as single lines, e.g.:
+//!
+//! ```text
+//! bench timed_loop runs=100 min=40000 median=40000 max=40002 overhead=11
+//! ```
+//!
+//! which the host collector (`tools`, `bench` binary) picks up from the
+//! RTT output.
+//!
+//! Run the benchmarks with interrupts disabled (e.g., from `init`) to
+//! avoid preemption during measurements. The cycle counter must be
+//! enabled (`DWT::enable_cycle_counter`).
+
+use core::fmt;
+
+use crate::measure::{self, Span};
+
+/// A named benchmark.
+pub struct Bench {
+    pub name: &'static str,
+    pub f: fn(),
+}
+
+/// Registers a benchmark, either a function (named after its path) or a
+/// name along with a non capturing closure.
+///
+/// ```ignore
+/// let benches = [
+///     bench!(timed_loop),
+///     bench!("u64 add", || { ... }),
+/// ];
+/// ```
+#[macro_export]
+macro_rules! bench {
+    ($f:path) => {
+        $crate::bench::Bench {
+            name: stringify!($f),
+            f: $f,
+        }
+    };
+    ($name:expr, $f:expr) => {
+        $crate::bench::Bench { name: $name, f: $f }
+    };
+}
+
+/// Statistics of a benchmark (in clock cycles, overhead subtracted).
+#[derive(Clone, Copy, Debug)]
+pub struct Stats {
+    pub name: &'static str,
+    pub runs: usize,
+    pub min: u32,
+    pub median: u32,
+    pub max: u32,
+    /// The subtracted instrumentation overhead.
+    pub overhead: u32,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bench {} runs={} min={} median={} max={} overhead={}",
+            self.name, self.runs, self.min, self.median, self.max, self.overhead
+        )
+    }
+}
+
+/// Cycles spent by `f`, including the instrumentation overhead.
+#[inline(never)]
+fn measure(f: fn()) -> u32 {
+    let span = Span::start();
+    f();
+    span.elapsed()
+}
+
+/// Runs `bench` once per element of `samples`.
+pub fn run(bench: &Bench, samples: &mut [u32]) -> Stats {
+    for s in samples.iter_mut() {
+        *s = measure(bench.f);
+    }
+    stats(bench.name, samples)
+}
+
+/// Statistics of raw `samples` (including the instrumentation overhead,
+/// see `measure::calibrate`), for benchmarks that are timed in place
+/// rather than run as a function.
+pub fn stats(name: &'static str, samples: &mut [u32]) -> Stats {
+    let overhead = measure::overhead();
+    for s in samples.iter_mut() {
+        *s = s.saturating_sub(overhead);
+    }
+    samples.sort_unstable();
+
+    let runs = samples.len();
+    Stats {
+        name,
+        runs,
+        min: samples.first().copied().unwrap_or(0),
+        median: samples.get(runs / 2).copied().unwrap_or(0),
+        max: samples.last().copied().unwrap_or(0),
+        overhead,
+    }
+}
+
+/// Calibrates the overhead, runs all `benches` and hands the statistics
+/// to `report`, e.g.:
+///
+/// ```ignore
+/// let mut samples = [0; 100];
+/// bench::run_all(&benches, &mut samples, |stats| rprintln!("{}", stats));
+/// ```
+pub fn run_all(benches: &[Bench], samples: &mut [u32], mut report: impl FnMut(&Stats)) {
+    measure::calibrate();
+    for bench in benches {
+        report(&run(bench, samples));
+    }
+}
@@ -0,0 +1,302 @@
+//! binlog.rs
+//!
+//! Compact binary logging, with deferred formatting.
+//!
+//! `binlog!` takes a format string and arguments, like `rprintln!`, but
+//! the string is never formatted (nor even stored in flash) on the target.
+//! Instead it is placed in the `.binlog` section (see `binlog.x`), which is
+//! kept in the ELF but not loaded. Only the index of the string (its offset
+//! in the section) and the arguments are sent, over a dedicated RTT up
+//! channel. The host decoder (`tools`, `binlog` binary) looks up the string
+//! in the ELF and formats the message.
+//!
+//! ```ignore
+//! binlog::init(channels.up.1);
+//! binlog!("t1 response time {}, missed {}", rt, missed);
+//! ```
+//!
+//! A frame is a length byte (of the rest of the frame), the string index
+//! (LEB128) and the arguments, each a type tag followed by the value:
+//!
+//! - `u8`..`u32`, `usize`: `U32`, LEB128
+//! - `i8`..`i32`, `isize`: `I32`, zigzag LEB128
+//! - `u64`, `i64`: `U64`, `I64`, as above
+//! - `bool`: `FALSE` or `TRUE`, no value
+//! - `char`: `CHAR`, LEB128
+//! - `f32`: `F32`, 4 bytes little endian
+//! - `&str`: `STR`, LEB128 length followed by the bytes
+//!
+//! Supported placeholders are `{}`, `{:?}`, `{:x}` and `{:#x}`.
+
+use core::cell::RefCell;
+
+use cortex_m::interrupt::{self, Mutex};
+use rtt_target::UpChannel;
+
+/// Maximum frame size, arguments that do not fit are dropped (and shown
+/// as `<?>` by the decoder).
+pub const MAX_FRAME: usize = 128;
+
+// argument type tags (keep in sync with `tools/src/binlog.rs`)
+pub const U32: u8 = 0;
+pub const I32: u8 = 1;
+pub const U64: u8 = 2;
+pub const I64: u8 = 3;
+pub const FALSE: u8 = 4;
+pub const TRUE: u8 = 5;
+pub const STR: u8 = 6;
+pub const F32: u8 = 7;
+pub const CHAR: u8 = 8;
+
+static CHANNEL: Mutex<RefCell<Option<UpChannel>>> = Mutex::new(RefCell::new(None));
+
+/// Sets the RTT up channel used for the frames (raw binary, not a
+/// terminal).
+pub fn init(channel: UpChannel) {
+    interrupt::free(|cs| *CHANNEL.borrow(cs).borrow_mut() = Some(channel));
+}
+
+/// Sends a frame, dropped if `init` was not called.
+pub fn write(frame: &[u8]) {
+    interrupt::free(|cs| {
+        if let Some(channel) = CHANNEL.borrow(cs).borrow_mut().as_mut() {
+            channel.write(frame);
+        }
+    });
+}
+
+/// Copies a format string into a NUL terminated array (`N` = length + 1),
+/// as stored in the `.binlog` section.
+pub const fn format<const N: usize>(s: &str) -> [u8; N] {
+    let bytes = s.as_bytes();
+    let mut a = [0; N];
+    let mut i = 0;
+    while i < bytes.len() {
+        a[i] = bytes[i];
+        i += 1;
+    }
+    a
+}
+
+/// Builds a frame.
+pub struct Encoder {
+    buf: [u8; MAX_FRAME],
+    len: usize,
+    /// An argument did not fit.
+    full: bool,
+}
+
+impl Encoder {
+    /// Starts a frame for the string at `index`.
+    pub fn new(index: usize) -> Self {
+        let mut e = Encoder {
+            buf: [0; MAX_FRAME],
+            len: 1,
+            full: false,
+        };
+        e.varint(index as u64);
+        e
+    }
+
+    /// Adds an argument, if it fits (otherwise it is dropped, along with
+    /// all following arguments).
+    pub fn arg<T: Encode + ?Sized>(&mut self, arg: &T) {
+        if self.full {
+            return;
+        }
+        let len = self.len;
+        arg.encode(self);
+        if self.full {
+            self.len = len;
+        }
+    }
+
+    pub fn byte(&mut self, byte: u8) {
+        if self.len < MAX_FRAME {
+            self.buf[self.len] = byte;
+            self.len += 1;
+        } else {
+            self.full = true;
+        }
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        bytes.iter().for_each(|b| self.byte(*b));
+    }
+
+    pub fn varint(&mut self, mut value: u64) {
+        loop {
+            let low = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                return self.byte(low);
+            }
+            self.byte(low | 0x80);
+        }
+    }
+
+    pub fn zigzag(&mut self, value: i64) {
+        self.varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    /// Completes the frame (sets the length).
+    pub fn finish(&mut self) -> &[u8] {
+        self.buf[0] = (self.len - 1) as u8;
+        &self.buf[..self.len]
+    }
+}
+
+/// Types that can be `binlog!` arguments.
+pub trait Encode {
+    fn encode(&self, e: &mut Encoder);
+}
+
+macro_rules! encode_unsigned {
+    ($($t:ty),*) => {
+        $(impl Encode for $t {
+            fn encode(&self, e: &mut Encoder) {
+                e.byte(U32);
+                e.varint(*self as u64);
+            }
+        })*
+    };
+}
+
+macro_rules! encode_signed {
+    ($($t:ty),*) => {
+        $(impl Encode for $t {
+            fn encode(&self, e: &mut Encoder) {
+                e.byte(I32);
+                e.zigzag(*self as i64);
+            }
+        })*
+    };
+}
+
+encode_unsigned!(u8, u16, u32, usize);
+encode_signed!(i8, i16, i32, isize);
+
+impl Encode for u64 {
+    fn encode(&self, e: &mut Encoder) {
+        e.byte(U64);
+        e.varint(*self);
+    }
+}
+
+impl Encode for i64 {
+    fn encode(&self, e: &mut Encoder) {
+        e.byte(I64);
+        e.zigzag(*self);
+    }
+}
+
+impl Encode for bool {
+    fn encode(&self, e: &mut Encoder) {
+        e.byte(if *self { TRUE } else { FALSE });
+    }
+}
+
+impl Encode for char {
+    fn encode(&self, e: &mut Encoder) {
+        e.byte(CHAR);
+        e.varint(*self as u64);
+    }
+}
+
+impl Encode for f32 {
+    fn encode(&self, e: &mut Encoder) {
+        e.byte(F32);
+        e.bytes(&self.to_le_bytes());
+    }
+}
+
+impl Encode for str {
+    fn encode(&self, e: &mut Encoder) {
+        e.byte(STR);
+        e.varint(self.len() as u64);
+        e.bytes(self.as_bytes());
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode(&self, e: &mut Encoder) {
+        (**self).encode(e)
+    }
+}
+
+/// Logs a message, see the module documentation.
+#[macro_export]
+macro_rules! binlog {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {{
+        #[link_section = ".binlog"]
+        static FMT: [u8; $fmt.len() + 1] = $crate::binlog::format($fmt);
+        let mut e = $crate::binlog::Encoder::new(&FMT as *const _ as usize);
+        $(e.arg(&$arg);)*
+        $crate::binlog::write(e.finish());
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(index: usize, args: impl FnOnce(&mut Encoder)) -> Vec<u8> {
+        let mut e = Encoder::new(index);
+        args(&mut e);
+        e.finish().to_vec()
+    }
+
+    /// Frames of every argument type, decoded by the host tests
+    /// (`tools/src/binlog.rs`).
+    #[test]
+    fn encode_every_type() {
+        let mut stream = Vec::new();
+        stream.extend(frame(0, |e| {
+            e.arg(&7u8);
+            e.arg(&300u16);
+            e.arg(&u32::MAX);
+            e.arg(&0usize);
+        }));
+        stream.extend(frame(12, |e| {
+            e.arg(&-1i8);
+            e.arg(&-300i16);
+            e.arg(&i32::MIN);
+            e.arg(&5isize);
+        }));
+        stream.extend(frame(200, |e| {
+            e.arg(&u64::MAX);
+            e.arg(&i64::MIN);
+        }));
+        stream.extend(frame(3, |e| {
+            e.arg(&true);
+            e.arg(&false);
+            e.arg(&'é');
+            e.arg(&'x');
+        }));
+        stream.extend(frame(4, |e| {
+            e.arg(&1.5f32);
+            e.arg("hi");
+            e.arg(&"");
+        }));
+        assert_eq!(stream, &include_bytes!("../tools/fixtures/binlog.bin")[..]);
+    }
+
+    #[test]
+    fn arguments_that_do_not_fit_are_dropped() {
+        let long = [b'x'; MAX_FRAME];
+        let long = core::str::from_utf8(&long).unwrap();
+        let f = frame(1, |e| {
+            e.arg(&1u32);
+            e.arg(long);
+            // dropped along with the one that did not fit
+            e.arg(&2u32);
+        });
+        assert_eq!(f, [3, 1, U32, 1]);
+    }
+
+    #[test]
+    fn format_is_nul_terminated() {
+        const FMT: [u8; 4] = format("a{}");
+        assert_eq!(FMT, *b"a{}\0");
+    }
+}
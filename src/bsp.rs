@@ -0,0 +1,250 @@
+//! bsp.rs
+//!
+//! Board support for the Nucleo-F411RE.
+//!
+//! - LD2, the green user LED on PA5 (active high).
+//! - B1, the blue user button on PC13 (active low, external pull-up),
+//!   raising EXTI line 13 (the `EXTI15_10` interrupt) when pressed.
+//! - The ST-LINK virtual COM port, USART2 on PA2 (TX) and PA3 (RX), AF7.
+//!
+//! `Board::new` configures the PAC peripherals involved and hands out one
+//! handle per function, so each handle can be given to a single RTIC task
+//! as a resource, e.g.:
+//!
+//! ```ignore
+//! #[task(binds = EXTI15_10, resources = [button, led])]
+//! fn button(cx: button::Context) {
+//!     cx.resources.button.clear_interrupt();
+//!     cx.resources.led.toggle();
+//! }
+//! ```
+//!
+//! `Led` and `Button` are pin (and EXTI line) level handles, they only
+//! touch the bits of PA5, PC13 and EXTI13, through the set/reset and the
+//! write 1 to clear registers, or in a critical section. GPIOA, GPIOC,
+//! SYSCFG and EXTI thus stay with the application, e.g., for other pins and
+//! lines. `Vcp` owns USART2, which makes the board a singleton.
+//!
+//! Clocks are left at reset defaults, the core runs on the 16 MHz HSI.
+
+use core::fmt;
+
+use cortex_m::interrupt;
+use stm32f4::stm32f411::{exti, gpioa, gpioh, EXTI, GPIOA, GPIOC, RCC, SYSCFG, USART2};
+
+/// Core (and bus) clock after reset (HSI).
+pub const CLOCK_HZ: u32 = 16_000_000;
+
+/// Default baud rate of the virtual COM port.
+pub const BAUD_RATE: u32 = 115_200;
+
+const LED_PIN: u32 = 5;
+const BUTTON_PIN: u32 = 13;
+const TX_PIN: u32 = 2;
+const RX_PIN: u32 = 3;
+
+// GPIO mode and alternate function values
+const MODER_OUTPUT: u32 = 0b01;
+const MODER_ALTERNATE: u32 = 0b10;
+const AF7: u32 = 7;
+
+// SYSCFG_EXTICR4 source for EXTI13 (port C)
+const EXTICR_PORT_C: u32 = 0b0010;
+
+// USART status and control bits
+const SR_RXNE: u32 = 1 << 5;
+const SR_TXE: u32 = 1 << 7;
+const CR1_RE: u32 = 1 << 2;
+const CR1_TE: u32 = 1 << 3;
+const CR1_UE: u32 = 1 << 13;
+
+/// The board peripherals, one handle per function.
+pub struct Board {
+    pub led: Led,
+    pub button: Button,
+    pub vcp: Vcp,
+}
+
+impl Board {
+    /// Enables the clocks and configures the pins, the button interrupt
+    /// (falling edge) and the virtual COM port (8N1, `BAUD_RATE`).
+    ///
+    /// The NVIC is left alone, RTIC unmasks the interrupts bound to tasks.
+    pub fn new(rcc: &RCC, gpioa: &GPIOA, syscfg: &SYSCFG, exti: &EXTI, usart2: USART2) -> Self {
+        rcc.ahb1enr
+            .modify(|_, w| w.gpioaen().set_bit().gpiocen().set_bit());
+        rcc.apb1enr.modify(|_, w| w.usart2en().set_bit());
+        rcc.apb2enr.modify(|_, w| w.syscfgen().set_bit());
+        // the clock reaches the peripheral two bus cycles after the write,
+        // read back the enable registers before the first access ("Delay
+        // after an RCC peripheral clock enabling", errata sheet ES0287)
+        rcc.ahb1enr.read();
+        rcc.apb1enr.read();
+        rcc.apb2enr.read();
+
+        // NOTE(unsafe) the written values are valid for the fields
+        gpioa.moder.modify(|r, w| unsafe {
+            let moder = set_field(r.bits(), LED_PIN, 2, MODER_OUTPUT);
+            let moder = set_field(moder, TX_PIN, 2, MODER_ALTERNATE);
+            w.bits(set_field(moder, RX_PIN, 2, MODER_ALTERNATE))
+        });
+        gpioa.afrl.modify(|r, w| unsafe {
+            w.bits(set_field(set_field(r.bits(), TX_PIN, 4, AF7), RX_PIN, 4, AF7))
+        });
+
+        // PC13 is an input after reset, route it to EXTI13
+        syscfg.exticr4.modify(|r, w| unsafe {
+            w.bits(set_field(r.bits(), BUTTON_PIN - 12, 4, EXTICR_PORT_C))
+        });
+        exti.ftsr
+            .modify(|r, w| unsafe { w.bits(r.bits() | 1 << BUTTON_PIN) });
+        exti.imr
+            .modify(|r, w| unsafe { w.bits(r.bits() | 1 << BUTTON_PIN) });
+
+        let mut vcp = Vcp { usart: usart2 };
+        vcp.set_baud_rate(BAUD_RATE);
+        vcp.usart
+            .cr1
+            .write(|w| unsafe { w.bits(CR1_UE | CR1_TE | CR1_RE) });
+
+        Board {
+            led: Led { _pin: () },
+            button: Button { _line: () },
+            vcp,
+        }
+    }
+}
+
+/// Replaces the `width` bits wide field number `index` in `bits`.
+fn set_field(bits: u32, index: u32, width: u32, value: u32) -> u32 {
+    let shift = index * width;
+    let mask = ((1 << width) - 1) << shift;
+    bits & !mask | value << shift
+}
+
+/// LD2, the green user LED (PA5).
+pub struct Led {
+    _pin: (),
+}
+
+impl Led {
+    fn gpioa(&self) -> &gpioa::RegisterBlock {
+        // NOTE(unsafe) only PA5 is written, atomically (BSRR)
+        unsafe { &*GPIOA::ptr() }
+    }
+
+    pub fn on(&mut self) {
+        // NOTE(unsafe) sets (resets) PA5 only
+        self.gpioa().bsrr.write(|w| unsafe { w.bits(1 << LED_PIN) });
+    }
+
+    pub fn off(&mut self) {
+        self.gpioa()
+            .bsrr
+            .write(|w| unsafe { w.bits(1 << (LED_PIN + 16)) });
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.gpioa().odr.read().bits() & 1 << LED_PIN != 0
+    }
+
+    pub fn toggle(&mut self) {
+        if self.is_on() {
+            self.off()
+        } else {
+            self.on()
+        }
+    }
+}
+
+/// B1, the blue user button (PC13), raising `EXTI15_10` when pressed.
+///
+/// The application must keep EXTI13 routed to port C (SYSCFG_EXTICR4).
+pub struct Button {
+    _line: (),
+}
+
+impl Button {
+    fn gpioc(&self) -> &gpioh::RegisterBlock {
+        // NOTE(unsafe) read only
+        unsafe { &*GPIOC::ptr() }
+    }
+
+    fn exti(&self) -> &exti::RegisterBlock {
+        // NOTE(unsafe) only line 13 is written, atomically (PR), or in a
+        // critical section (IMR)
+        unsafe { &*EXTI::ptr() }
+    }
+
+    /// The button pulls the pin low when pressed.
+    pub fn is_pressed(&self) -> bool {
+        self.gpioc().idr.read().bits() & 1 << BUTTON_PIN == 0
+    }
+
+    /// Returns true if the button interrupt is pending.
+    pub fn is_pending(&self) -> bool {
+        self.exti().pr.read().bits() & 1 << BUTTON_PIN != 0
+    }
+
+    /// Clears the pending interrupt, to be called by the bound task.
+    /// (Otherwise the task is re-entered as soon as it returns.)
+    pub fn clear_interrupt(&mut self) {
+        // NOTE(unsafe) write 1 to clear, no effect on the other lines
+        self.exti().pr.write(|w| unsafe { w.bits(1 << BUTTON_PIN) });
+    }
+
+    /// Masks (`false`) or unmasks (`true`) the button interrupt.
+    pub fn listen(&mut self, enable: bool) {
+        // the other lines may be (un)masked by the application
+        interrupt::free(|_| {
+            self.exti().imr.modify(|r, w| unsafe {
+                w.bits(if enable {
+                    r.bits() | 1 << BUTTON_PIN
+                } else {
+                    r.bits() & !(1 << BUTTON_PIN)
+                })
+            })
+        });
+    }
+}
+
+/// The ST-LINK virtual COM port (USART2, PA2/PA3).
+pub struct Vcp {
+    usart: USART2,
+}
+
+impl Vcp {
+    /// Sets the baud rate, assuming the bus runs at `CLOCK_HZ`.
+    pub fn set_baud_rate(&mut self, baud: u32) {
+        // 16x oversampling, BRR holds the (rounded) 12.4 fixed point divider
+        let brr = (CLOCK_HZ + baud / 2) / baud;
+        self.usart.brr.write(|w| unsafe { w.bits(brr) });
+    }
+
+    /// Sends a byte, blocking while the transmit register is full.
+    pub fn write_byte(&mut self, byte: u8) {
+        while self.usart.sr.read().bits() & SR_TXE == 0 {}
+        self.usart.dr.write(|w| unsafe { w.bits(byte as u32) });
+    }
+
+    /// Returns a received byte, if any.
+    pub fn read_byte(&mut self) -> Option<u8> {
+        if self.usart.sr.read().bits() & SR_RXNE != 0 {
+            Some(self.usart.dr.read().bits() as u8)
+        } else {
+            None
+        }
+    }
+
+    /// Releases the USART, e.g., to hand it to an interrupt driven driver.
+    pub fn free(self) -> USART2 {
+        self.usart
+    }
+}
+
+impl fmt::Write for Vcp {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        s.bytes().for_each(|b| self.write_byte(b));
+        Ok(())
+    }
+}
@@ -0,0 +1,265 @@
+//! control.rs
+//!
+//! Run-time control of timing experiments.
+//!
+//! `Control` holds the settings of an experiment, which tasks consult as
+//! they run: the emulated workload per task, whether tracing is enabled,
+//! and one-shot deadline miss tests. Commands, typically received over an
+//! RTT down channel (see `examples/timing_control.rs`), change the settings
+//! without reflashing:
+//!
+//! ```text
+//! load t1 12000     emulated workload of t1 (cycles)
+//! load t1 default   back to the built-in workload
+//! trace on          print response times as tasks complete
+//! miss t3           make the next instance of t3 overrun its deadline
+//! status            print the settings
+//! ```
+//!
+//! Settings are atomics, so a `static Control` can be shared by all tasks.
+
+use core::{
+    fmt,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
+
+/// Workload value meaning "built-in".
+const DEFAULT: u32 = 0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Help,
+    /// Set the workload of a task (`None` restores the built-in one).
+    Load { task: usize, cycles: Option<u32> },
+    Trace(bool),
+    Miss { task: usize },
+    Status,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseError<'a> {
+    Empty,
+    Unknown(&'a str),
+    UnknownTask(&'a str),
+    /// An argument is missing, or invalid (the expected syntax is given).
+    Usage(&'static str),
+}
+
+impl fmt::Display for ParseError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => Ok(()),
+            ParseError::Unknown(cmd) => write!(f, "unknown command `{}`, try `help`", cmd),
+            ParseError::UnknownTask(task) => write!(f, "unknown task `{}`", task),
+            ParseError::Usage(usage) => write!(f, "usage: {}", usage),
+        }
+    }
+}
+
+const LOAD_USAGE: &str = "load <task> <cycles>|default";
+const TRACE_USAGE: &str = "trace on|off";
+const MISS_USAGE: &str = "miss <task>";
+
+impl Command {
+    /// Parses `line`, tasks are named by `tasks`.
+    pub fn parse<'a>(line: &'a str, tasks: &[&str]) -> Result<Command, ParseError<'a>> {
+        let mut words = line.split_whitespace();
+        let task = |word: Option<&'a str>, usage| {
+            let word = word.ok_or(ParseError::Usage(usage))?;
+            tasks
+                .iter()
+                .position(|t| *t == word)
+                .ok_or(ParseError::UnknownTask(word))
+        };
+
+        let cmd = match words.next().ok_or(ParseError::Empty)? {
+            "help" | "?" => Command::Help,
+            "status" => Command::Status,
+            "load" => {
+                let task = task(words.next(), LOAD_USAGE)?;
+                let cycles = match words.next() {
+                    Some("default") => None,
+                    Some(n) => Some(
+                        n.parse()
+                            .ok()
+                            .filter(|n| *n != DEFAULT)
+                            .ok_or(ParseError::Usage(LOAD_USAGE))?,
+                    ),
+                    None => return Err(ParseError::Usage(LOAD_USAGE)),
+                };
+                Command::Load { task, cycles }
+            }
+            "trace" => match words.next() {
+                Some("on") => Command::Trace(true),
+                Some("off") => Command::Trace(false),
+                _ => return Err(ParseError::Usage(TRACE_USAGE)),
+            },
+            "miss" => Command::Miss {
+                task: task(words.next(), MISS_USAGE)?,
+            },
+            other => return Err(ParseError::Unknown(other)),
+        };
+        Ok(cmd)
+    }
+}
+
+/// The settings of an experiment with `N` tasks.
+pub struct Control<const N: usize> {
+    tasks: [&'static str; N],
+    workload: [AtomicU32; N],
+    tracing: AtomicBool,
+    /// One bit per task with a pending deadline miss test.
+    miss: AtomicU32,
+}
+
+impl<const N: usize> Control<N> {
+    /// Settings for the tasks named `tasks` (at most 32).
+    pub const fn new(tasks: [&'static str; N]) -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const BUILT_IN: AtomicU32 = AtomicU32::new(DEFAULT);
+        Control {
+            tasks,
+            workload: [BUILT_IN; N],
+            tracing: AtomicBool::new(false),
+            miss: AtomicU32::new(0),
+        }
+    }
+
+    pub fn tasks(&self) -> &[&'static str] {
+        &self.tasks
+    }
+
+    /// The workload of `task`, or `built_in` if not set.
+    pub fn workload(&self, task: usize, built_in: u32) -> u32 {
+        match self.workload[task].load(Ordering::Relaxed) {
+            DEFAULT => built_in,
+            cycles => cycles,
+        }
+    }
+
+    pub fn tracing(&self) -> bool {
+        self.tracing.load(Ordering::Relaxed)
+    }
+
+    /// Returns true (once) if a deadline miss test is pending for `task`.
+    pub fn take_miss(&self, task: usize) -> bool {
+        self.miss.fetch_and(!(1 << task), Ordering::Relaxed) & 1 << task != 0
+    }
+
+    /// Parses and applies `line`, writes the outcome to `out`.
+    pub fn execute(&self, line: &str, out: &mut impl fmt::Write) -> fmt::Result {
+        let cmd = match Command::parse(line, &self.tasks) {
+            Ok(cmd) => cmd,
+            Err(ParseError::Empty) => return Ok(()),
+            Err(e) => return writeln!(out, "{}", e),
+        };
+        match cmd {
+            Command::Help => {
+                for usage in [LOAD_USAGE, TRACE_USAGE, MISS_USAGE, "status"].iter() {
+                    writeln!(out, "{}", usage)?;
+                }
+                Ok(())
+            }
+            Command::Load { task, cycles } => {
+                self.workload[task].store(cycles.unwrap_or(DEFAULT), Ordering::Relaxed);
+                self.status(out)
+            }
+            Command::Trace(on) => {
+                self.tracing.store(on, Ordering::Relaxed);
+                self.status(out)
+            }
+            Command::Miss { task } => {
+                self.miss.fetch_or(1 << task, Ordering::Relaxed);
+                writeln!(out, "next {} will miss its deadline", self.tasks[task])
+            }
+            Command::Status => self.status(out),
+        }
+    }
+
+    fn status(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        for (name, workload) in self.tasks.iter().zip(self.workload.iter()) {
+            match workload.load(Ordering::Relaxed) {
+                DEFAULT => writeln!(out, "{} load default", name)?,
+                cycles => writeln!(out, "{} load {}", name, cycles)?,
+            }
+        }
+        writeln!(out, "trace {}", if self.tracing() { "on" } else { "off" })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TASKS: [&str; 3] = ["t1", "t2", "t3"];
+
+    #[test]
+    fn parse() {
+        let parse = |line| Command::parse(line, &TASKS);
+        assert_eq!(parse("help"), Ok(Command::Help));
+        assert_eq!(parse(" ? "), Ok(Command::Help));
+        assert_eq!(parse("status"), Ok(Command::Status));
+        assert_eq!(
+            parse("load t2 12000"),
+            Ok(Command::Load {
+                task: 1,
+                cycles: Some(12000)
+            })
+        );
+        assert_eq!(
+            parse("load  t1   default"),
+            Ok(Command::Load {
+                task: 0,
+                cycles: None
+            })
+        );
+        assert_eq!(parse("trace on"), Ok(Command::Trace(true)));
+        assert_eq!(parse("trace off"), Ok(Command::Trace(false)));
+        assert_eq!(parse("miss t3"), Ok(Command::Miss { task: 2 }));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let parse = |line| Command::parse(line, &TASKS);
+        assert_eq!(parse("  "), Err(ParseError::Empty));
+        assert_eq!(parse("lod t1 1"), Err(ParseError::Unknown("lod")));
+        assert_eq!(parse("load t4 1"), Err(ParseError::UnknownTask("t4")));
+        assert_eq!(parse("load t1"), Err(ParseError::Usage(LOAD_USAGE)));
+        // 0 means built-in, `default` is explicit
+        assert_eq!(parse("load t1 0"), Err(ParseError::Usage(LOAD_USAGE)));
+        assert_eq!(parse("load t1 -5"), Err(ParseError::Usage(LOAD_USAGE)));
+        assert_eq!(parse("trace"), Err(ParseError::Usage(TRACE_USAGE)));
+        assert_eq!(parse("miss"), Err(ParseError::Usage(MISS_USAGE)));
+    }
+
+    #[test]
+    fn execute() {
+        let control = Control::new(TASKS);
+        let mut out = String::new();
+        control.execute("load t1 500", &mut out).unwrap();
+        assert_eq!(out, "t1 load 500\nt2 load default\nt3 load default\ntrace off\n");
+        assert_eq!(control.workload(0, 100), 500);
+        assert_eq!(control.workload(1, 100), 100);
+
+        out.clear();
+        control.execute("load t1 default", &mut out).unwrap();
+        assert_eq!(control.workload(0, 100), 100);
+
+        control.execute("trace on", &mut out).unwrap();
+        assert!(control.tracing());
+
+        out.clear();
+        control.execute("miss t2", &mut out).unwrap();
+        assert_eq!(out, "next t2 will miss its deadline\n");
+        assert!(!control.take_miss(0));
+        assert!(control.take_miss(1));
+        // once
+        assert!(!control.take_miss(1));
+
+        out.clear();
+        control.execute("", &mut out).unwrap();
+        assert_eq!(out, "");
+        control.execute("miss t9", &mut out).unwrap();
+        assert_eq!(out, "unknown task `t9`\n");
+    }
+}
@@ -0,0 +1,374 @@
+//! dma_rx.rs
+//!
+//! DMA based USART2 reception, with idle line detection.
+//!
+//! DMA1 stream 5 (channel 4, USART2_RX) copies received bytes into a
+//! circular buffer, without any per byte interrupt. The software is
+//! involved only when:
+//!
+//! - the line goes idle (USART IDLE interrupt), i.e., a frame is complete,
+//! - the DMA reaches the middle (half transfer) or the end (transfer
+//!   complete) of the buffer (`DMA1_STREAM5` interrupt).
+//!
+//! In both cases `DmaRx::poll` collects the new bytes into the current
+//! frame, and returns the frame once the line went idle.
+//!
+//! The buffer index arithmetic is kept in `RxIndex`, free of hardware
+//! access, so it can be run on the host.
+
+use core::{
+    fmt,
+    sync::atomic::{compiler_fence, Ordering},
+};
+
+use stm32f4::stm32f411::{DMA1, RCC, USART2};
+
+/// Receive buffer position and overflow (lap) detection.
+///
+/// The DMA writes at `N - NDTR`, the reader follows at `read`. Positions
+/// alone cannot tell whether the writer lapped the reader (wrote more than
+/// `N` bytes since the last update), so the half transfer and transfer
+/// complete events are counted as well. Each event is one crossing of a
+/// half buffer boundary, if the events outnumber the crossings explained
+/// by the positions, data was overwritten before it was read.
+///
+/// An event may be taken before the corresponding position is read (or
+/// the other way around), thus one crossing may be credited to the next
+/// update. Laps of up to one and a half buffers are always detected,
+/// beyond that the (coalesced) event flags may hide them.
+#[derive(Clone, Copy, Debug)]
+pub struct RxIndex<const N: usize> {
+    read: usize,
+    /// Crossings explained by positions, minus events seen.
+    balance: i32,
+}
+
+/// New data in the receive buffer, `len` bytes from `start` (wrapping).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Chunk {
+    pub start: usize,
+    pub len: usize,
+}
+
+/// The writer lapped the reader, data was lost.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Overflow;
+
+impl<const N: usize> Default for RxIndex<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> RxIndex<N> {
+    /// `crossings` counts half buffers of `N / 2` bytes.
+    const EVEN: () = assert!(N >= 2 && N & 1 == 0, "N must be even, and at least 2");
+
+    pub const fn new() -> Self {
+        let () = Self::EVEN;
+        RxIndex {
+            read: 0,
+            balance: 0,
+        }
+    }
+
+    /// The read position.
+    pub fn read(&self) -> usize {
+        self.read
+    }
+
+    /// Write position for a DMA remaining count (`NDTR`), which counts
+    /// down from `N` and reloads at 0 (circular mode).
+    pub fn write_position(ndtr: usize) -> usize {
+        (N - ndtr.min(N)) % N
+    }
+
+    /// Number of half buffer boundaries (`0` and `N / 2`) in the interval
+    /// `(from, from + len]`.
+    pub fn crossings(from: usize, len: usize) -> i32 {
+        let half = N / 2;
+        ((from + len) / half - from / half) as i32
+    }
+
+    /// Advances the reader to the write position `write`, given the number
+    /// of half transfer/transfer complete `events` since the last update.
+    ///
+    /// Returns the new data, or `Overflow` (the reader then restarts at the
+    /// write position).
+    pub fn update(&mut self, write: usize, events: u32) -> Result<Chunk, Overflow> {
+        let len = (write + N - self.read) % N;
+        let chunk = Chunk {
+            start: self.read,
+            len,
+        };
+        self.balance += Self::crossings(self.read, len) - events as i32;
+        self.read = write;
+        if self.balance < 0 {
+            self.balance = 0;
+            Err(Overflow)
+        } else {
+            Ok(chunk)
+        }
+    }
+
+    /// The chunk as (up to) two slices of `buffer`.
+    pub fn slices<'a>(buffer: &'a [u8; N], chunk: &Chunk) -> (&'a [u8], &'a [u8]) {
+        let end = chunk.start + chunk.len;
+        if end <= N {
+            (&buffer[chunk.start..end], &[])
+        } else {
+            (&buffer[chunk.start..], &buffer[..end - N])
+        }
+    }
+}
+
+/// A received frame of up to `F` bytes.
+#[derive(Clone, Copy)]
+pub struct Frame<const F: usize> {
+    data: [u8; F],
+    len: usize,
+    truncated: bool,
+}
+
+impl<const F: usize> Default for Frame<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const F: usize> Frame<F> {
+    pub const fn new() -> Self {
+        Frame {
+            data: [0; F],
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+
+    /// Returns true if bytes beyond the frame size were dropped.
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Appends as much of `bytes` as fits.
+    fn extend(&mut self, bytes: &[u8]) {
+        let n = bytes.len().min(F - self.len);
+        self.data[self.len..self.len + n].copy_from_slice(&bytes[..n]);
+        self.len += n;
+        self.truncated |= n < bytes.len();
+    }
+}
+
+/// Reception statistics.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    pub bytes: u32,
+    pub frames: u32,
+    /// Buffer overflows (data overwritten before read).
+    pub overflows: u32,
+    /// Frames longer than the frame size (truncated).
+    pub truncated: u32,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bytes {}, frames {}, overflows {}, truncated {}",
+            self.bytes, self.frames, self.overflows, self.truncated
+        )
+    }
+}
+
+// DMA stream 5 (HISR/HIFCR) flags
+const HTIF5: u32 = 1 << 10;
+const TCIF5: u32 = 1 << 11;
+const STREAM5_FLAGS: u32 = 0b111101 << 6;
+
+// DMA stream configuration (SxCR)
+const CR_EN: u32 = 1;
+const CR_HTIE: u32 = 1 << 3;
+const CR_TCIE: u32 = 1 << 4;
+const CR_CIRC: u32 = 1 << 8;
+const CR_MINC: u32 = 1 << 10;
+const CR_CHSEL_4: u32 = 4 << 25;
+
+// USART bits
+const SR_IDLE: u32 = 1 << 4;
+const CR1_IDLEIE: u32 = 1 << 4;
+const CR3_DMAR: u32 = 1 << 6;
+
+const STREAM: usize = 5;
+
+/// USART2 reception through a DMA buffer of `N` bytes, delivering frames
+/// of up to `F` bytes.
+pub struct DmaRx<const N: usize, const F: usize> {
+    usart: USART2,
+    dma: DMA1,
+    buffer: &'static [u8; N],
+    index: RxIndex<N>,
+    frame: Frame<F>,
+    stats: Stats,
+}
+
+impl<const N: usize, const F: usize> DmaRx<N, F> {
+    /// Starts reception on a configured (enabled) USART2 (see `bsp.rs`),
+    /// into `buffer`.
+    pub fn new(rcc: &RCC, usart: USART2, dma: DMA1, buffer: &'static mut [u8; N]) -> Self {
+        rcc.ahb1enr.modify(|_, w| w.dma1en().set_bit());
+        // the clock reaches the DMA two bus cycles after the write (see
+        // `bsp.rs`)
+        rcc.ahb1enr.read();
+
+        // NOTE(unsafe) the stream is disabled, and `buffer` is handed to
+        // the DMA for good (`'static`)
+        let st = &dma.st[STREAM];
+        st.cr.write(|w| unsafe { w.bits(0) });
+        while st.cr.read().bits() & CR_EN != 0 {}
+        dma.hifcr.write(|w| unsafe { w.bits(STREAM5_FLAGS) });
+        st.par
+            .write(|w| unsafe { w.bits(&usart.dr as *const _ as u32) });
+        st.m0ar
+            .write(|w| unsafe { w.bits(buffer.as_ptr() as u32) });
+        st.ndtr.write(|w| unsafe { w.bits(N as u32) });
+        st.cr.write(|w| unsafe {
+            w.bits(CR_CHSEL_4 | CR_MINC | CR_CIRC | CR_HTIE | CR_TCIE | CR_EN)
+        });
+
+        usart
+            .cr3
+            .modify(|r, w| unsafe { w.bits(r.bits() | CR3_DMAR) });
+        usart
+            .cr1
+            .modify(|r, w| unsafe { w.bits(r.bits() | CR1_IDLEIE) });
+
+        DmaRx {
+            usart,
+            dma,
+            buffer,
+            index: RxIndex::new(),
+            frame: Frame::new(),
+            stats: Stats::default(),
+        }
+    }
+
+    /// Collects the received bytes, to be called from the tasks bound to
+    /// `USART2` (idle line) and `DMA1_STREAM5` (half/complete transfer).
+    ///
+    /// Returns the frame, if the line went idle.
+    pub fn poll(&mut self) -> Option<Frame<F>> {
+        // events first, then the position (see `RxIndex`)
+        let hisr = self.dma.hisr.read().bits();
+        let events = (hisr & HTIF5 != 0) as u32 + (hisr & TCIF5 != 0) as u32;
+        // NOTE(unsafe) write 1 to clear, stream 5 flags only
+        self.dma
+            .hifcr
+            .write(|w| unsafe { w.bits(hisr & (HTIF5 | TCIF5)) });
+
+        // reading SR then DR clears IDLE (the DR value is stale, the DMA
+        // already took it)
+        let idle = self.usart.sr.read().bits() & SR_IDLE != 0;
+        if idle {
+            let _ = self.usart.dr.read();
+        }
+
+        let ndtr = self.dma.st[STREAM].ndtr.read().bits() as usize;
+        // the buffer (written by the DMA) is read after NDTR
+        compiler_fence(Ordering::Acquire);
+        match self.index.update(RxIndex::<N>::write_position(ndtr), events) {
+            Ok(chunk) => {
+                self.stats.bytes += chunk.len as u32;
+                let (first, second) = RxIndex::slices(self.buffer, &chunk);
+                self.frame.extend(first);
+                self.frame.extend(second);
+            }
+            Err(Overflow) => {
+                // the frame is corrupt, start over
+                self.stats.overflows += 1;
+                self.frame = Frame::new();
+            }
+        }
+
+        if idle && self.frame.len > 0 {
+            self.stats.frames += 1;
+            self.stats.truncated += self.frame.truncated as u32;
+            Some(core::mem::take(&mut self.frame))
+        } else {
+            None
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Index = RxIndex<8>;
+
+    #[test]
+    fn write_position() {
+        // NDTR reloads to N, both are the start of the buffer
+        assert_eq!(Index::write_position(8), 0);
+        assert_eq!(Index::write_position(0), 0);
+        assert_eq!(Index::write_position(5), 3);
+        assert_eq!(Index::write_position(1), 7);
+    }
+
+    #[test]
+    fn crossings() {
+        assert_eq!(Index::crossings(0, 3), 0);
+        assert_eq!(Index::crossings(0, 4), 1);
+        assert_eq!(Index::crossings(3, 6), 2);
+        assert_eq!(Index::crossings(6, 8), 2);
+    }
+
+    #[test]
+    fn update_across_the_wrap() {
+        let mut index = Index::new();
+        assert_eq!(index.update(6, 1), Ok(Chunk { start: 0, len: 6 }));
+        // crosses the end of the buffer (transfer complete)
+        let chunk = index.update(2, 1).unwrap();
+        assert_eq!(chunk, Chunk { start: 6, len: 4 });
+        assert_eq!(index.read(), 2);
+
+        let buffer = [0, 1, 2, 3, 4, 5, 6, 7];
+        assert_eq!(Index::slices(&buffer, &chunk), (&[6, 7][..], &[0, 1][..]));
+    }
+
+    #[test]
+    fn event_credited_to_the_next_update() {
+        let mut index = Index::new();
+        // the writer crosses the middle between the reads of the events and
+        // of the position, the half transfer event is taken next time
+        assert_eq!(index.update(5, 0), Ok(Chunk { start: 0, len: 5 }));
+        assert_eq!(index.update(6, 1), Ok(Chunk { start: 5, len: 1 }));
+        assert_eq!(index.update(1, 1), Ok(Chunk { start: 6, len: 3 }));
+    }
+
+    #[test]
+    fn lap_of_one_buffer() {
+        let mut index = Index::new();
+        index.update(2, 0).unwrap();
+        // 8 bytes later, back at the same position
+        assert_eq!(index.update(2, 2), Err(Overflow));
+        assert_eq!(index.read(), 2);
+        // the reader restarts at the write position
+        assert_eq!(index.update(3, 0), Ok(Chunk { start: 2, len: 1 }));
+    }
+
+    #[test]
+    fn lap_of_one_and_a_half_buffers() {
+        let mut index = Index::new();
+        // 12 bytes, crossing 4 (HT), 8 (TC) and 12 (HT), the HT flags
+        // coalesce
+        assert_eq!(index.update(4, 2), Err(Overflow));
+    }
+}
@@ -0,0 +1,38 @@
+//! lib.rs
+//!
+//! Support code shared by the examples.
+//!
+//! The library builds for the target as well as the host, where the
+//! hardware independent logic (e.g., `spsc`, `task_set`, `uptime`, the
+//! parsers of `shell` and `control`, and the drivers on mocks) runs under
+//! the standard test harness:
+//!
+//! > cargo test-host
+//!
+//! Hardware access compiles on the host, but must not be executed there.
+
+#![deny(warnings)]
+#![cfg_attr(not(test), no_std)]
+
+pub mod bench;
+pub mod binlog;
+pub mod bsp;
+pub mod control;
+pub mod dma_rx;
+pub mod lock_profile;
+pub mod log;
+pub mod measure;
+pub mod semihosting;
+pub mod shell;
+pub mod spawn;
+pub mod spsc;
+// uses symbols of the target linker script
+#[cfg(target_arch = "arm")]
+pub mod stack;
+pub mod task_set;
+pub mod task_stats;
+pub mod testing;
+pub mod uptime;
+pub mod usart;
+
+pub use app_macros::tests;
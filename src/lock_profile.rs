@@ -0,0 +1,235 @@
+//! lock_profile.rs
+//!
+//! Profiling of RTIC resource locks.
+//!
+//! A resource is wrapped in a `Profiled<T>`, which keeps a `LockStats`
+//! next to the data. Each critical section is timed using the cycle
+//! counter (CYCCNT) while the lock is held, so the statistics are updated
+//! under the very same critical section, no additional locking is needed.
+//! Hold times are corrected for the instrumentation overhead (see
+//! `measure::calibrate`).
+//!
+//! The measured hold times are what the SRP analysis uses as blocking
+//! terms, the maximum hold time of a resource bounds the blocking any
+//! higher priority task (with priority <= ceiling) may suffer.
+//!
+//! To see if a critical section actually blocked someone, the NVIC is
+//! inspected at the end of the section (opt-in, feature `lock-blocking`).
+//! An enabled and pending interrupt with a higher priority than the
+//! currently running task can only be held back by the raised BASEPRI,
+//! i.e., by this lock.
+//!
+//! Notice, the instant an interrupt was pended is not visible to
+//! software, thus the hold time of a blocking section is recorded as the
+//! (pessimistic) blocking time.
+//!
+//! The inspection is not part of the hold time, but it is part of the
+//! task: after each critical section, the 3 pending and 3 enable words of
+//! the NVIC are read, plus the priority of the running and of each pending
+//! interrupt, which adds to the measured response times. Without the
+//! feature, only the hold times are recorded (two CYCCNT reads).
+
+use core::fmt;
+
+use cortex_m::peripheral::{scb::VectActive, NVIC, SCB};
+use rtic::Mutex;
+
+use crate::measure::Span;
+
+/// Number of `ISPR`/`ISER` words covering the STM32F411 interrupts (86).
+const NVIC_WORDS: usize = 3;
+
+/// Lock statistics for a single resource (in clock cycles).
+#[derive(Clone, Copy, Debug)]
+pub struct LockStats {
+    /// Number of critical sections.
+    pub count: u32,
+    /// Accumulated hold time.
+    pub total: u64,
+    /// Shortest hold time.
+    pub min_hold: u32,
+    /// Longest hold time.
+    pub max_hold: u32,
+    /// Number of critical sections that blocked a higher priority task
+    /// (0 without the `lock-blocking` feature).
+    pub blocked_count: u32,
+    /// Longest critical section that blocked a higher priority task.
+    pub max_blocked: u32,
+}
+
+impl Default for LockStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LockStats {
+    pub const fn new() -> Self {
+        LockStats {
+            count: 0,
+            total: 0,
+            min_hold: u32::MAX,
+            max_hold: 0,
+            blocked_count: 0,
+            max_blocked: 0,
+        }
+    }
+
+    /// Average hold time, 0 if the resource was never locked.
+    pub fn avg_hold(&self) -> u32 {
+        if self.count == 0 {
+            0
+        } else {
+            (self.total / self.count as u64) as u32
+        }
+    }
+
+    fn record(&mut self, hold: u32, blocked: bool) {
+        self.count = self.count.wrapping_add(1);
+        self.total += hold as u64;
+        self.min_hold = self.min_hold.min(hold);
+        self.max_hold = self.max_hold.max(hold);
+        if blocked {
+            self.blocked_count = self.blocked_count.wrapping_add(1);
+            self.max_blocked = self.max_blocked.max(hold);
+        }
+    }
+}
+
+impl fmt::Display for LockStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "count {}, hold min/avg/max {}/{}/{}",
+            self.count,
+            if self.count == 0 { 0 } else { self.min_hold },
+            self.avg_hold(),
+            self.max_hold
+        )?;
+        if cfg!(feature = "lock-blocking") {
+            write!(
+                f,
+                ", blocked {} times, max blocked {}",
+                self.blocked_count, self.max_blocked
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A resource along with its lock statistics.
+///
+/// Use it as the resource type, e.g.:
+///
+/// ```ignore
+/// #[init(Profiled::new(0))]
+/// shared: Profiled<u64>,
+/// ```
+///
+/// In gdb, the statistics can be inspected by:
+///
+/// ```text
+/// (gdb) p timing_resources_profiled::APP::shared.stats
+/// ```
+#[derive(Debug)]
+pub struct Profiled<T> {
+    data: T,
+    stats: LockStats,
+}
+
+impl<T> Profiled<T> {
+    pub const fn new(data: T) -> Self {
+        Profiled {
+            data,
+            stats: LockStats::new(),
+        }
+    }
+
+    /// Runs `f` as a profiled critical section.
+    ///
+    /// Must be called with the resource locked (or from the task at the
+    /// ceiling priority, where RTIC hands out direct access).
+    #[inline(always)]
+    pub fn section<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R {
+        let span = Span::start();
+        let r = f(&mut self.data);
+        let hold = span.stop().corrected;
+        self.stats.record(
+            hold,
+            cfg!(feature = "lock-blocking") && higher_priority_pending(),
+        );
+        r
+    }
+
+    /// Direct access, without profiling.
+    pub fn data(&mut self) -> &mut T {
+        &mut self.data
+    }
+
+    pub fn stats(&self) -> &LockStats {
+        &self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = LockStats::new();
+    }
+}
+
+/// Profiled locking of `Profiled<T>` resources.
+///
+/// Implemented for all RTIC resource proxies (and `&mut` references)
+/// of `Profiled<T>`, e.g.:
+///
+/// ```ignore
+/// cx.resources.R2.profiled_lock(|r2| {
+///     *r2 += 1;
+/// });
+/// ```
+pub trait ProfiledLock {
+    type T;
+
+    fn profiled_lock<R>(&mut self, f: impl FnOnce(&mut Self::T) -> R) -> R;
+}
+
+impl<M, T> ProfiledLock for M
+where
+    M: Mutex<T = Profiled<T>>,
+{
+    type T = T;
+
+    #[inline(always)]
+    fn profiled_lock<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R {
+        self.lock(|p| p.section(f))
+    }
+}
+
+/// Returns true if an enabled interrupt with a higher priority than the
+/// currently running task is pending.
+///
+/// (Lower NVIC priority values denote higher urgency, thread mode
+/// (`idle`) is preempted by any interrupt.)
+fn higher_priority_pending() -> bool {
+    let current = match SCB::vect_active() {
+        VectActive::Interrupt { irqn } => nvic_priority(irqn as usize) as u16,
+        _ => 0x100,
+    };
+
+    // NOTE(unsafe) atomic reads with no side effects
+    let nvic = unsafe { &*NVIC::ptr() };
+    for word in 0..NVIC_WORDS {
+        let mut pending = nvic.ispr[word].read() & nvic.iser[word].read();
+        while pending != 0 {
+            let bit = pending.trailing_zeros() as usize;
+            if (nvic_priority(word * 32 + bit) as u16) < current {
+                return true;
+            }
+            pending &= pending - 1;
+        }
+    }
+    false
+}
+
+fn nvic_priority(irqn: usize) -> u8 {
+    // NOTE(unsafe) atomic read with no side effects
+    unsafe { (*NVIC::ptr()).ipr[irqn].read() }
+}
@@ -0,0 +1,218 @@
+//! log.rs
+//!
+//! Leveled logging, with compile-time and run-time filtering.
+//!
+//! `error!`, `warn!`, `info!`, `debug!` and `trace!` take `format!` style
+//! arguments, and print the message prefixed by the level and the module:
+//!
+//! ```text
+//! [INFO ] timing::t1: response time 40725
+//! ```
+//!
+//! Compile-time filtering: the most verbose level is set by the Cargo
+//! features `log-debug`, `log-info`, `log-warn` and `log-error` (all
+//! levels if none, the most restrictive if several), `log-off` strips all
+//! logging. Filtered statements, including their arguments, are removed
+//! entirely, e.g., for timing sensitive release builds:
+//!
+//! > cargo run --example logging --release --features log-off
+//!
+//! Run-time filtering: `set_level` sets the level for all modules, and
+//! `set_module_level` overrides it for a module and its submodules (the
+//! longest matching path applies).
+//!
+//! Messages are printed over RTT (`rtt_init_print!` must be called), or
+//! with the `log-semihosting` feature, over semihosting (requires a
+//! debugger, `monitor arm semihosting enable`, and is much slower).
+
+use core::{
+    cell::RefCell,
+    fmt,
+    sync::atomic::{AtomicU8, AtomicUsize, Ordering},
+};
+
+use cortex_m::interrupt::{self, Mutex};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Level {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        })
+    }
+}
+
+/// The most verbose level compiled in (0 if logging is off).
+pub const STATIC_MAX_LEVEL: u8 = if cfg!(feature = "log-off") {
+    0
+} else if cfg!(feature = "log-error") {
+    Level::Error as u8
+} else if cfg!(feature = "log-warn") {
+    Level::Warn as u8
+} else if cfg!(feature = "log-info") {
+    Level::Info as u8
+} else if cfg!(feature = "log-debug") {
+    Level::Debug as u8
+} else {
+    Level::Trace as u8
+};
+
+/// Maximum number of per module filters.
+pub const MAX_FILTERS: usize = 8;
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Trace as u8);
+static FILTER_COUNT: AtomicUsize = AtomicUsize::new(0);
+static FILTERS: Mutex<RefCell<[(&str, u8); MAX_FILTERS]>> =
+    Mutex::new(RefCell::new([("", 0); MAX_FILTERS]));
+
+/// Sets the run-time level (`None` turns logging off).
+pub fn set_level(level: Option<Level>) {
+    LEVEL.store(level.map_or(0, |l| l as u8), Ordering::Relaxed);
+}
+
+/// Sets the run-time level for the module `prefix` (e.g., `"app::usart"`)
+/// and its submodules, returns false if there are already `MAX_FILTERS`
+/// other filters.
+pub fn set_module_level(prefix: &'static str, level: Option<Level>) -> bool {
+    let level = level.map_or(0, |l| l as u8);
+    interrupt::free(|cs| {
+        let mut filters = FILTERS.borrow(cs).borrow_mut();
+        let count = FILTER_COUNT.load(Ordering::Relaxed);
+        if let Some(f) = filters[..count].iter_mut().find(|f| f.0 == prefix) {
+            f.1 = level;
+        } else if count < MAX_FILTERS {
+            filters[count] = (prefix, level);
+            FILTER_COUNT.store(count + 1, Ordering::Relaxed);
+        } else {
+            return false;
+        }
+        true
+    })
+}
+
+/// Removes all per module filters.
+pub fn clear_module_levels() {
+    FILTER_COUNT.store(0, Ordering::Relaxed);
+}
+
+/// Run-time filter, used by the macros.
+pub fn enabled(level: Level, module: &str) -> bool {
+    let mut max = LEVEL.load(Ordering::Relaxed);
+    // no critical section unless filters are in use
+    if FILTER_COUNT.load(Ordering::Relaxed) > 0 {
+        interrupt::free(|cs| {
+            let filters = FILTERS.borrow(cs).borrow();
+            let count = FILTER_COUNT.load(Ordering::Relaxed);
+            if let Some(level) = module_level(&filters[..count], module) {
+                max = level;
+            }
+        });
+    }
+    level as u8 <= max
+}
+
+/// The level of the filter with the longest prefix matching `module`.
+fn module_level(filters: &[(&str, u8)], module: &str) -> Option<u8> {
+    filters
+        .iter()
+        .filter(|f| matches(f.0, module))
+        .max_by_key(|f| f.0.len())
+        .map(|f| f.1)
+}
+
+/// `module` is `prefix`, or one of its submodules (`app::sh` does not
+/// match `app::shell`).
+fn matches(prefix: &str, module: &str) -> bool {
+    match module.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        None => false,
+    }
+}
+
+/// Prints a message, used by the macros.
+pub fn write(level: Level, module: &str, args: fmt::Arguments) {
+    #[cfg(not(feature = "log-semihosting"))]
+    rtt_target::rprintln!("[{:<5}] {}: {}", level, module, args);
+
+    #[cfg(feature = "log-semihosting")]
+    cortex_m_semihosting::hprintln!("[{:<5}] {}: {}", level, module, args).ok();
+}
+
+/// Logs at `level`, see the module documentation.
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)+) => {{
+        let level: $crate::log::Level = $level;
+        if level as u8 <= $crate::log::STATIC_MAX_LEVEL
+            && $crate::log::enabled(level, module_path!())
+        {
+            $crate::log::write(level, module_path!(), format_args!($($arg)+));
+        }
+    }};
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => { $crate::log!($crate::log::Level::Error, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => { $crate::log!($crate::log::Level::Warn, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => { $crate::log!($crate::log::Level::Info, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => { $crate::log!($crate::log::Level::Debug, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)+) => { $crate::log!($crate::log::Level::Trace, $($arg)+) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_path_boundaries() {
+        assert!(matches("app", "app"));
+        assert!(matches("app", "app::shell"));
+        assert!(matches("app::shell", "app::shell"));
+        assert!(matches("app::shell", "app::shell::parse"));
+        assert!(!matches("app::sh", "app::shell"));
+        assert!(!matches("app::shell", "app"));
+        assert!(!matches("app::shell", "app::usart"));
+        assert!(!matches("timing", "app::timing"));
+    }
+
+    #[test]
+    fn longest_match() {
+        let filters = [("app", 1), ("app::shell", 4), ("app::sh", 5)];
+        assert_eq!(module_level(&filters, "app::shell"), Some(4));
+        assert_eq!(module_level(&filters, "app::shell::parse"), Some(4));
+        assert_eq!(module_level(&filters, "app::usart"), Some(1));
+        assert_eq!(module_level(&filters, "app::sh"), Some(5));
+        assert_eq!(module_level(&filters, "timing"), None);
+        assert_eq!(module_level(&[], "app"), None);
+    }
+}
@@ -0,0 +1,133 @@
+//! measure.rs
+//!
+//! Cycle counter measurements, corrected for the instrumentation overhead.
+//!
+//! Reading the cycle counter (CYCCNT) itself takes time, so a measured
+//! region appears longer than the code it encloses. At startup, call
+//! `calibrate` to measure an empty region, the overhead is then subtracted
+//! from all measurements. Both the raw and the corrected values are kept,
+//! and `set_mode` selects which of them are printed in traces.
+//!
+//! ```ignore
+//! measure::calibrate();
+//! let span = Span::start();
+//! work();
+//! let m = span.stop();
+//! rprintln!("work {}", m); // e.g., `work corrected=9998`
+//! ```
+
+use core::{
+    fmt,
+    sync::atomic::{compiler_fence, AtomicU32, AtomicU8, Ordering},
+};
+
+use cortex_m::peripheral::DWT;
+
+/// Number of empty region measurements used for calibration.
+const CALIBRATION_RUNS: usize = 16;
+
+static OVERHEAD: AtomicU32 = AtomicU32::new(0);
+static MODE: AtomicU8 = AtomicU8::new(Mode::Corrected as u8);
+
+/// Values to print in traces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Mode {
+    Raw = 0,
+    Corrected = 1,
+    Both = 2,
+}
+
+pub fn set_mode(mode: Mode) {
+    MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+pub fn mode() -> Mode {
+    match MODE.load(Ordering::Relaxed) {
+        0 => Mode::Raw,
+        1 => Mode::Corrected,
+        _ => Mode::Both,
+    }
+}
+
+/// Measures the overhead of an empty region (the minimum over a number of
+/// runs), stores it for later corrections, and returns it.
+///
+/// The cycle counter must be enabled.
+pub fn calibrate() -> u32 {
+    let overhead = (0..CALIBRATION_RUNS)
+        .map(|_| Span::start().elapsed())
+        .min()
+        .unwrap_or(0);
+    OVERHEAD.store(overhead, Ordering::Relaxed);
+    overhead
+}
+
+/// The calibrated overhead (0 if not calibrated).
+pub fn overhead() -> u32 {
+    OVERHEAD.load(Ordering::Relaxed)
+}
+
+/// A measurement in clock cycles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Measurement {
+    /// As read from the cycle counter.
+    pub raw: u32,
+    /// With the calibrated overhead subtracted.
+    pub corrected: u32,
+}
+
+impl Measurement {
+    /// Corrects a raw measurement.
+    pub fn new(raw: u32) -> Self {
+        Measurement {
+            raw,
+            corrected: raw.saturating_sub(overhead()),
+        }
+    }
+}
+
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match mode() {
+            Mode::Raw => write!(f, "raw={}", self.raw),
+            Mode::Corrected => write!(f, "corrected={}", self.corrected),
+            Mode::Both => write!(f, "raw={} corrected={}", self.raw, self.corrected),
+        }
+    }
+}
+
+/// The start of a measured region.
+#[derive(Clone, Copy, Debug)]
+pub struct Span {
+    start: u32,
+}
+
+impl Span {
+    #[inline(always)]
+    pub fn start() -> Self {
+        let start = DWT::cycle_count();
+        compiler_fence(Ordering::SeqCst);
+        Span { start }
+    }
+
+    /// Raw cycles since the start.
+    #[inline(always)]
+    pub fn elapsed(&self) -> u32 {
+        compiler_fence(Ordering::SeqCst);
+        DWT::cycle_count().wrapping_sub(self.start)
+    }
+
+    #[inline(always)]
+    pub fn stop(self) -> Measurement {
+        Measurement::new(self.elapsed())
+    }
+}
+
+/// Measures the execution of `f`.
+#[inline(always)]
+pub fn measure<R>(f: impl FnOnce() -> R) -> (R, Measurement) {
+    let span = Span::start();
+    let r = f();
+    (r, span.stop())
+}
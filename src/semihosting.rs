@@ -0,0 +1,121 @@
+//! semihosting.rs
+//!
+//! Host file I/O and exit status, over semihosting.
+//!
+//! With semihosting, the target asks the debugger (openocd, or QEMU with
+//! `-semihosting`) to perform I/O on the host, by executing `bkpt 0xab`.
+//! Here it is used to write measurement results directly to host files,
+//! and to end the session with a status, so a test run is visible to
+//! scripts.
+//!
+//! ```ignore
+//! let mut file = File::create("response_times.md")?;
+//! writeln!(file, "| task | response time |")?;
+//! semihosting::exit(passed);
+//! ```
+//!
+//! Relative paths are relative to the working directory of the debugger.
+//! Semihosting requires a debugger to be attached (`monitor arm semihosting
+//! enable`, see `openocd.gdb`), otherwise the `bkpt` raises a HardFault.
+//! Each call halts the core for milliseconds, so keep it out of timing
+//! measurements.
+//!
+//! The exit status reaches the shell only if the debugger exits: QEMU
+//! does, and so does openocd, but only when no gdb is connected. With gdb
+//! attached (e.g., the `openocd.gdb` runner of `cargo run`), openocd prints
+//! `semihosting: *** application exited with N ***`, halts the core and
+//! keeps running. To script a test, run openocd alone (see
+//! `examples/test_report.rs`):
+//!
+//! ```text
+//! > timeout 60 openocd -f openocd.cfg -c "init; arm semihosting enable" -c "program <elf> verify reset"
+//! ```
+//!
+//! (`timeout` shuts openocd down, with status 124, if the target never
+//! exits.)
+
+use core::fmt;
+
+use cortex_m_semihosting::{debug, nr, syscall};
+
+/// Maximum path length.
+pub const MAX_PATH: usize = 64;
+
+/// A failed (or unsupported) semihosting operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Error;
+
+impl From<fmt::Error> for Error {
+    fn from(_: fmt::Error) -> Self {
+        Error
+    }
+}
+
+/// A file on the host, closed when dropped.
+pub struct File {
+    fd: usize,
+}
+
+impl File {
+    /// Creates (or truncates) the file at `path`.
+    pub fn create(path: &str) -> Result<File, Error> {
+        File::open(path, nr::open::W_TRUNC)
+    }
+
+    /// Opens the file at `path` for appending, creating it if needed.
+    pub fn append(path: &str) -> Result<File, Error> {
+        File::open(path, nr::open::W_APPEND)
+    }
+
+    fn open(path: &str, mode: usize) -> Result<File, Error> {
+        // the path is passed NUL terminated
+        let mut name = [0u8; MAX_PATH + 1];
+        let path = path.as_bytes();
+        if path.len() > MAX_PATH {
+            return Err(Error);
+        }
+        name[..path.len()].copy_from_slice(path);
+
+        // NOTE(unsafe) `name` outlives the call
+        match unsafe { syscall!(OPEN, name.as_ptr(), mode, path.len()) } as isize {
+            -1 => Err(Error),
+            fd => Ok(File { fd: fd as usize }),
+        }
+    }
+
+    pub fn write_all(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        // NOTE(unsafe) `bytes` outlives the call, returns the number of
+        // bytes NOT written
+        match unsafe { syscall!(WRITE, self.fd, bytes.as_ptr(), bytes.len()) } {
+            0 => Ok(()),
+            _ => Err(Error),
+        }
+    }
+}
+
+impl fmt::Write for File {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        // NOTE(unsafe) the descriptor is not used after this
+        unsafe { syscall!(CLOSE, self.fd) };
+    }
+}
+
+/// Ends the session, the debugger (QEMU, or openocd without gdb attached)
+/// exits with status 0 if `success`, 1 otherwise.
+pub fn exit(success: bool) -> ! {
+    debug::exit(if success {
+        debug::EXIT_SUCCESS
+    } else {
+        debug::EXIT_FAILURE
+    });
+    // reached if the debugger keeps running (openocd with gdb attached)
+    loop {
+        continue;
+    }
+}
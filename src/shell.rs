@@ -0,0 +1,307 @@
+//! shell.rs
+//!
+//! A line oriented command shell.
+//!
+//! Bytes (e.g., from the virtual COM port) are fed to a `LineBuffer`, which
+//! hands out complete lines. `dispatch` parses a line into a `Command`, and
+//! runs it on an application provided `Handler`, writing the output to any
+//! `fmt::Write`. Nothing here touches the hardware, so the shell can be run
+//! on the host.
+//!
+//! ```ignore
+//! if let Some(line) = line_buffer.feed(byte) {
+//!     shell::dispatch(line, &mut handler, &mut out).ok();
+//! }
+//! ```
+
+use core::{fmt, str};
+
+/// The commands, along with their help text.
+pub const COMMANDS: [(&str, &str); 6] = [
+    ("help", "list commands"),
+    ("stats", "response time and lock statistics"),
+    ("reset-stats", "clear all statistics"),
+    ("tasks", "the task set"),
+    ("uptime", "time since reset"),
+    ("mem", "static memory and stack usage"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Help,
+    Stats,
+    ResetStats,
+    Tasks,
+    Uptime,
+    Mem,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseError<'a> {
+    /// An empty (or blank) line.
+    Empty,
+    Unknown(&'a str),
+    /// The command takes no arguments.
+    UnexpectedArgument(&'a str),
+}
+
+impl fmt::Display for ParseError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => Ok(()),
+            ParseError::Unknown(cmd) => write!(f, "unknown command `{}`, try `help`", cmd),
+            ParseError::UnexpectedArgument(arg) => write!(f, "unexpected argument `{}`", arg),
+        }
+    }
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, ParseError<'_>> {
+        let mut words = line.split_whitespace();
+        let cmd = match words.next().ok_or(ParseError::Empty)? {
+            "help" | "?" => Command::Help,
+            "stats" => Command::Stats,
+            "reset-stats" => Command::ResetStats,
+            "tasks" => Command::Tasks,
+            "uptime" => Command::Uptime,
+            "mem" => Command::Mem,
+            other => return Err(ParseError::Unknown(other)),
+        };
+        match words.next() {
+            Some(arg) => Err(ParseError::UnexpectedArgument(arg)),
+            None => Ok(cmd),
+        }
+    }
+}
+
+/// The application side of the commands.
+pub trait Handler {
+    fn stats(&mut self, out: &mut dyn fmt::Write) -> fmt::Result;
+
+    fn reset_stats(&mut self);
+
+    fn tasks(&mut self, out: &mut dyn fmt::Write) -> fmt::Result;
+
+    /// Time since reset, in clock cycles.
+    fn uptime(&mut self) -> u64;
+
+    /// Core clock frequency, used to print the uptime.
+    fn clock_hz(&self) -> u32;
+
+    fn mem(&mut self, out: &mut dyn fmt::Write) -> fmt::Result;
+}
+
+/// Parses and runs `line`, writes the output (or error) to `out`.
+pub fn dispatch(line: &str, handler: &mut impl Handler, out: &mut impl fmt::Write) -> fmt::Result {
+    let cmd = match Command::parse(line) {
+        Ok(cmd) => cmd,
+        Err(ParseError::Empty) => return Ok(()),
+        Err(e) => return write!(out, "{}\r\n", e),
+    };
+
+    match cmd {
+        Command::Help => {
+            for (name, help) in COMMANDS.iter() {
+                write!(out, "{:<12} {}\r\n", name, help)?;
+            }
+            Ok(())
+        }
+        Command::Stats => handler.stats(out),
+        Command::ResetStats => {
+            handler.reset_stats();
+            write!(out, "statistics cleared\r\n")
+        }
+        Command::Tasks => handler.tasks(out),
+        Command::Uptime => {
+            let cycles = handler.uptime();
+            let hz = handler.clock_hz() as u64;
+            write!(
+                out,
+                "{}.{:03} s ({} cycles)\r\n",
+                cycles / hz,
+                cycles % hz * 1000 / hz,
+                cycles
+            )
+        }
+        Command::Mem => handler.mem(out),
+    }
+}
+
+/// Collects bytes into lines of up to `N` bytes.
+///
+/// Lines end with CR or LF (empty lines are skipped, so CR LF counts as
+/// one), backspace and delete remove the last byte. Bytes beyond `N` are
+/// dropped.
+pub struct LineBuffer<const N: usize> {
+    buffer: [u8; N],
+    len: usize,
+    complete: bool,
+}
+
+impl<const N: usize> Default for LineBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> LineBuffer<N> {
+    pub const fn new() -> Self {
+        LineBuffer {
+            buffer: [0; N],
+            len: 0,
+            complete: false,
+        }
+    }
+
+    /// Adds `byte`, returns the line if complete.
+    ///
+    /// (A line which is not valid UTF-8 is returned as an empty line.)
+    pub fn feed(&mut self, byte: u8) -> Option<&str> {
+        if self.complete {
+            self.len = 0;
+            self.complete = false;
+        }
+        match byte {
+            b'\r' | b'\n' if self.len > 0 => {
+                self.complete = true;
+                return Some(str::from_utf8(&self.buffer[..self.len]).unwrap_or(""));
+            }
+            b'\r' | b'\n' => {}
+            0x08 | 0x7f => self.len = self.len.saturating_sub(1),
+            _ if self.len < N => {
+                self.buffer[self.len] = byte;
+                self.len += 1;
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed<const N: usize>(buffer: &mut LineBuffer<N>, bytes: &[u8]) -> Vec<String> {
+        bytes
+            .iter()
+            .filter_map(|&b| buffer.feed(b).map(String::from))
+            .collect()
+    }
+
+    #[test]
+    fn lines_end_with_cr_or_lf() {
+        let mut buffer = LineBuffer::<16>::new();
+        assert_eq!(
+            feed(&mut buffer, b"stats\r\nmem\nuptime\r"),
+            ["stats", "mem", "uptime"]
+        );
+        // the LF of CR LF is an empty line, skipped
+        assert_eq!(feed(&mut buffer, b"\r\n\r\n"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn backspace_and_delete() {
+        let mut buffer = LineBuffer::<16>::new();
+        assert_eq!(feed(&mut buffer, b"statx\x08s\r"), ["stats"]);
+        assert_eq!(feed(&mut buffer, b"mex\x7f\x7fem\r"), ["mem"]);
+        // on an empty line
+        assert_eq!(feed(&mut buffer, b"\x08\x7fhelp\r"), ["help"]);
+    }
+
+    #[test]
+    fn overflow_is_dropped() {
+        let mut buffer = LineBuffer::<4>::new();
+        assert_eq!(feed(&mut buffer, b"uptime\r"), ["upti"]);
+        // the next line starts over
+        assert_eq!(feed(&mut buffer, b"mem\r"), ["mem"]);
+        // backspace removes from the stored bytes
+        assert_eq!(feed(&mut buffer, b"statsx\x08\r"), ["sta"]);
+    }
+
+    #[test]
+    fn invalid_utf8_is_an_empty_line() {
+        let mut buffer = LineBuffer::<4>::new();
+        assert_eq!(feed(&mut buffer, b"\xff\xfe\r"), [""]);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(Command::parse("  stats "), Ok(Command::Stats));
+        assert_eq!(Command::parse("?"), Ok(Command::Help));
+        assert_eq!(Command::parse(" \t"), Err(ParseError::Empty));
+        assert_eq!(Command::parse("stat"), Err(ParseError::Unknown("stat")));
+        assert_eq!(
+            Command::parse("mem all"),
+            Err(ParseError::UnexpectedArgument("all"))
+        );
+    }
+
+    #[derive(Default)]
+    struct Mock {
+        resets: usize,
+    }
+
+    impl Handler for Mock {
+        fn stats(&mut self, out: &mut dyn fmt::Write) -> fmt::Result {
+            write!(out, "t1 max 40725\r\n")
+        }
+
+        fn reset_stats(&mut self) {
+            self.resets += 1;
+        }
+
+        fn tasks(&mut self, out: &mut dyn fmt::Write) -> fmt::Result {
+            write!(out, "t1 t2 t3\r\n")
+        }
+
+        fn uptime(&mut self) -> u64 {
+            // 2.5 s at 16 MHz
+            40_000_000
+        }
+
+        fn clock_hz(&self) -> u32 {
+            16_000_000
+        }
+
+        fn mem(&mut self, out: &mut dyn fmt::Write) -> fmt::Result {
+            write!(out, "stack 512\r\n")
+        }
+    }
+
+    fn run(line: &str, handler: &mut Mock) -> String {
+        let mut out = String::new();
+        dispatch(line, handler, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn dispatch_commands() {
+        let mut handler = Mock::default();
+        assert_eq!(run("stats", &mut handler), "t1 max 40725\r\n");
+        assert_eq!(run("tasks", &mut handler), "t1 t2 t3\r\n");
+        assert_eq!(run("mem", &mut handler), "stack 512\r\n");
+        assert_eq!(run("uptime", &mut handler), "2.500 s (40000000 cycles)\r\n");
+        assert_eq!(run("reset-stats", &mut handler), "statistics cleared\r\n");
+        assert_eq!(handler.resets, 1);
+
+        let help = run("help", &mut handler);
+        assert_eq!(help.lines().count(), COMMANDS.len());
+        assert!(help.starts_with("help         list commands\r\n"));
+    }
+
+    #[test]
+    fn dispatch_errors() {
+        let mut handler = Mock::default();
+        assert_eq!(run("", &mut handler), "");
+        assert_eq!(
+            run("reboot", &mut handler),
+            "unknown command `reboot`, try `help`\r\n"
+        );
+        assert_eq!(
+            run("reset-stats now", &mut handler),
+            "unexpected argument `now`\r\n"
+        );
+        assert_eq!(handler.resets, 0);
+    }
+}
@@ -0,0 +1,126 @@
+//! spawn.rs
+//!
+//! Instrumentation of RTIC software tasks.
+//!
+//! Messages are wrapped in `Stamped<T>`, which records the cycle counter
+//! (CYCCNT) at spawn. The receiving task can then compute the spawn to
+//! start latency (dispatch, plus the time spent waiting in the queue).
+//!
+//! `SpawnStats` counts successful and failed spawns (the message queue was
+//! full, i.e., more than `capacity` messages pending), and the latency of
+//! received messages. It is built on atomics, so it can be shared as a
+//! `static` between tasks at any priority.
+
+use core::{
+    fmt,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use cortex_m::peripheral::DWT;
+
+/// A message along with the time it was spawned.
+#[derive(Clone, Copy, Debug)]
+pub struct Stamped<T> {
+    pub msg: T,
+    pub spawned: u32,
+}
+
+impl<T> Stamped<T> {
+    pub fn new(msg: T) -> Self {
+        Stamped {
+            msg,
+            spawned: DWT::cycle_count(),
+        }
+    }
+
+    /// Cycles since the message was spawned.
+    pub fn latency(&self) -> u32 {
+        DWT::cycle_count().wrapping_sub(self.spawned)
+    }
+}
+
+pub struct SpawnStats {
+    spawned: AtomicU32,
+    failed: AtomicU32,
+    received: AtomicU32,
+    min_latency: AtomicU32,
+    max_latency: AtomicU32,
+}
+
+impl Default for SpawnStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpawnStats {
+    pub const fn new() -> Self {
+        SpawnStats {
+            spawned: AtomicU32::new(0),
+            failed: AtomicU32::new(0),
+            received: AtomicU32::new(0),
+            min_latency: AtomicU32::new(u32::MAX),
+            max_latency: AtomicU32::new(0),
+        }
+    }
+
+    /// Counts the outcome of a spawn, and passes it on.
+    ///
+    /// ```ignore
+    /// if let Err(msg) = STATS.spawn(cx.spawn.consumer(Stamped::new(msg))) {
+    ///     // queue full, `msg` is handed back
+    /// }
+    /// ```
+    pub fn spawn<T>(&self, r: Result<(), T>) -> Result<(), T> {
+        match r {
+            Ok(()) => self.spawned.fetch_add(1, Ordering::Relaxed),
+            Err(_) => self.failed.fetch_add(1, Ordering::Relaxed),
+        };
+        r
+    }
+
+    /// Records the reception of a message, returns its latency.
+    pub fn receive<T>(&self, msg: &Stamped<T>) -> u32 {
+        let latency = msg.latency();
+        self.received.fetch_add(1, Ordering::Relaxed);
+        self.min_latency.fetch_min(latency, Ordering::Relaxed);
+        self.max_latency.fetch_max(latency, Ordering::Relaxed);
+        latency
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let received = self.received.load(Ordering::Relaxed);
+        Snapshot {
+            spawned: self.spawned.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            received,
+            min_latency: if received == 0 {
+                0
+            } else {
+                self.min_latency.load(Ordering::Relaxed)
+            },
+            max_latency: self.max_latency.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The statistics at a given time.
+#[derive(Clone, Copy, Debug)]
+pub struct Snapshot {
+    pub spawned: u32,
+    /// Spawns rejected due to a full queue.
+    pub failed: u32,
+    pub received: u32,
+    pub min_latency: u32,
+    pub max_latency: u32,
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "spawned {}, failed {}, received {}, latency min/max {}/{}",
+            self.spawned, self.failed, self.received, self.min_latency, self.max_latency
+        )
+    }
+}
@@ -0,0 +1,211 @@
+//! spsc.rs
+//!
+//! Lock-free single-producer single-consumer queue.
+//!
+//! The queue is split into a `Producer` and a `Consumer` handle, which can
+//! be handed out as separate RTIC resources, e.g., the producer to an
+//! interrupt task and the consumer to `idle`. Each handle is owned by a
+//! single task, so no lock (no BASEPRI critical section) is needed, the
+//! handles synchronize through the (atomic) `head` and `tail` indices.
+//!
+//! ```ignore
+//! #[init]
+//! fn init(_cx: init::Context) -> init::LateResources {
+//!     static mut Q: Queue<u32, 8> = Queue::new();
+//!     let (producer, consumer) = Q.split();
+//!     init::LateResources { producer, consumer }
+//! }
+//! ```
+//!
+//! One slot is kept empty to tell a full queue from an empty one, thus a
+//! `Queue<T, N>` holds at most `N - 1` elements (like `heapless` 0.5).
+
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    ptr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// A fixed capacity queue, holding up to `N - 1` elements of type `T`.
+pub struct Queue<T, const N: usize> {
+    /// Index of the next element to dequeue (written by the consumer).
+    head: AtomicUsize,
+    /// Index of the next free slot (written by the producer).
+    tail: AtomicUsize,
+    buffer: UnsafeCell<MaybeUninit<[T; N]>>,
+}
+
+impl<T, const N: usize> Default for Queue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Queue<T, N> {
+    pub const fn new() -> Self {
+        Queue {
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            buffer: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Maximum number of elements.
+    pub const fn capacity(&self) -> usize {
+        N - 1
+    }
+
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+        (tail + N - head) % N
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    /// Splits the queue into its producer and consumer ends.
+    pub fn split(&mut self) -> (Producer<'_, T, N>, Consumer<'_, T, N>) {
+        (Producer { queue: self }, Consumer { queue: self })
+    }
+
+    #[inline(always)]
+    fn slot(&self, index: usize) -> *mut T {
+        (self.buffer.get() as *mut T).wrapping_add(index)
+    }
+}
+
+impl<T, const N: usize> Drop for Queue<T, N> {
+    fn drop(&mut self) {
+        let (_, mut consumer) = self.split();
+        while consumer.dequeue().is_some() {}
+    }
+}
+
+/// The producer end of a `Queue`.
+pub struct Producer<'a, T, const N: usize> {
+    queue: &'a Queue<T, N>,
+}
+
+// NOTE(unsafe) the producer is the only writer of `tail` and of the free
+// slots, so it can be moved to another task (context)
+unsafe impl<'a, T: Send, const N: usize> Send for Producer<'a, T, N> {}
+
+impl<'a, T, const N: usize> Producer<'a, T, N> {
+    /// Adds `value` at the back of the queue, hands it back if the queue is
+    /// full.
+    #[inline(always)]
+    pub fn enqueue(&mut self, value: T) -> Result<(), T> {
+        let tail = self.queue.tail.load(Ordering::Relaxed);
+        let next = (tail + 1) % N;
+        if next == self.queue.head.load(Ordering::Acquire) {
+            return Err(value);
+        }
+        // NOTE(unsafe) the slot at `tail` is free, and not visible to the
+        // consumer until `tail` is updated
+        unsafe { ptr::write(self.queue.slot(tail), value) };
+        self.queue.tail.store(next, Ordering::Release);
+        Ok(())
+    }
+
+    pub fn ready(&self) -> bool {
+        !self.queue.is_full()
+    }
+}
+
+/// The consumer end of a `Queue`.
+pub struct Consumer<'a, T, const N: usize> {
+    queue: &'a Queue<T, N>,
+}
+
+// NOTE(unsafe) the consumer is the only writer of `head` and the only
+// reader of the occupied slots, so it can be moved to another task (context)
+unsafe impl<'a, T: Send, const N: usize> Send for Consumer<'a, T, N> {}
+
+impl<'a, T, const N: usize> Consumer<'a, T, N> {
+    /// Removes the element at the front of the queue, if any.
+    #[inline(always)]
+    pub fn dequeue(&mut self) -> Option<T> {
+        let head = self.queue.head.load(Ordering::Relaxed);
+        if head == self.queue.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        // NOTE(unsafe) the slot at `head` was written by the producer before
+        // `tail` was updated, and is not reused until `head` is updated
+        let value = unsafe { ptr::read(self.queue.slot(head)) };
+        self.queue.head.store((head + 1) % N, Ordering::Release);
+        Some(value)
+    }
+
+    pub fn ready(&self) -> bool {
+        !self.queue.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn fifo_and_capacity() {
+        let mut q: Queue<u32, 4> = Queue::new();
+        assert_eq!(q.capacity(), 3);
+        let (mut p, mut c) = q.split();
+        assert!(c.is_empty() && !c.ready() && p.ready());
+        for i in 0..3 {
+            assert_eq!(p.enqueue(i), Ok(()));
+        }
+        // one slot is kept free
+        assert_eq!(p.enqueue(3), Err(3));
+        assert!(!p.ready());
+        assert_eq!(c.len(), 3);
+        for i in 0..3 {
+            assert_eq!(c.dequeue(), Some(i));
+        }
+        assert_eq!(c.dequeue(), None);
+    }
+
+    #[test]
+    fn wraps_around() {
+        let mut q: Queue<u32, 3> = Queue::new();
+        let (mut p, mut c) = q.split();
+        for i in 0..10 {
+            assert_eq!(p.enqueue(i), Ok(()));
+            assert_eq!(p.enqueue(i + 100), Ok(()));
+            assert_eq!(c.len(), 2);
+            assert_eq!(c.dequeue(), Some(i));
+            assert_eq!(c.dequeue(), Some(i + 100));
+        }
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn drops_remaining_elements() {
+        let rc = Rc::new(());
+        {
+            let mut q: Queue<Rc<()>, 4> = Queue::new();
+            let (mut p, mut c) = q.split();
+            for _ in 0..3 {
+                p.enqueue(rc.clone()).unwrap();
+            }
+            drop(c.dequeue());
+            assert_eq!(Rc::strong_count(&rc), 3);
+        }
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+}
@@ -0,0 +1,66 @@
+//! stack.rs
+//!
+//! Stack usage measurement, by painting.
+//!
+//! RTIC tasks all run on the single (main) stack, which grows down from
+//! the end of RAM (`_stack_start`) towards the statically allocated data
+//! (ending at `__sheap`, see the `cortex-m-rt` linker script). `paint`
+//! fills the unused part with a known pattern, `usage` later finds the
+//! deepest word overwritten, i.e., the high-water mark of the stack.
+
+use core::ptr;
+
+use cortex_m::register::msp;
+
+const PATTERN: u32 = 0xdead_beef;
+
+/// Start of RAM (see `memory.x`).
+const RAM_START: usize = 0x2000_0000;
+
+/// Bytes below the current stack pointer left unpainted (the frame of
+/// `paint` itself).
+const MARGIN: usize = 64;
+
+extern "C" {
+    static mut __sheap: u32;
+    static _stack_start: u32;
+}
+
+/// RAM used by static data (`.data`, `.bss` and `.uninit`).
+pub fn static_size() -> usize {
+    ptr::addr_of!(__sheap) as usize - RAM_START
+}
+
+/// The space available for the stack, in bytes.
+pub fn size() -> usize {
+    ptr::addr_of!(_stack_start) as usize - ptr::addr_of!(__sheap) as usize
+}
+
+/// Paints the free stack.
+///
+/// Call once, early (e.g., first thing in `init`).
+pub fn paint() {
+    let sp = msp::read() as usize - MARGIN;
+    // NOTE(unsafe) the area between the static data and the stack pointer
+    // (minus a margin) is not in use
+    unsafe {
+        let mut p = ptr::addr_of_mut!(__sheap);
+        while (p as usize) < sp {
+            ptr::write_volatile(p, PATTERN);
+            p = p.add(1);
+        }
+    }
+}
+
+/// The maximum stack usage since `paint`, in bytes.
+pub fn usage() -> usize {
+    // NOTE(unsafe) reads of RAM below the stack pointer
+    unsafe {
+        let mut p = ptr::addr_of!(__sheap);
+        let end = ptr::addr_of!(_stack_start);
+        while p < end && ptr::read_volatile(p) == PATTERN {
+            p = p.add(1);
+        }
+        end as usize - p as usize
+    }
+}
@@ -0,0 +1,114 @@
+//! task_set.rs
+//!
+//! Periodic task sets, as used by the examples and their analysis.
+//!
+//! Times are in clock cycles. The functions are `const`, so a task set
+//! can size or parameterize an application at compile time:
+//!
+//! ```ignore
+//! const RUN_CYCLES: u64 = 50 * task_set::hyperperiod(&task_set::EXAM);
+//! ```
+
+/// A periodic task.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Task {
+    pub name: &'static str,
+    pub priority: u8,
+    /// Inter-arrival time.
+    pub period: u32,
+    /// Relative deadline.
+    pub deadline: u32,
+    /// Worst case execution time.
+    pub wcet: u32,
+}
+
+/// The task set of `timing_exam.rs`, also modelled (with its critical
+/// sections) by `tools/task_sets/exam.json`, kept in sync by the tests of
+/// `tools`.
+pub const EXAM: [Task; 3] = [
+    Task {
+        name: "t1",
+        priority: 1,
+        period: 100_000,
+        deadline: 100_000,
+        wcet: 10_000,
+    },
+    Task {
+        name: "t2",
+        priority: 2,
+        period: 200_000,
+        deadline: 200_000,
+        wcet: 30_000,
+    },
+    Task {
+        name: "t3",
+        priority: 3,
+        period: 50_000,
+        deadline: 50_000,
+        wcet: 30_000,
+    },
+];
+
+const fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+/// The least common multiple of the periods, after which the release
+/// pattern repeats (1 for an empty set).
+pub const fn hyperperiod(tasks: &[Task]) -> u64 {
+    let mut h = 1;
+    let mut i = 0;
+    while i < tasks.len() {
+        let p = tasks[i].period as u64;
+        h = h / gcd(h, p) * p;
+        i += 1;
+    }
+    h
+}
+
+/// Total utilization, in parts per million (sum of `wcet / period`).
+pub const fn utilization_ppm(tasks: &[Task]) -> u32 {
+    let mut u = 0;
+    let mut i = 0;
+    while i < tasks.len() {
+        u += tasks[i].wcet as u64 * 1_000_000 / tasks[i].period as u64;
+        i += 1;
+    }
+    u as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exam() {
+        assert_eq!(hyperperiod(&EXAM), 200_000);
+        // 10 % + 15 % + 60 %
+        assert_eq!(utilization_ppm(&EXAM), 850_000);
+    }
+
+    #[test]
+    fn hyperperiod_of_coprime_periods() {
+        let task = |period| Task {
+            name: "t",
+            priority: 1,
+            period,
+            deadline: period,
+            wcet: 1,
+        };
+        assert_eq!(hyperperiod(&[]), 1);
+        assert_eq!(hyperperiod(&[task(6), task(4)]), 12);
+        assert_eq!(hyperperiod(&[task(7), task(9), task(10)]), 630);
+        // beyond 32 bits
+        assert_eq!(
+            hyperperiod(&[task(u32::MAX), task(u32::MAX - 1)]),
+            u32::MAX as u64 * (u32::MAX - 1) as u64
+        );
+    }
+}
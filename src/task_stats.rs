@@ -0,0 +1,159 @@
+//! task_stats.rs
+//!
+//! Response time statistics of periodic tasks.
+//!
+//! The response time of a task instance is the time from its release
+//! (`cx.scheduled`) to its completion. `ResponseTime` keeps the min/max
+//! over all instances and counts deadline misses. It is built on atomics,
+//! so it can be a `static` updated by the task and read (or reset) by
+//! others, e.g., the shell (`shell.rs`):
+//!
+//! ```ignore
+//! static T1: ResponseTime = ResponseTime::new("t1", 100_000);
+//!
+//! // at the end of `t1`
+//! T1.record(cx.scheduled.elapsed().as_cycles());
+//! ```
+
+use core::{
+    fmt,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+pub struct ResponseTime {
+    pub name: &'static str,
+    /// Relative deadline (in clock cycles).
+    pub deadline: u32,
+    count: AtomicU32,
+    min: AtomicU32,
+    max: AtomicU32,
+    misses: AtomicU32,
+}
+
+impl ResponseTime {
+    pub const fn new(name: &'static str, deadline: u32) -> Self {
+        ResponseTime {
+            name,
+            deadline,
+            count: AtomicU32::new(0),
+            min: AtomicU32::new(u32::MAX),
+            max: AtomicU32::new(0),
+            misses: AtomicU32::new(0),
+        }
+    }
+
+    /// Records a response time, returns true if the deadline was missed.
+    pub fn record(&self, response_time: u32) -> bool {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.min.fetch_min(response_time, Ordering::Relaxed);
+        self.max.fetch_max(response_time, Ordering::Relaxed);
+        let missed = response_time > self.deadline;
+        if missed {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        missed
+    }
+
+    /// Number of recorded instances.
+    pub fn count(&self) -> u32 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Minimum response time, 0 if none recorded.
+    pub fn min(&self) -> u32 {
+        if self.count() == 0 {
+            0
+        } else {
+            self.min.load(Ordering::Relaxed)
+        }
+    }
+
+    pub fn max(&self) -> u32 {
+        self.max.load(Ordering::Relaxed)
+    }
+
+    /// Number of deadline misses.
+    pub fn misses(&self) -> u32 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        self.count.store(0, Ordering::Relaxed);
+        self.min.store(u32::MAX, Ordering::Relaxed);
+        self.max.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Display for ResponseTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} count {}, response time min/max {}/{}, deadline {}, misses {}",
+            self.name,
+            self.count(),
+            self.min(),
+            self.max(),
+            self.deadline,
+            self.misses()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let rt = ResponseTime::new("t1", 100);
+        assert_eq!((rt.count(), rt.min(), rt.max(), rt.misses()), (0, 0, 0, 0));
+    }
+
+    #[test]
+    fn record() {
+        let rt = ResponseTime::new("t1", 100);
+        assert!(!rt.record(40));
+        assert!(!rt.record(100));
+        assert!(!rt.record(20));
+        assert_eq!(
+            (rt.count(), rt.min(), rt.max(), rt.misses()),
+            (3, 20, 100, 0)
+        );
+
+        // only beyond the deadline is a miss
+        assert!(rt.record(101));
+        assert!(rt.record(u32::MAX));
+        assert_eq!(
+            (rt.count(), rt.min(), rt.max(), rt.misses()),
+            (5, 20, u32::MAX, 2)
+        );
+    }
+
+    #[test]
+    fn reset() {
+        let rt = ResponseTime::new("t1", 100);
+        rt.record(40);
+        rt.record(200);
+        rt.reset();
+        assert_eq!((rt.count(), rt.min(), rt.max(), rt.misses()), (0, 0, 0, 0));
+
+        // min starts over, it is not stuck at the earlier 40
+        rt.record(60);
+        assert_eq!(
+            (rt.count(), rt.min(), rt.max(), rt.misses()),
+            (1, 60, 60, 0)
+        );
+    }
+
+    #[test]
+    fn display() {
+        let rt = ResponseTime::new("t1", 100);
+        rt.record(40);
+        rt.record(120);
+        assert_eq!(
+            rt.to_string(),
+            "t1 count 2, response time min/max 40/120, deadline 100, misses 1"
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! testing.rs
+//!
+//! On-target tests.
+//!
+//! Test functions are collected by the `#[tests]` attribute (crate
+//! `macros`, re-exported as `app::tests`) and run by `run`, typically from
+//! `idle`, so the tasks of the application are live and timing or driver
+//! behaviour can be asserted on hardware:
+//!
+//! ```ignore
+//! #[app::tests]
+//! mod tests {
+//!     #[test]
+//!     fn wraps() {
+//!         assert_eq!(u32::MAX.wrapping_add(1), 0);
+//!     }
+//! }
+//!
+//! #[idle]
+//! fn idle(_cx: idle::Context) -> ! {
+//!     testing::run(tests::TESTS)
+//! }
+//!
+//! #[panic_handler]
+//! fn panic(info: &PanicInfo) -> ! {
+//!     testing::panicked(info)
+//! }
+//! ```
+//!
+//! Results are reported line by line, in the format of `cargo test`:
+//!
+//! ```text
+//! running 3 tests
+//! test tests::wraps ... ok
+//! test tests::queue ... FAILED: "full"
+//! test tests::slow ... ignored
+//! test result: FAILED. 1 passed; 1 failed; 1 ignored
+//! ```
+//!
+//! over RTT, or semihosting with the `test-semihosting` feature, in which
+//! case the debug session ends with the test status (as the exit status of
+//! openocd only without gdb attached, see `semihosting.rs`). The host runner
+//! (`tools`, `target_test` binary) aggregates the results.
+//!
+//! A failing assertion panics, there is no unwinding on the target, so
+//! the remaining tests are not run (the host runner reports them).
+
+use core::{
+    fmt::{self, Write},
+    panic::PanicInfo,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+/// A test, see `#[tests]`.
+pub struct Test {
+    pub name: &'static str,
+    pub ignore: bool,
+    /// Runs the test, returns false if it failed (the reason is written to
+    /// `out`).
+    pub run: fn(out: &mut dyn Write) -> bool,
+}
+
+/// Result of a test function.
+pub trait Outcome {
+    /// Returns true on success, otherwise writes the reason to `out`.
+    fn report(self, out: &mut dyn Write) -> bool;
+}
+
+impl Outcome for () {
+    fn report(self, _out: &mut dyn Write) -> bool {
+        true
+    }
+}
+
+impl<E: fmt::Debug> Outcome for Result<(), E> {
+    fn report(self, out: &mut dyn Write) -> bool {
+        match self {
+            Ok(()) => true,
+            Err(e) => {
+                write!(out, "{:?}", e).ok();
+                false
+            }
+        }
+    }
+}
+
+/// Test counts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub passed: u32,
+    pub failed: u32,
+    pub ignored: u32,
+}
+
+impl Summary {
+    pub fn success(&self) -> bool {
+        self.failed == 0
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "test result: {}. {} passed; {} failed; {} ignored",
+            if self.success() { "ok" } else { "FAILED" },
+            self.passed,
+            self.failed,
+            self.ignored
+        )
+    }
+}
+
+// kept in statics for `panicked`, a test is run at a time
+static PASSED: AtomicU32 = AtomicU32::new(0);
+static FAILED: AtomicU32 = AtomicU32::new(0);
+static IGNORED: AtomicU32 = AtomicU32::new(0);
+
+fn summary() -> Summary {
+    Summary {
+        passed: PASSED.load(Ordering::Relaxed),
+        failed: FAILED.load(Ordering::Relaxed),
+        ignored: IGNORED.load(Ordering::Relaxed),
+    }
+}
+
+/// Runs the tests, reporting to `out`, and returns the counts.
+pub fn run_with(tests: &[Test], out: &mut dyn Write) -> Summary {
+    let plural = if tests.len() == 1 { "" } else { "s" };
+    writeln!(out, "running {} test{}", tests.len(), plural).ok();
+    for test in tests {
+        write!(out, "test {} ... ", test.name).ok();
+        if test.ignore {
+            IGNORED.fetch_add(1, Ordering::Relaxed);
+            writeln!(out, "ignored").ok();
+            continue;
+        }
+
+        // a failure reason follows, `panicked` completes the line
+        let mut reason = Reason {
+            out: &mut *out,
+            started: false,
+        };
+        let passed = (test.run)(&mut reason);
+        let started = reason.started;
+        if passed {
+            PASSED.fetch_add(1, Ordering::Relaxed);
+            writeln!(out, "ok").ok();
+        } else {
+            FAILED.fetch_add(1, Ordering::Relaxed);
+            if !started {
+                write!(out, "FAILED").ok();
+            }
+            writeln!(out).ok();
+        }
+    }
+    let summary = summary();
+    writeln!(out, "{}", summary).ok();
+    summary
+}
+
+/// Runs the tests, reporting on the console, and ends the test session.
+pub fn run(tests: &[Test]) -> ! {
+    let summary = run_with(tests, &mut Console);
+    exit(summary.success())
+}
+
+/// Reports the running test as failed, along with the panic message, and
+/// ends the test session. Call from the `#[panic_handler]`.
+pub fn panicked(info: &PanicInfo) -> ! {
+    FAILED.fetch_add(1, Ordering::Relaxed);
+    // the test name is already written
+    writeln!(Console, "FAILED: {}", info).ok();
+    writeln!(Console, "{}", summary()).ok();
+    exit(false)
+}
+
+/// Prefixes the first write of a failure reason.
+struct Reason<'a> {
+    out: &'a mut dyn Write,
+    started: bool,
+}
+
+impl Write for Reason<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if !self.started {
+            self.started = true;
+            self.out.write_str("FAILED: ")?;
+        }
+        self.out.write_str(s)
+    }
+}
+
+/// Test output, RTT (channel 0, initialized by the application), or
+/// semihosting with the `test-semihosting` feature.
+pub struct Console;
+
+impl Write for Console {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        #[cfg(not(feature = "test-semihosting"))]
+        rtt_target::rprint!("{}", s);
+
+        #[cfg(feature = "test-semihosting")]
+        cortex_m_semihosting::hprint!("{}", s).map_err(|_| fmt::Error)?;
+
+        Ok(())
+    }
+}
+
+/// Ends the test session. Over semihosting the debugger exits with the test
+/// status (see `semihosting::exit`), over RTT the core is halted at a
+/// breakpoint.
+fn exit(success: bool) -> ! {
+    #[cfg(feature = "test-semihosting")]
+    crate::semihosting::exit(success);
+
+    #[cfg(not(feature = "test-semihosting"))]
+    {
+        let _ = success;
+        loop {
+            cortex_m::asm::bkpt();
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! uptime.rs
+//!
+//! Time since reset, extending the 32 bit cycle counter (CYCCNT) to 64 bits.
+//!
+//! The counter wraps every 2^32 cycles (268 s at 16 MHz). `Uptime` counts
+//! the wraps, given that it is updated at least once per counter period:
+//!
+//! ```ignore
+//! let mut uptime = Uptime::new(DWT::cycle_count());
+//! // periodically, e.g., in `idle`
+//! let cycles = uptime.update(DWT::cycle_count());
+//! ```
+//!
+//! The counter value is passed in, so the arithmetic runs on the host.
+
+#[derive(Clone, Copy, Debug)]
+pub struct Uptime {
+    last: u32,
+    wraps: u32,
+}
+
+impl Uptime {
+    pub const fn new(now: u32) -> Self {
+        Uptime {
+            last: now,
+            wraps: 0,
+        }
+    }
+
+    /// Updates with the current counter value, returns the cycles since
+    /// reset.
+    pub fn update(&mut self, now: u32) -> u64 {
+        if now < self.last {
+            self.wraps += 1;
+        }
+        self.last = now;
+        self.cycles()
+    }
+
+    /// Cycles since reset, as of the last update.
+    pub fn cycles(&self) -> u64 {
+        (self.wraps as u64) << 32 | self.last as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_wraps() {
+        let mut uptime = Uptime::new(u32::MAX - 10);
+        assert_eq!(uptime.cycles(), u32::MAX as u64 - 10);
+        assert_eq!(uptime.update(u32::MAX), u32::MAX as u64);
+        // wrapped
+        assert_eq!(uptime.update(5), (1 << 32) + 5);
+        assert_eq!(uptime.update(5), (1 << 32) + 5);
+        assert_eq!(uptime.update(3), (2 << 32) + 3);
+        assert_eq!(uptime.cycles(), (2 << 32) + 3);
+    }
+}
@@ -0,0 +1,480 @@
+//! usart.rs
+//!
+//! Interrupt driven USART driver.
+//!
+//! The driver is written against the `Registers` trait, a minimal view
+//! of the USART registers. It is implemented for the USART2 peripheral
+//! (the virtual COM port, see `bsp.rs`), and by `mock::Mock`, which lets
+//! the driver state machine run on the host without hardware.
+//!
+//! Reception: the RXNE interrupt is enabled at all times, each interrupt
+//! delivers one byte, or an error, from `on_interrupt`. Errors are counted
+//! (`errors`). On overrun and noise the byte in the data register is still
+//! valid, and carried by the error.
+//!
+//! Transmission: `write` queues bytes in a ring buffer and enables the TXE
+//! interrupt, `on_interrupt` then moves one byte at a time to the data
+//! register, and disables the TXE interrupt once the buffer is drained.
+//!
+//! Both directions share the USART interrupt, so a single task is bound to
+//! it, e.g.:
+//!
+//! ```ignore
+//! #[task(binds = USART2, resources = [serial], spawn = [received])]
+//! fn usart2(cx: usart2::Context) {
+//!     match cx.resources.serial.on_interrupt() {
+//!         Some(Ok(byte)) => { cx.spawn.received(byte).ok(); }
+//!         Some(Err(e)) => rprintln!("{:?}, byte {:?}", e, e.byte()),
+//!         None => {}
+//!     }
+//! }
+//! ```
+
+use core::fmt;
+
+use stm32f4::stm32f411::USART2;
+
+// status register (SR) flags
+const SR_FE: u32 = 1 << 1;
+const SR_NF: u32 = 1 << 2;
+const SR_ORE: u32 = 1 << 3;
+const SR_RXNE: u32 = 1 << 5;
+const SR_TXE: u32 = 1 << 7;
+
+// control register 1 (CR1) interrupt enables
+const CR1_RXNEIE: u32 = 1 << 5;
+const CR1_TXEIE: u32 = 1 << 7;
+
+/// The USART registers used by the driver.
+pub trait Registers {
+    /// Reads the status register (SR).
+    fn status(&self) -> u32;
+
+    /// Reads the data register (DR). Reading SR then DR clears RXNE and
+    /// the error flags.
+    fn read_data(&mut self) -> u8;
+
+    /// Writes the data register (DR), clearing TXE.
+    fn write_data(&mut self, byte: u8);
+
+    /// Enables or disables the interrupts given by the `CR1` `mask`.
+    fn listen(&mut self, mask: u32, enable: bool);
+
+    /// Returns true if the interrupts given by the `CR1` `mask` are enabled.
+    fn listening(&self, mask: u32) -> bool;
+}
+
+impl Registers for USART2 {
+    fn status(&self) -> u32 {
+        self.sr.read().bits()
+    }
+
+    fn read_data(&mut self) -> u8 {
+        self.dr.read().bits() as u8
+    }
+
+    fn write_data(&mut self, byte: u8) {
+        self.dr.write(|w| unsafe { w.bits(byte as u32) });
+    }
+
+    fn listen(&mut self, mask: u32, enable: bool) {
+        self.cr1.modify(|r, w| unsafe {
+            w.bits(if enable {
+                r.bits() | mask
+            } else {
+                r.bits() & !mask
+            })
+        });
+    }
+
+    fn listening(&self, mask: u32) -> bool {
+        self.cr1.read().bits() & mask == mask
+    }
+}
+
+/// Receive errors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// A byte arrived before the previous one was read, and was lost. The
+    /// previous one is kept, and carried.
+    Overrun(u8),
+    /// No stop bit was detected (wrong baud rate, or a break), the byte is
+    /// dropped.
+    Framing,
+    /// Noise was detected on the line, the byte (the bits taken by majority
+    /// of the samples) is carried.
+    Noise(u8),
+}
+
+impl Error {
+    /// The byte received despite the error, if valid.
+    pub fn byte(&self) -> Option<u8> {
+        match *self {
+            Error::Overrun(byte) | Error::Noise(byte) => Some(byte),
+            Error::Framing => None,
+        }
+    }
+}
+
+/// Error counts, by kind.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ErrorCounts {
+    pub overrun: u32,
+    pub framing: u32,
+    pub noise: u32,
+}
+
+impl fmt::Display for ErrorCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "overrun {}, framing {}, noise {}",
+            self.overrun, self.framing, self.noise
+        )
+    }
+}
+
+/// A fixed size byte ring buffer.
+struct Ring<const N: usize> {
+    buffer: [u8; N],
+    head: usize,
+    len: usize,
+}
+
+impl<const N: usize> Ring<N> {
+    const fn new() -> Self {
+        Ring {
+            buffer: [0; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, byte: u8) -> bool {
+        if self.len == N {
+            return false;
+        }
+        self.buffer[(self.head + self.len) % N] = byte;
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let byte = self.buffer[self.head];
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(byte)
+    }
+}
+
+/// The driver, with a transmit buffer of `N` bytes.
+pub struct Usart<R, const N: usize> {
+    regs: R,
+    tx: Ring<N>,
+    errors: ErrorCounts,
+}
+
+impl<R: Registers, const N: usize> Usart<R, N> {
+    /// Takes a configured (enabled) USART, and enables the RXNE interrupt.
+    pub fn new(mut regs: R) -> Self {
+        regs.listen(CR1_RXNEIE, true);
+        Usart {
+            regs,
+            tx: Ring::new(),
+            errors: ErrorCounts::default(),
+        }
+    }
+
+    /// Queues `bytes` for transmission, returns the number queued (fewer
+    /// than `bytes.len()` if the buffer is full).
+    pub fn write(&mut self, bytes: &[u8]) -> usize {
+        let queued = bytes.iter().take_while(|b| self.tx.push(**b)).count();
+        if queued > 0 {
+            self.regs.listen(CR1_TXEIE, true);
+        }
+        queued
+    }
+
+    /// Number of bytes waiting for transmission.
+    pub fn pending(&self) -> usize {
+        self.tx.len
+    }
+
+    /// Handles a USART interrupt, to be called from the bound task.
+    ///
+    /// Moves the next queued byte (if any) to the transmitter, and returns
+    /// the received byte or error (if any). A framing error takes
+    /// precedence over overrun, and overrun over noise, all are counted.
+    pub fn on_interrupt(&mut self) -> Option<Result<u8, Error>> {
+        let sr = self.regs.status();
+
+        if sr & SR_TXE != 0 && self.regs.listening(CR1_TXEIE) {
+            match self.tx.pop() {
+                Some(byte) => self.regs.write_data(byte),
+                None => self.regs.listen(CR1_TXEIE, false),
+            }
+        }
+
+        if sr & (SR_RXNE | SR_ORE | SR_FE | SR_NF) == 0 {
+            return None;
+        }
+        // reading DR (after SR) clears the flags
+        let byte = self.regs.read_data();
+        // on overrun, DR holds the byte received before the lost one
+        if sr & SR_ORE != 0 {
+            self.errors.overrun += 1;
+        }
+        if sr & SR_NF != 0 {
+            self.errors.noise += 1;
+        }
+        if sr & SR_FE != 0 {
+            // the byte is not trusted, it is dropped
+            self.errors.framing += 1;
+            Some(Err(Error::Framing))
+        } else if sr & SR_RXNE == 0 {
+            None
+        } else if sr & SR_ORE != 0 {
+            Some(Err(Error::Overrun(byte)))
+        } else if sr & SR_NF != 0 {
+            Some(Err(Error::Noise(byte)))
+        } else {
+            Some(Ok(byte))
+        }
+    }
+
+    pub fn errors(&self) -> ErrorCounts {
+        self.errors
+    }
+
+    pub fn registers(&self) -> &R {
+        &self.regs
+    }
+
+    pub fn registers_mut(&mut self) -> &mut R {
+        &mut self.regs
+    }
+
+    /// Releases the registers.
+    pub fn free(self) -> R {
+        self.regs
+    }
+}
+
+/// Queues the string, fails if it does not fit the transmit buffer.
+impl<R: Registers, const N: usize> fmt::Write for Usart<R, N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.write(s.as_bytes()) == s.len() {
+            Ok(())
+        } else {
+            Err(fmt::Error)
+        }
+    }
+}
+
+pub mod mock {
+    //! A register level mock of the USART, for running the driver on the
+    //! host.
+    //!
+    //! The transmitter is instantaneous (TXE is always set), transmitted
+    //! bytes are recorded in `sent`. Received bytes (and errors) are
+    //! injected by `receive` (and `noise`, `framing`).
+    //!
+    //! ```ignore
+    //! let mut serial: Usart<Mock, 8> = Usart::new(Mock::new());
+    //! serial.registers_mut().receive(b'a');
+    //! assert_eq!(serial.on_interrupt(), Some(Ok(b'a')));
+    //! serial.write(b"hi");
+    //! while serial.registers().interrupt() {
+    //!     serial.on_interrupt();
+    //! }
+    //! assert_eq!(serial.registers().sent(), b"hi");
+    //! ```
+
+    use super::*;
+
+    pub struct Mock {
+        pub sr: u32,
+        pub cr1: u32,
+        pub rx: u8,
+        pub sent: [u8; 64],
+        pub sent_len: usize,
+    }
+
+    impl Default for Mock {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Mock {
+        pub const fn new() -> Self {
+            Mock {
+                sr: SR_TXE,
+                cr1: 0,
+                rx: 0,
+                sent: [0; 64],
+                sent_len: 0,
+            }
+        }
+
+        /// A byte arrives, or is lost if the previous one is unread
+        /// (overrun).
+        pub fn receive(&mut self, byte: u8) {
+            if self.sr & SR_RXNE != 0 {
+                self.sr |= SR_ORE;
+            } else {
+                self.rx = byte;
+                self.sr |= SR_RXNE;
+            }
+        }
+
+        /// A byte arrives with noise detected.
+        pub fn noise(&mut self, byte: u8) {
+            self.receive(byte);
+            self.sr |= SR_NF;
+        }
+
+        /// A byte arrives without a stop bit.
+        pub fn framing(&mut self, byte: u8) {
+            self.receive(byte);
+            self.sr |= SR_FE;
+        }
+
+        /// Returns true if the interrupt line is raised.
+        pub fn interrupt(&self) -> bool {
+            (self.cr1 & CR1_RXNEIE != 0 && self.sr & (SR_RXNE | SR_ORE) != 0)
+                || (self.cr1 & CR1_TXEIE != 0 && self.sr & SR_TXE != 0)
+        }
+
+        pub fn sent(&self) -> &[u8] {
+            &self.sent[..self.sent_len]
+        }
+    }
+
+    impl Registers for Mock {
+        fn status(&self) -> u32 {
+            self.sr
+        }
+
+        fn read_data(&mut self) -> u8 {
+            self.sr &= !(SR_RXNE | SR_ORE | SR_FE | SR_NF);
+            self.rx
+        }
+
+        fn write_data(&mut self, byte: u8) {
+            if self.sent_len < self.sent.len() {
+                self.sent[self.sent_len] = byte;
+                self.sent_len += 1;
+            }
+        }
+
+        fn listen(&mut self, mask: u32, enable: bool) {
+            if enable {
+                self.cr1 |= mask;
+            } else {
+                self.cr1 &= !mask;
+            }
+        }
+
+        fn listening(&self, mask: u32) -> bool {
+            self.cr1 & mask == mask
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mock::Mock, *};
+
+    fn serial() -> Usart<Mock, 4> {
+        Usart::new(Mock::new())
+    }
+
+    #[test]
+    fn receive() {
+        let mut serial = serial();
+        assert!(serial.registers().listening(CR1_RXNEIE));
+        assert_eq!(serial.on_interrupt(), None);
+        serial.registers_mut().receive(b'a');
+        assert!(serial.registers().interrupt());
+        assert_eq!(serial.on_interrupt(), Some(Ok(b'a')));
+        assert!(!serial.registers().interrupt());
+        assert_eq!(serial.on_interrupt(), None);
+    }
+
+    #[test]
+    fn transmit_drains_and_stops_listening() {
+        let mut serial = serial();
+        assert_eq!(serial.write(b"hello"), 4);
+        assert_eq!(serial.pending(), 4);
+        assert!(serial.registers().listening(CR1_TXEIE));
+        let mut interrupts = 0;
+        while serial.registers().interrupt() {
+            assert_eq!(serial.on_interrupt(), None);
+            interrupts += 1;
+        }
+        // one interrupt per byte, and one to disable TXEIE
+        assert_eq!(interrupts, 5);
+        assert_eq!(serial.registers().sent(), b"hell");
+        assert_eq!(serial.pending(), 0);
+        assert!(!serial.registers().listening(CR1_TXEIE));
+    }
+
+    #[test]
+    fn overrun_keeps_the_unread_byte() {
+        let mut serial = serial();
+        serial.registers_mut().receive(b'a');
+        serial.registers_mut().receive(b'b');
+        assert_eq!(serial.on_interrupt(), Some(Err(Error::Overrun(b'a'))));
+        assert_eq!(serial.errors().overrun, 1);
+        assert_eq!(serial.registers().status() & (SR_RXNE | SR_ORE), 0);
+        assert_eq!(serial.on_interrupt(), None);
+    }
+
+    #[test]
+    fn framing_error_drops_the_byte() {
+        let mut serial = serial();
+        serial.registers_mut().framing(b'x');
+        assert_eq!(serial.on_interrupt(), Some(Err(Error::Framing)));
+        assert_eq!(serial.errors().framing, 1);
+        serial.registers_mut().receive(b'y');
+        assert_eq!(serial.on_interrupt(), Some(Ok(b'y')));
+    }
+
+    #[test]
+    fn noise_carries_the_byte() {
+        let mut serial = serial();
+        serial.registers_mut().noise(b'n');
+        assert_eq!(serial.on_interrupt(), Some(Err(Error::Noise(b'n'))));
+        let errors = serial.errors();
+        assert_eq!((errors.overrun, errors.framing, errors.noise), (0, 0, 1));
+        assert_eq!(serial.on_interrupt(), None);
+    }
+
+    #[test]
+    fn precedence() {
+        // noise on the kept byte, then overrun
+        let mut serial = serial();
+        serial.registers_mut().noise(b'a');
+        serial.registers_mut().receive(b'b');
+        assert_eq!(serial.on_interrupt(), Some(Err(Error::Overrun(b'a'))));
+        let errors = serial.errors();
+        assert_eq!((errors.overrun, errors.framing, errors.noise), (1, 0, 1));
+
+        // framing on an overrun
+        serial.registers_mut().framing(b'c');
+        serial.registers_mut().receive(b'd');
+        assert_eq!(serial.on_interrupt(), Some(Err(Error::Framing)));
+        assert_eq!(serial.errors().overrun, 2);
+    }
+
+    #[test]
+    fn error_byte() {
+        assert_eq!(Error::Overrun(b'a').byte(), Some(b'a'));
+        assert_eq!(Error::Noise(b'n').byte(), Some(b'n'));
+        assert_eq!(Error::Framing.byte(), None);
+    }
+}
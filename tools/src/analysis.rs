@@ -0,0 +1,589 @@
+//! analysis.rs
+//!
+//! Schedulability analysis of task sets (see `task_set.rs`).
+//!
+//! The utilization based tests of Liu & Layland and the hyperbolic bound
+//! (Bini et al.) hold for rate monotonic priorities and deadlines equal
+//! to the periods. They are extended with the SRP blocking (Sha et al.):
+//! task `i` is schedulable if
+//!
+//! - `U_1 + .. + U_i + B_i / T_i <= i (2^(1/i) - 1)` (Liu & Layland), or
+//! - `(U_1 + 1) .. (U_i-1 + 1) (U_i + B_i / T_i + 1) <= 2` (hyperbolic),
+//!
+//! with the tasks `1 .. i` of priority higher than or equal to task `i`,
+//! and `B_i` the longest critical section of a lower priority task on a
+//! resource with a ceiling higher than or equal to its priority. The cost
+//! of a task includes the overhead of its release and dispatch.
+//!
+//! The tests are sufficient only: a failing test is inconclusive, while a
+//! total utilization above 1 is infeasible.
+//!
+//! The exact test is the response time analysis (`Rta`), the response time
+//! of task `i` is the fixed point of
+//!
+//! `R_i = C_i + B_i + sum(ceil(R_i / T_j) C_j)`
+//!
+//! over the tasks `j` of priority higher than or equal to task `i` (equal
+//! priorities run in release order, counting them is pessimistic). The
+//! lower priority tasks `k` are released by the timer queue handler, at the
+//! timer priority, which adds `sum(ceil(R_i / T_k) C_rel)` for a task `i`
+//! at or below the timer priority. It assumes deadlines within the
+//! periods, the iteration stops once `R_i` exceeds the deadline.
+
+use std::fmt;
+
+use crate::task_set::{Task, TaskSet};
+
+/// Execution time of a task instance, its release and dispatch included.
+pub fn cost(set: &TaskSet, task: &Task) -> u64 {
+    task.wcet() as u64 + set.overhead.release as u64 + set.overhead.dispatch as u64
+}
+
+/// The longest time a task can be blocked by lower priority tasks, under
+/// SRP at most one critical section.
+pub fn blocking(set: &TaskSet, task: &Task) -> u64 {
+    let ceilings = set.ceilings();
+    set.tasks
+        .iter()
+        .filter(|t| t.priority < task.priority)
+        .flat_map(|t| t.trace.sections())
+        .filter(|&(r, _)| ceilings[r] >= task.priority)
+        .map(|(_, hold)| hold as u64)
+        .max()
+        .unwrap_or(0)
+}
+
+/// The worst case response time of a task, `None` if above its deadline.
+pub fn response_time(set: &TaskSet, task: &Task) -> Option<u64> {
+    let own = cost(set, task) + blocking(set, task);
+    let interference: Vec<(u64, u64)> = set
+        .tasks
+        .iter()
+        .filter(|t| t.priority >= task.priority && t.name != task.name)
+        .map(|t| (cost(set, t), t.period as u64))
+        .chain(releases(set, task))
+        .collect();
+    let mut r = own + interference.iter().map(|&(c, _)| c).sum::<u64>();
+    loop {
+        if r > task.deadline as u64 {
+            return None;
+        }
+        let next = own
+            + interference
+                .iter()
+                .map(|&(c, t)| r.div_ceil(t) * c)
+                .sum::<u64>();
+        if next == r {
+            return Some(r);
+        }
+        r = next;
+    }
+}
+
+/// The releases of the lower priority tasks (cost, period), preempting
+/// `task` unless it runs above the timer priority.
+fn releases<'a>(set: &'a TaskSet, task: &'a Task) -> impl Iterator<Item = (u64, u64)> + 'a {
+    set.tasks
+        .iter()
+        .filter(move |t| t.priority < task.priority && task.priority <= set.timer_priority())
+        .map(move |t| (set.overhead.release as u64, t.period as u64))
+}
+
+/// All tasks meet their deadlines.
+pub fn schedulable(set: &TaskSet) -> bool {
+    set.tasks.iter().all(|t| response_time(set, t).is_some())
+}
+
+pub fn utilization(set: &TaskSet, task: &Task) -> f64 {
+    cost(set, task) as f64 / task.period as f64
+}
+
+/// The Liu & Layland bound for `n` tasks, `n (2^(1/n) - 1)`.
+pub fn liu_layland_bound(n: usize) -> f64 {
+    let n = n as f64;
+    n * (2f64.powf(1.0 / n) - 1.0)
+}
+
+/// Priorities are rate monotonic, shorter periods have higher (or equal)
+/// priorities.
+pub fn rate_monotonic(set: &TaskSet) -> bool {
+    set.tasks.iter().all(|a| {
+        set.tasks
+            .iter()
+            .all(|b| a.period >= b.period || a.priority >= b.priority)
+    })
+}
+
+/// Outcome of a sufficient test.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Verdict {
+    Schedulable,
+    /// The test fails for the task(s), which may still be schedulable.
+    Inconclusive(Vec<String>),
+    /// The assumptions of the test do not hold.
+    NotApplicable(String),
+}
+
+/// A task of the utilization based tests.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskBound {
+    pub task: String,
+    pub utilization: f64,
+    pub blocking: u64,
+    /// `U_1 + .. + U_i + B_i / T_i`.
+    pub liu_layland: f64,
+    /// The Liu & Layland bound of the tasks `1 .. i`.
+    pub bound: f64,
+    /// `(U_1 + 1) .. (U_i + B_i / T_i + 1)`.
+    pub hyperbolic: f64,
+}
+
+/// The timer queue demand of a task.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueueDemand {
+    pub task: String,
+    pub pending: u32,
+    pub capacity: u32,
+}
+
+/// Timer queue capacity against the simultaneously pending `schedule`
+/// calls, each task contributing its `pending` instances, which must fit
+/// in its own message queue as well.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimerQueue {
+    pub capacity: u32,
+    pub pending: u32,
+    pub tasks: Vec<QueueDemand>,
+}
+
+impl TimerQueue {
+    pub fn new(set: &TaskSet) -> Self {
+        let tasks: Vec<QueueDemand> = set
+            .tasks
+            .iter()
+            .map(|t| QueueDemand {
+                task: t.name.clone(),
+                pending: t.pending,
+                capacity: t.capacity,
+            })
+            .collect();
+        TimerQueue {
+            capacity: set.timer_queue(),
+            pending: tasks.iter().map(|t| t.pending).sum(),
+            tasks,
+        }
+    }
+
+    /// Tasks with more pending instances than their capacity.
+    pub fn overflows(&self) -> impl Iterator<Item = &QueueDemand> {
+        self.tasks.iter().filter(|t| t.pending > t.capacity)
+    }
+
+    pub fn success(&self) -> bool {
+        self.pending <= self.capacity && self.overflows().next().is_none()
+    }
+}
+
+/// The sufficient tests of a task set.
+pub struct Feasibility {
+    pub utilization: f64,
+    pub hyperperiod: u64,
+    /// Task instances released per hyperperiod.
+    pub jobs: u64,
+    /// In priority order, highest first.
+    pub tasks: Vec<TaskBound>,
+    pub liu_layland: Verdict,
+    pub hyperbolic: Verdict,
+    pub timer_queue: TimerQueue,
+}
+
+impl Feasibility {
+    pub fn new(set: &TaskSet) -> Self {
+        let hyperperiod = set.hyperperiod();
+        let mut order: Vec<&Task> = set.tasks.iter().collect();
+        order.sort_by_key(|t| (std::cmp::Reverse(t.priority), t.period));
+
+        let mut tasks = Vec::new();
+        let (mut sum, mut product) = (0.0, 1.0);
+        for (i, t) in order.iter().enumerate() {
+            let u = utilization(set, t);
+            let b = blocking(set, t);
+            let bu = b as f64 / t.period as f64;
+            tasks.push(TaskBound {
+                task: t.name.clone(),
+                utilization: u,
+                blocking: b,
+                liu_layland: sum + u + bu,
+                bound: liu_layland_bound(i + 1),
+                hyperbolic: product * (u + bu + 1.0),
+            });
+            sum += u;
+            product *= u + 1.0;
+        }
+
+        let applicable = if !rate_monotonic(set) {
+            Some("priorities are not rate monotonic".to_string())
+        } else {
+            set.tasks
+                .iter()
+                .find(|t| t.deadline != t.period)
+                .map(|t| format!("the deadline of `{}` differs from its period", t.name))
+        };
+        let verdict = |fails: &dyn Fn(&TaskBound) -> bool| match &applicable {
+            Some(reason) => Verdict::NotApplicable(reason.clone()),
+            None => {
+                let failed: Vec<String> = tasks
+                    .iter()
+                    .filter(|t| fails(t))
+                    .map(|t| t.task.clone())
+                    .collect();
+                if failed.is_empty() {
+                    Verdict::Schedulable
+                } else {
+                    Verdict::Inconclusive(failed)
+                }
+            }
+        };
+        let liu_layland = verdict(&|t| t.liu_layland > t.bound);
+        let hyperbolic = verdict(&|t| t.hyperbolic > 2.0);
+
+        Feasibility {
+            utilization: sum,
+            hyperperiod,
+            jobs: set
+                .tasks
+                .iter()
+                .map(|t| hyperperiod / t.period as u64)
+                .sum(),
+            tasks,
+            liu_layland,
+            hyperbolic,
+            timer_queue: TimerQueue::new(set),
+        }
+    }
+
+    /// The total utilization exceeds the processor.
+    pub fn overloaded(&self) -> bool {
+        self.utilization > 1.0
+    }
+
+    pub fn schedulable(&self) -> bool {
+        self.liu_layland == Verdict::Schedulable || self.hyperbolic == Verdict::Schedulable
+    }
+
+    /// Not proven infeasible, and the timer queue suffices.
+    pub fn success(&self) -> bool {
+        !self.overloaded() && self.timer_queue.success()
+    }
+}
+
+/// The response time analysis of a task.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskResponse {
+    pub task: String,
+    pub priority: u8,
+    pub cost: u64,
+    pub blocking: u64,
+    pub deadline: u64,
+    /// `None` if above the deadline.
+    pub response_time: Option<u64>,
+}
+
+/// The response time analysis of a task set, in priority order.
+pub struct Rta {
+    pub tasks: Vec<TaskResponse>,
+}
+
+impl Rta {
+    pub fn new(set: &TaskSet) -> Self {
+        let mut tasks: Vec<TaskResponse> = set
+            .tasks
+            .iter()
+            .map(|t| TaskResponse {
+                task: t.name.clone(),
+                priority: t.priority,
+                cost: cost(set, t),
+                blocking: blocking(set, t),
+                deadline: t.deadline as u64,
+                response_time: response_time(set, t),
+            })
+            .collect();
+        tasks.sort_by_key(|t| std::cmp::Reverse(t.priority));
+        Rta { tasks }
+    }
+
+    pub fn schedulable(&self) -> bool {
+        self.tasks.iter().all(|t| t.response_time.is_some())
+    }
+}
+
+impl fmt::Display for Rta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<8} {:>4} {:>8} {:>8} {:>8} {:>8}",
+            "task", "prio", "cost", "blocking", "deadline", "rt"
+        )?;
+        for t in &self.tasks {
+            writeln!(
+                f,
+                "{:<8} {:>4} {:>8} {:>8} {:>8} {:>8}",
+                t.task,
+                t.priority,
+                t.cost,
+                t.blocking,
+                t.deadline,
+                match t.response_time {
+                    Some(r) => r.to_string(),
+                    None => "MISS".to_string(),
+                }
+            )?;
+        }
+        write!(
+            f,
+            "response time analysis: {}",
+            if self.schedulable() {
+                "schedulable"
+            } else {
+                "NOT schedulable"
+            }
+        )
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Schedulable => write!(f, "schedulable"),
+            Verdict::Inconclusive(tasks) => {
+                write!(f, "inconclusive (fails for {})", tasks.join(", "))
+            }
+            Verdict::NotApplicable(reason) => write!(f, "not applicable, {}", reason),
+        }
+    }
+}
+
+impl fmt::Display for Feasibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<8} {:>8} {:>8} {:>10} {:>8} {:>10}",
+            "task", "U", "blocking", "U + B/T", "bound", "hyperbolic"
+        )?;
+        for t in &self.tasks {
+            writeln!(
+                f,
+                "{:<8} {:>8.4} {:>8} {:>10.4} {:>8.4} {:>10.4}",
+                t.task, t.utilization, t.blocking, t.liu_layland, t.bound, t.hyperbolic
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "utilization:   {:.4}{}",
+            self.utilization,
+            if self.overloaded() {
+                ", above 1, INFEASIBLE"
+            } else {
+                ""
+            }
+        )?;
+        writeln!(
+            f,
+            "hyperperiod:   {} cycles, {} jobs",
+            self.hyperperiod, self.jobs
+        )?;
+        writeln!(f, "liu & layland: {}", self.liu_layland)?;
+        writeln!(f, "hyperbolic:    {}", self.hyperbolic)?;
+        let q = &self.timer_queue;
+        write!(
+            f,
+            "timer queue:   {} pending, capacity {}{}",
+            q.pending,
+            q.capacity,
+            if q.pending > q.capacity {
+                ", OVERFLOW"
+            } else {
+                ""
+            }
+        )?;
+        for t in q.overflows() {
+            write!(
+                f,
+                "\nerror: `{}` has {} instances pending, its capacity is {}",
+                t.task, t.pending, t.capacity
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::Simulation;
+    use std::path::Path;
+
+    fn load(name: &str) -> TaskSet {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("task_sets")
+            .join(name);
+        TaskSet::load(&path).unwrap()
+    }
+
+    /// exam.json, with rate monotonic priorities (t3, t1, t2).
+    fn rate_monotonic_exam() -> TaskSet {
+        let mut set = load("exam.json");
+        set.tasks[0].priority = 2;
+        set.tasks[1].priority = 1;
+        set
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn response_times(set: &TaskSet) -> Vec<Option<u64>> {
+        set.tasks.iter().map(|t| response_time(set, t)).collect()
+    }
+
+    #[test]
+    fn response_time_exam() {
+        let set = load("exam.json");
+        let blocking: Vec<_> = set.tasks.iter().map(|t| blocking(&set, t)).collect();
+        assert_eq!(blocking, [0, 0, 4_000]);
+        assert_eq!(
+            response_times(&set),
+            [Some(100_000), Some(90_000), Some(34_000)]
+        );
+        assert!(schedulable(&set));
+
+        let rta = Rta::new(&set);
+        let order: Vec<_> = rta.tasks.iter().map(|t| &*t.task).collect();
+        assert_eq!(order, ["t3", "t2", "t1"]);
+        assert!(rta.schedulable());
+    }
+
+    #[test]
+    fn response_time_measured() {
+        // the release and dispatch overhead is added to each instance
+        let set = load("exam_measured.json");
+        let cost: Vec<_> = set.tasks.iter().map(|t| cost(&set, t)).collect();
+        assert_eq!(cost, [10_363, 30_463, 30_362]);
+        // t3 is preempted by the releases of t1 and t2 (55 cycles each)
+        assert_eq!(response_times(&set), [None, Some(91_242), Some(34_472)]);
+        assert!(!schedulable(&set));
+        assert!(!Rta::new(&set).schedulable());
+    }
+
+    #[test]
+    fn response_time_release_overhead() {
+        // without the R2 sections (no blocking), the bounds are reached
+        let mut set = load("exam_measured.json");
+        set.tasks[1].trace.inner[0].inner.clear();
+        set.tasks[2].trace.inner.clear();
+        assert_eq!(response_times(&set), [None, Some(91_242), Some(30_472)]);
+
+        let sim = Simulation::run(&set, 200_000 + 2 * set.hyperperiod());
+        let simulated: Vec<_> = sim
+            .stats(&set)
+            .into_iter()
+            .map(|s| s.max_response_time)
+            .collect();
+        assert_eq!(simulated[1..], [91_242, 30_472]);
+
+        // above the timer priority, the releases do not interfere
+        set.overhead.timer_priority = Some(2);
+        assert_eq!(response_time(&set, &set.tasks[2]), Some(30_362));
+        assert_eq!(response_time(&set, &set.tasks[1]), Some(91_242));
+    }
+
+    #[test]
+    fn response_time_equal_priorities() {
+        // equal priorities interfere with each other
+        let mut set = load("exam.json");
+        set.tasks[0].priority = 2;
+        assert_eq!(response_time(&set, &set.tasks[1]), Some(100_000));
+        set.tasks[0].deadline = 99_999;
+        assert_eq!(response_time(&set, &set.tasks[0]), None);
+    }
+
+    #[test]
+    fn feasibility_exam() {
+        let set = load("exam.json");
+        let f = Feasibility::new(&set);
+        assert!(close(f.utilization, 0.85));
+        assert_eq!((f.hyperperiod, f.jobs), (200_000, 7));
+        assert!(!f.overloaded());
+        assert!(!rate_monotonic(&set));
+        assert!(matches!(f.liu_layland, Verdict::NotApplicable(_)));
+        assert!(matches!(f.hyperbolic, Verdict::NotApplicable(_)));
+
+        let order: Vec<_> = f.tasks.iter().map(|t| (&*t.task, t.blocking)).collect();
+        assert_eq!(order, [("t3", 4_000), ("t2", 0), ("t1", 0)]);
+        // U_3 + B_3 / T_3
+        assert!(close(f.tasks[0].liu_layland, 0.68));
+        assert!(f.success());
+    }
+
+    #[test]
+    fn feasibility_rate_monotonic() {
+        let set = rate_monotonic_exam();
+        assert!(rate_monotonic(&set));
+        let f = Feasibility::new(&set);
+        // t1 is blocked by t2 on R2 (ceiling 3)
+        let t1 = &f.tasks[1];
+        assert_eq!((&*t1.task, t1.blocking), ("t1", 4_000));
+        assert!(close(t1.liu_layland, 0.74));
+        assert!(close(t1.hyperbolic, 1.6 * 1.14));
+        // 0.85 is above the bound for 3 tasks, and 1.6 * 1.1 * 1.15 above 2
+        assert_eq!(f.liu_layland, Verdict::Inconclusive(vec!["t2".into()]));
+        assert_eq!(f.hyperbolic, Verdict::Inconclusive(vec!["t2".into()]));
+        assert!(!f.schedulable());
+
+        let mut set = set;
+        set.tasks[1].trace.end = 20_000;
+        set.tasks[1].trace.inner.pop();
+        let f = Feasibility::new(&set);
+        assert_eq!(f.liu_layland, Verdict::Inconclusive(vec!["t2".into()]));
+        assert_eq!(f.hyperbolic, Verdict::Schedulable);
+        assert!(f.schedulable());
+    }
+
+    #[test]
+    fn liu_layland() {
+        assert!(close(liu_layland_bound(1), 1.0));
+        assert!(close(liu_layland_bound(2), 2.0 * (2f64.sqrt() - 1.0)));
+        assert!(liu_layland_bound(100) > std::f64::consts::LN_2);
+    }
+
+    #[test]
+    fn overloaded() {
+        let mut set = load("exam.json");
+        set.tasks[2].trace.end = 40_000;
+        let f = Feasibility::new(&set);
+        assert!(close(f.utilization, 1.05));
+        assert!(f.overloaded());
+        assert!(!f.success());
+    }
+
+    #[test]
+    fn timer_queue() {
+        let mut set = load("exam.json");
+        assert_eq!(TimerQueue::new(&set).pending, 3);
+        assert!(TimerQueue::new(&set).success());
+
+        set.timer_queue = Some(2);
+        assert!(!TimerQueue::new(&set).success());
+
+        set.timer_queue = None;
+        set.tasks[0].pending = 2;
+        let q = TimerQueue::new(&set);
+        assert_eq!((q.pending, q.capacity), (4, 3));
+        let overflows: Vec<_> = q.overflows().map(|t| &*t.task).collect();
+        assert_eq!(overflows, ["t1"]);
+
+        set.tasks[0].capacity = 2;
+        assert!(TimerQueue::new(&set).success());
+    }
+}
@@ -0,0 +1,184 @@
+//! bench.rs
+//!
+//! Collection of micro-benchmark results reported by the firmware
+//! (`src/bench.rs`), and comparison across commits.
+
+use std::{fmt, fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+/// Statistics of a single benchmark (in clock cycles).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub name: String,
+    pub runs: u32,
+    pub min: u32,
+    pub median: u32,
+    pub max: u32,
+    pub overhead: u32,
+}
+
+/// The benchmarks of a single run.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Results {
+    /// The commit the firmware was built from.
+    pub commit: String,
+    pub benches: Vec<Stats>,
+}
+
+impl Stats {
+    /// Parses a report line, e.g.:
+    ///
+    /// `bench timed_loop runs=100 min=40000 median=40000 max=40002 overhead=11`
+    ///
+    /// The fields are taken from the right, so names may contain spaces
+    /// (`bench!("u64 add", ..)`).
+    pub fn parse(line: &str) -> Option<Self> {
+        let rest = line.trim().strip_prefix("bench ")?;
+        let mut words = rest.rsplitn(6, ' ');
+        let mut field = |key: &str| -> Option<u32> {
+            let (k, v) = words.next()?.split_once('=')?;
+            if k == key {
+                v.parse().ok()
+            } else {
+                None
+            }
+        };
+        let overhead = field("overhead")?;
+        let max = field("max")?;
+        let median = field("median")?;
+        let min = field("min")?;
+        let runs = field("runs")?;
+        let name = words.next()?.trim();
+        if name.is_empty() {
+            return None;
+        }
+        Some(Stats {
+            name: name.to_string(),
+            runs,
+            min,
+            median,
+            max,
+            overhead,
+        })
+    }
+}
+
+impl Results {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        serde_json::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn store(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        fs::write(path, json)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Stats> {
+        self.benches.iter().find(|b| b.name == name)
+    }
+}
+
+/// Median of each benchmark in `new` compared to `base`.
+pub struct Comparison<'a> {
+    pub base: &'a Results,
+    pub new: &'a Results,
+}
+
+impl fmt::Display for Comparison<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<24} {:>12} {:>12} {:>9}",
+            "bench", self.base.commit, self.new.commit, "change"
+        )?;
+        for new in &self.new.benches {
+            match self.base.get(&new.name) {
+                Some(base) => {
+                    let change = if base.median == 0 {
+                        0.0
+                    } else {
+                        100.0 * (new.median as f64 - base.median as f64) / base.median as f64
+                    };
+                    writeln!(
+                        f,
+                        "{:<24} {:>12} {:>12} {:>8.1}%",
+                        new.name, base.median, new.median, change
+                    )?;
+                }
+                None => writeln!(f, "{:<24} {:>12} {:>12}", new.name, "-", new.median)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(name: &str) -> Stats {
+        Stats {
+            name: name.into(),
+            runs: 100,
+            min: 40000,
+            median: 40000,
+            max: 40002,
+            overhead: 11,
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let line = "bench timed_loop runs=100 min=40000 median=40000 max=40002 overhead=11";
+        assert_eq!(Stats::parse(line), Some(stats("timed_loop")));
+        let line = "bench u64 add runs=100 min=40000 median=40000 max=40002 overhead=11\r";
+        assert_eq!(Stats::parse(line), Some(stats("u64 add")));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for line in [
+            "",
+            "init done",
+            "bench runs=100 min=40000 median=40000 max=40002 overhead=11",
+            "bench x runs=100 min=40000 median=40000 max=40002",
+            "bench x runs=100 min=40000 median=40000 overhead=11 max=40002",
+            "bench x runs=100 min=40000 median=- max=40002 overhead=11",
+            "benchmark x runs=100 min=40000 median=40000 max=40002 overhead=11",
+        ] {
+            assert_eq!(Stats::parse(line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn compare() {
+        let base = Results {
+            commit: "a".into(),
+            benches: vec![stats("timed_loop")],
+        };
+        let mut new = Results {
+            commit: "b".into(),
+            benches: vec![stats("timed_loop"), stats("u64 add")],
+        };
+        new.benches[0].median = 44000;
+        let table = Comparison {
+            base: &base,
+            new: &new,
+        }
+        .to_string();
+        let lines: Vec<_> = table.lines().collect();
+        assert!(
+            lines[1].ends_with("40000        44000     10.0%"),
+            "{}",
+            lines[1]
+        );
+        assert!(lines[2].starts_with("u64 add"));
+        assert!(lines[2].contains(" - "));
+    }
+}
@@ -0,0 +1,170 @@
+//! bin/analyzer.rs
+//!
+//! Scheduling analysis of task sets (see `task_set.rs`).
+//!
+//! > cargo run --bin analyzer -- check task_sets/exam.json
+//! > cargo run --bin analyzer -- sensitivity task_sets/exam_measured.json
+//! > cargo run --bin analyzer -- priorities task_sets/exam_measured.json
+//! > cargo run --bin analyzer -- simulate task_sets/exam.json --timeline
+
+use std::{error::Error, fs, path::PathBuf, process};
+
+use clap::{Parser, Subcommand};
+use tools::{
+    analysis::{Feasibility, Rta},
+    ceilings::NVIC_PRIO_BITS,
+    model::App,
+    priorities::{self, Assignments},
+    sensitivity::Sensitivity,
+    sim::{Simulation, Summary},
+    task_set::TaskSet,
+};
+
+#[derive(Parser)]
+#[command(about = "Scheduling analysis of task sets")]
+struct Opt {
+    #[command(subcommand)]
+    command: Cmd,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// Checks the utilization based tests, the hyperperiod and the timer
+    /// queue capacity
+    Check {
+        /// The task set (JSON)
+        task_set: PathBuf,
+    },
+
+    /// Computes the response times, and the slack of the task WCETs and
+    /// the critical sections
+    Sensitivity {
+        /// The task set (JSON)
+        task_set: PathBuf,
+    },
+
+    /// Suggests priorities (deadline monotonic, or Audsley's algorithm)
+    Priorities {
+        /// The task set (JSON)
+        task_set: PathBuf,
+
+        /// Available priority levels (NVIC)
+        #[arg(long, default_value_t = 1 << NVIC_PRIO_BITS)]
+        levels: u8,
+
+        /// The application source, to print the changes of its `#[task]`
+        /// attributes as a patch
+        #[arg(long)]
+        app: Option<PathBuf>,
+    },
+
+    /// Simulates the task set under SRP scheduling
+    Simulate {
+        /// The task set (JSON)
+        task_set: PathBuf,
+
+        /// Release jobs up to this time (default the largest offset plus
+        /// two hyperperiods)
+        #[arg(long)]
+        until: Option<u64>,
+
+        /// Print the events (releases, starts, preemptions, locks)
+        #[arg(long)]
+        timeline: bool,
+    },
+}
+
+fn main() {
+    match run(Opt::parse()) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Returns false if a deadline is (or may be) missed.
+fn run(opt: Opt) -> Result<bool, Box<dyn Error>> {
+    match opt.command {
+        Cmd::Check { task_set } => {
+            let set = TaskSet::load(&task_set)?;
+            let feasibility = Feasibility::new(&set);
+            println!("{}", feasibility);
+            Ok(feasibility.success())
+        }
+        Cmd::Sensitivity { task_set } => {
+            let set = TaskSet::load(&task_set)?;
+            let rta = Rta::new(&set);
+            println!("{}\n", rta);
+            println!("{}", Sensitivity::new(&set));
+            Ok(rta.schedulable())
+        }
+        Cmd::Priorities {
+            task_set,
+            levels,
+            app,
+        } => {
+            if levels == 0 {
+                return Err("at least one priority level is needed".into());
+            }
+            let set = TaskSet::load(&task_set)?;
+            let assignments = Assignments::new(&set, levels);
+            let suggested = match assignments.suggested() {
+                Some(p) => p,
+                None => {
+                    println!("{}", assignments);
+                    return Ok(false);
+                }
+            };
+            match app {
+                Some(path) => {
+                    let src = fs::read_to_string(&path)?;
+                    let changes = priorities::changes(&App::load(&path)?, &src, suggested)?;
+                    if changes.is_empty() {
+                        eprintln!("{}: no changes", path.display());
+                    } else {
+                        print!(
+                            "{}",
+                            priorities::diff(&path.display().to_string(), &changes)
+                        );
+                    }
+                }
+                None => {
+                    println!("{}\n", assignments);
+                    for (task, priority) in suggested {
+                        println!("{}: priority = {}", task, priority);
+                    }
+                }
+            }
+            Ok(true)
+        }
+        Cmd::Simulate {
+            task_set,
+            until,
+            timeline,
+        } => {
+            let set = TaskSet::load(&task_set)?;
+            let offset = set.tasks.iter().map(|t| t.offset as u64).max().unwrap_or(0);
+            let until = until.unwrap_or(offset + 2 * set.hyperperiod());
+            let sim = Simulation::run(&set, until);
+            if timeline {
+                for event in &sim.events {
+                    println!("{}", event);
+                }
+                println!();
+            }
+            let stats = sim.stats(&set);
+            println!("simulated releases up to {}\n", sim.until);
+            print!(
+                "{}",
+                Summary {
+                    set: &set,
+                    stats: &stats
+                }
+            );
+            Ok(stats.iter().all(|s| s.misses == 0))
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! bin/bench.rs
+//!
+//! Collects the micro-benchmark results from the firmware output (read from
+//! stdin), and stores them as JSON per commit.
+//!
+//! > cargo run --example bench --release --features nightly | (cd tools && cargo run --bin bench)
+//!
+//! The firmware output is passed through. Collection ends at the
+//! `bench done` line (or end of input).
+
+use std::{
+    error::Error,
+    io::{self, BufRead},
+    path::PathBuf,
+    process::{self, Command},
+};
+
+use clap::Parser;
+use tools::bench::{Comparison, Results, Stats};
+
+#[derive(Parser)]
+#[command(about = "Collects micro-benchmark results reported over RTT")]
+struct Opt {
+    /// Output file, defaults to `bench/<commit>.json`
+    #[arg(long)]
+    out: Option<PathBuf>,
+
+    /// Compare to previously stored results
+    #[arg(long)]
+    compare: Option<PathBuf>,
+}
+
+fn main() {
+    if let Err(e) = run(Opt::parse()) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
+    let mut results = Results {
+        commit: commit(),
+        benches: Vec::new(),
+    };
+
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        println!("{}", line);
+        if let Some(stats) = Stats::parse(line.trim()) {
+            results.benches.push(stats);
+        } else if line.trim() == "bench done" {
+            break;
+        }
+    }
+    if results.benches.is_empty() {
+        return Err("no benchmark results found in the input".into());
+    }
+
+    let out = opt
+        .out
+        .unwrap_or_else(|| PathBuf::from(format!("bench/{}.json", results.commit)));
+    results.store(&out)?;
+    println!("\nresults stored in {}", out.display());
+
+    if let Some(path) = opt.compare {
+        let base = Results::load(&path)?;
+        print!(
+            "\n{}",
+            Comparison {
+                base: &base,
+                new: &results,
+            }
+        );
+    }
+    Ok(())
+}
+
+/// The current commit (short hash), marked `-dirty` for uncommitted changes.
+fn commit() -> String {
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+    };
+    match git(&["rev-parse", "--short", "HEAD"]) {
+        Some(hash) => match git(&["status", "--porcelain", "--untracked-files=no"]) {
+            Some(status) if !status.is_empty() => format!("{}-dirty", hash),
+            _ => hash,
+        },
+        None => "unknown".into(),
+    }
+}
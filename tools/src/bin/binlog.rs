@@ -0,0 +1,67 @@
+//! bin/binlog.rs
+//!
+//! Decodes `binlog!` output of the firmware.
+//!
+//! The raw frames are read from the RTT channel (e.g., exposed by openocd
+//! on a TCP port), or from a file (or stdin) holding a capture:
+//!
+//! > cargo run --bin binlog -- --elf ../target/thumbv7em-none-eabi/release/examples/binlog --tcp localhost:9091
+
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, Read},
+    net::TcpStream,
+    path::PathBuf,
+    process,
+};
+
+use clap::Parser;
+use tools::binlog::{Decoder, Table};
+
+#[derive(Parser)]
+#[command(about = "Decodes binary log frames, using the format strings of the ELF")]
+struct Opt {
+    /// The firmware ELF
+    #[arg(long)]
+    elf: PathBuf,
+
+    /// Read frames from a TCP server (e.g., `monitor rtt server start 9091 1`)
+    #[arg(long, conflicts_with = "input")]
+    tcp: Option<String>,
+
+    /// Read frames from a file (default stdin)
+    input: Option<PathBuf>,
+}
+
+fn main() {
+    if let Err(e) = run(Opt::parse()) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
+    let table = Table::load(&opt.elf)?;
+    let mut input: Box<dyn Read> = match (opt.tcp, opt.input) {
+        (Some(addr), _) => Box::new(TcpStream::connect(addr)?),
+        (None, Some(path)) => Box::new(File::open(path)?),
+        (None, None) => Box::new(io::stdin()),
+    };
+
+    let mut decoder = Decoder::new();
+    let mut buf = [0; 256];
+    loop {
+        let n = input.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        decoder.push(&buf[..n]);
+        while let Some(frame) = decoder.next_frame() {
+            match frame {
+                Ok(frame) => println!("{}", frame.format(&table)),
+                Err(e) => eprintln!("warning: {}", e),
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! bin/ceilings.rs
+//!
+//! Verifies the resource ceilings (BASEPRI writes) in the compiled code
+//! against the task model of the application.
+//!
+//! > cargo run --bin ceilings -- --model ../examples/timing_exam.rs --elf ../target/thumbv7em-none-eabi/release/examples/timing_exam
+//!
+//! or from a disassembly at hand:
+//!
+//! > cargo objdump --example timing_exam --release -- -d --no-show-raw-insn > timing_exam.txt
+//! > (cd tools && cargo run --bin ceilings -- --model ../examples/timing_exam.rs --disasm ../timing_exam.txt)
+
+use std::{error::Error, fs, path::PathBuf, process, process::Command};
+
+use clap::Parser;
+use tools::{
+    ceilings::{self, Check},
+    model::App,
+};
+
+#[derive(Parser)]
+#[command(about = "Verifies the resource ceilings of an RTIC application in its code")]
+struct Opt {
+    /// The application source, or its model (JSON)
+    #[arg(long)]
+    model: PathBuf,
+
+    /// The firmware ELF, disassembled by `--objdump`
+    #[arg(long, required_unless_present = "disasm")]
+    elf: Option<PathBuf>,
+
+    /// A disassembly (`objdump -d` output) instead of the ELF
+    #[arg(long, conflicts_with = "elf")]
+    disasm: Option<PathBuf>,
+
+    /// The disassembler
+    #[arg(long, default_value = "rust-objdump")]
+    objdump: String,
+
+    /// NVIC priority bits
+    #[arg(long, default_value_t = ceilings::NVIC_PRIO_BITS)]
+    bits: u8,
+}
+
+fn main() {
+    match run(Opt::parse()) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn run(opt: Opt) -> Result<bool, Box<dyn Error>> {
+    if !(1..=8).contains(&opt.bits) {
+        return Err("the number of priority bits must be 1..=8".into());
+    }
+    let app = App::load(&opt.model)?;
+    let disassembly = match (&opt.disasm, &opt.elf) {
+        (Some(path), _) => fs::read_to_string(path)?,
+        (None, Some(elf)) => {
+            let output = Command::new(&opt.objdump)
+                .args(["-d", "--no-show-raw-insn"])
+                .arg(elf)
+                .output()
+                .map_err(|e| format!("failed to run `{}`: {}", opt.objdump, e))?;
+            if !output.status.success() {
+                return Err(String::from_utf8_lossy(&output.stderr).into());
+            }
+            String::from_utf8(output.stdout)?
+        }
+        (None, None) => unreachable!(),
+    };
+
+    for (resource, ceiling) in app.ceilings() {
+        println!(
+            "resource {:<12} ceiling {} (BASEPRI {:#04x})",
+            resource,
+            ceiling,
+            ceilings::hardware(ceiling, opt.bits)
+        );
+    }
+    println!();
+    let check = Check::new(&app, &ceilings::scan(&disassembly), opt.bits);
+    println!("{}", check);
+    Ok(check.success())
+}
@@ -0,0 +1,91 @@
+//! bin/harness.rs
+//!
+//! Runs an example under `openocd` (or a simulator gdb stub) and records the
+//! cycle counter at each `asm::bkpt()`.
+//!
+//! > openocd -f openocd.cfg
+//! > cargo build --example timing_task --release --features nightly
+//! > cd tools
+//! > cargo run --bin harness -- --elf ../target/thumbv7em-none-eabi/release/examples/timing_task --load --label start,entry,return -n 3
+
+use std::{fs, path::PathBuf, process, time::Duration};
+
+use clap::Parser;
+use tools::{elf::Symbols, gdb::Client, harness};
+
+#[derive(Parser)]
+#[command(about = "Records CYCCNT at each breakpoint of an example")]
+struct Opt {
+    /// Address of the gdb stub
+    #[arg(long, default_value = "localhost:3333")]
+    remote: String,
+
+    /// The example ELF (for symbols, and for `--load`)
+    #[arg(long)]
+    elf: Option<PathBuf>,
+
+    /// Flash the ELF (`monitor program`, openocd only)
+    #[arg(long, requires = "elf")]
+    load: bool,
+
+    /// Do not reset the target before running
+    #[arg(long)]
+    no_reset: bool,
+
+    /// Step over `bkpt` instructions (for stubs that do not do it themselves)
+    #[arg(long)]
+    skip_bkpt: bool,
+
+    /// Number of breakpoints to record
+    #[arg(short = 'n', long, default_value_t = 16)]
+    max_stops: usize,
+
+    /// Seconds to wait for each breakpoint
+    #[arg(long, default_value_t = 5)]
+    timeout: u64,
+
+    /// Names of the breakpoints, in order of arrival
+    #[arg(long, value_delimiter = ',')]
+    label: Vec<String>,
+
+    /// Write the report as JSON
+    #[arg(long)]
+    json: Option<PathBuf>,
+}
+
+fn main() {
+    if let Err(e) = run(Opt::parse()) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(opt: Opt) -> Result<(), Box<dyn std::error::Error>> {
+    let symbols = opt.elf.as_deref().map(Symbols::load).transpose()?;
+
+    let mut client = Client::connect(&opt.remote)?;
+    client.status()?;
+    if let (true, Some(elf)) = (opt.load, &opt.elf) {
+        client.monitor(&format!("program {} verify", fs::canonicalize(elf)?.display()))?;
+    }
+    if !opt.no_reset {
+        client.monitor("reset init")?;
+    }
+
+    let config = harness::Config {
+        max_stops: opt.max_stops,
+        timeout: Duration::from_secs(opt.timeout),
+        skip_bkpt: opt.skip_bkpt,
+        labels: opt.label,
+    };
+    let report = harness::run(&mut client, symbols.as_ref(), &config)?;
+    print!("{}", report);
+    if !client.console.is_empty() {
+        println!("console output:\n{}", client.console);
+    }
+
+    if let Some(path) = opt.json {
+        fs::write(path, serde_json::to_string_pretty(&report)?)?;
+    }
+    Ok(())
+}
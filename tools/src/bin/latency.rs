@@ -0,0 +1,87 @@
+//! bin/latency.rs
+//!
+//! Runs `examples/latency.rs` and reports the entry latency per case.
+//!
+//! > openocd -f openocd.cfg
+//! > cargo build --example latency --release --features nightly
+//! > cd tools
+//! > cargo run --bin latency -- --elf ../target/thumbv7em-none-eabi/release/examples/latency --load
+
+use std::{error::Error, fs, path::PathBuf, process, time::Duration};
+
+use clap::Parser;
+use tools::{
+    elf::Symbols,
+    gdb::Client,
+    harness,
+    latency::{self, Report},
+};
+
+#[derive(Parser)]
+#[command(about = "Interrupt latency report for the `latency` example")]
+struct Opt {
+    /// Address of the gdb stub
+    #[arg(long, default_value = "localhost:3333")]
+    remote: String,
+
+    /// The `latency` example ELF
+    #[arg(long)]
+    elf: PathBuf,
+
+    /// Flash the ELF (`monitor program`, openocd only)
+    #[arg(long)]
+    load: bool,
+
+    /// Step over `bkpt` instructions (for stubs that do not do it themselves)
+    #[arg(long)]
+    skip_bkpt: bool,
+
+    /// Seconds to wait for the measurements to complete
+    #[arg(long, default_value_t = 10)]
+    timeout: u64,
+
+    /// Write the report as JSON
+    #[arg(long)]
+    json: Option<PathBuf>,
+}
+
+fn main() {
+    if let Err(e) = run(Opt::parse()) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
+    let symbols = Symbols::load(&opt.elf)?;
+    let table = symbols
+        .variable(latency::SYMBOL)
+        .ok_or("no `LATENCY` table in the ELF, is this the `latency` example?")?;
+
+    let mut client = Client::connect(&opt.remote)?;
+    client.status()?;
+    if opt.load {
+        client.monitor(&format!(
+            "program {} verify",
+            fs::canonicalize(&opt.elf)?.display()
+        ))?;
+    }
+    client.monitor("reset init")?;
+
+    // the example stops at a breakpoint once all cases are measured
+    let config = harness::Config {
+        max_stops: 1,
+        timeout: Duration::from_secs(opt.timeout),
+        skip_bkpt: opt.skip_bkpt,
+        labels: vec!["done".into()],
+    };
+    harness::run(&mut client, Some(&symbols), &config)?;
+
+    let report = Report::decode(&client.read_memory(table.address, table.size as usize)?);
+    print!("{}", report);
+
+    if let Some(path) = opt.json {
+        fs::write(path, serde_json::to_string_pretty(&report)?)?;
+    }
+    Ok(())
+}
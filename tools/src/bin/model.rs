@@ -0,0 +1,48 @@
+//! bin/model.rs
+//!
+//! Extracts the task model of an RTIC application from its source, as JSON
+//! for the analysis and tracing tools.
+//!
+//! > cargo run --bin model -- ../examples/timing_exam.rs
+
+use std::{error::Error, fs, path::PathBuf, process};
+
+use clap::Parser;
+use tools::model::App;
+
+#[derive(Parser)]
+#[command(about = "Extracts the task model of an RTIC application")]
+struct Opt {
+    /// The application source (containing `#[rtic::app]`)
+    src: PathBuf,
+
+    /// Output file (default stdout)
+    #[arg(long)]
+    out: Option<PathBuf>,
+
+    /// Print a table instead of JSON
+    #[arg(long, conflicts_with = "out")]
+    table: bool,
+}
+
+fn main() {
+    if let Err(e) = run(Opt::parse()) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
+    let app = App::load(&opt.src)?;
+    if opt.table {
+        println!("{}", app);
+        return Ok(());
+    }
+    let mut json = serde_json::to_string_pretty(&app)?;
+    json.push('\n');
+    match opt.out {
+        Some(path) => fs::write(path, json)?,
+        None => print!("{}", json),
+    }
+    Ok(())
+}
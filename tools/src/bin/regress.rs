@@ -0,0 +1,152 @@
+//! bin/regress.rs
+//!
+//! Builds and runs each example listed in the baseline file, measures the
+//! timing properties and compares them to the recorded values.
+//!
+//! > openocd -f openocd.cfg
+//! > cd tools
+//! > cargo run --bin regress
+//!
+//! Exits with a non-zero status if any property regressed. Run with
+//! `--update` to record the measured values as the new baseline.
+
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    process::{self, Command},
+    time::Duration,
+};
+
+use clap::Parser;
+use tools::{
+    elf::Symbols,
+    gdb::Client,
+    harness,
+    regress::{Baselines, Case},
+};
+
+#[derive(Parser)]
+#[command(about = "Checks timing properties of the examples against baselines")]
+struct Opt {
+    /// The baseline file
+    #[arg(long, default_value = "baselines.json")]
+    baselines: PathBuf,
+
+    /// Root of the firmware crate
+    #[arg(long, default_value = "..")]
+    root: PathBuf,
+
+    /// Compilation target of the firmware
+    #[arg(long, default_value = "thumbv7em-none-eabi")]
+    target: String,
+
+    /// Address of the gdb stub (openocd or simulator)
+    #[arg(long, default_value = "localhost:3333")]
+    remote: String,
+
+    /// Step over `bkpt` instructions (for stubs that do not do it themselves)
+    #[arg(long)]
+    skip_bkpt: bool,
+
+    /// Seconds to wait for each breakpoint
+    #[arg(long, default_value_t = 5)]
+    timeout: u64,
+
+    /// Only run cases for this example
+    #[arg(long)]
+    example: Option<String>,
+
+    /// Store the measured values as the new baseline
+    #[arg(long)]
+    update: bool,
+}
+
+fn main() {
+    match run(Opt::parse()) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(2);
+        }
+    }
+}
+
+fn run(opt: Opt) -> Result<bool, Box<dyn Error>> {
+    let mut baselines = Baselines::load(&opt.baselines)?;
+    let mut client = Client::connect(&opt.remote)?;
+    client.status()?;
+
+    let (mut passed, mut failed) = (0, 0);
+    for case in &mut baselines.cases {
+        if opt.example.as_ref().is_some_and(|e| *e != case.example) {
+            continue;
+        }
+        let args = case.build_args();
+        println!("\n{}", args.join(" "));
+
+        let elf = build(&opt, case)?;
+        let symbols = Symbols::load(&elf)?;
+        client.monitor(&format!("program {} verify", elf.display()))?;
+        client.monitor("reset init")?;
+
+        let config = harness::Config {
+            max_stops: case.breakpoints.len(),
+            timeout: Duration::from_secs(opt.timeout),
+            skip_bkpt: opt.skip_bkpt,
+            labels: case.breakpoints.clone(),
+        };
+        let report = harness::run(&mut client, Some(&symbols), &config)?;
+        if let Some(ms) = case.settle_ms {
+            harness::run_for(&mut client, Duration::from_millis(ms))?;
+        }
+        let mut read_symbol = |name: &str| {
+            let s = symbols.variable(name)?;
+            client.read_u32(s.address).ok()
+        };
+
+        for check in &mut case.checks {
+            let measured = check.measure(&report, &mut read_symbol);
+            let outcome = check.evaluate(measured.clone());
+            println!(
+                "  {:<32} expected {:>8} +/- {:<4} {}",
+                check.name, check.expected, check.tolerance, outcome
+            );
+            if outcome.passed() {
+                passed += 1;
+            } else {
+                failed += 1;
+            }
+            if let (true, Ok(v)) = (opt.update, measured) {
+                check.expected = v;
+            }
+        }
+    }
+
+    println!("\n{} passed, {} failed", passed, failed);
+    if opt.update {
+        baselines.store(&opt.baselines)?;
+        println!("baselines updated");
+    }
+    Ok(failed == 0)
+}
+
+/// Builds the example and returns the path to the ELF.
+fn build(opt: &Opt, case: &Case) -> Result<PathBuf, Box<dyn Error>> {
+    let status = Command::new("cargo")
+        .arg("build")
+        .args(case.build_args())
+        .current_dir(&opt.root)
+        .status()?;
+    if !status.success() {
+        return Err(format!("failed to build `{}`", case.example).into());
+    }
+    let profile = if case.release { "release" } else { "debug" };
+    let elf = Path::new(&opt.root)
+        .join("target")
+        .join(&opt.target)
+        .join(profile)
+        .join("examples")
+        .join(&case.example);
+    Ok(elf.canonicalize()?)
+}
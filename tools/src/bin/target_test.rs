@@ -0,0 +1,77 @@
+//! bin/target_test.rs
+//!
+//! Aggregates the results of on-target tests (`src/testing.rs`).
+//!
+//! The firmware output is read from the RTT channel (e.g., exposed by
+//! openocd on a TCP port), or from files (or stdin) holding captures, one
+//! per test run:
+//!
+//! > cargo run --bin target_test -- --tcp localhost:9090
+//!
+//! The output is passed through, followed by a summary. Exits with a
+//! non-zero status unless all runs completed without failures.
+
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    net::TcpStream,
+    path::PathBuf,
+    process,
+};
+
+use clap::Parser;
+use tools::target_test::{Report, Run};
+
+#[derive(Parser)]
+#[command(about = "Aggregates the results of on-target tests")]
+struct Opt {
+    /// Read a run from a TCP server (e.g., `monitor rtt server start 9090 0`)
+    #[arg(long, conflicts_with = "inputs")]
+    tcp: Option<String>,
+
+    /// Read runs from files (default stdin)
+    inputs: Vec<PathBuf>,
+}
+
+fn main() {
+    match run(Opt::parse()) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn run(opt: Opt) -> Result<bool, Box<dyn Error>> {
+    let mut runs = Vec::new();
+    if let Some(addr) = opt.tcp {
+        runs.push(read(&addr, BufReader::new(TcpStream::connect(&addr)?))?);
+    } else if opt.inputs.is_empty() {
+        runs.push(read("stdin", io::stdin().lock())?);
+    } else {
+        for path in &opt.inputs {
+            let source = path.display().to_string();
+            runs.push(read(&source, BufReader::new(File::open(path)?))?);
+        }
+    }
+
+    let report = Report(&runs);
+    println!("\n{}", report);
+    Ok(report.success())
+}
+
+/// Reads a run, up to the `test result` line (or end of input).
+fn read(source: &str, input: impl BufRead) -> Result<Run, Box<dyn Error>> {
+    let mut run = Run::new(source);
+    for line in input.lines() {
+        let line = line?;
+        println!("{}", line);
+        if run.feed(&line) {
+            break;
+        }
+    }
+    Ok(run)
+}
@@ -0,0 +1,411 @@
+//! binlog.rs
+//!
+//! Decoder of the `binlog!` frames (`src/binlog.rs` of the firmware).
+//!
+//! The format strings are read from the `.binlog` section of the firmware
+//! ELF, frames from the raw RTT channel are matched to them by index, and
+//! the arguments formatted.
+
+use std::{error::Error, fmt, fs, path::Path};
+
+use object::{Object, ObjectSection};
+
+/// Name of the format string section.
+pub const SECTION: &str = ".binlog";
+
+// argument type tags (keep in sync with `src/binlog.rs`)
+const U32: u8 = 0;
+const I32: u8 = 1;
+const U64: u8 = 2;
+const I64: u8 = 3;
+const FALSE: u8 = 4;
+const TRUE: u8 = 5;
+const STR: u8 = 6;
+const F32: u8 = 7;
+const CHAR: u8 = 8;
+
+/// The format strings, NUL terminated, indexed by their offset.
+pub struct Table {
+    strings: Vec<u8>,
+}
+
+impl Table {
+    pub fn new(strings: Vec<u8>) -> Self {
+        Table { strings }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let data = fs::read(path)?;
+        let file = object::File::parse(&*data)?;
+        let section = file.section_by_name(SECTION).ok_or(
+            "no `.binlog` section in the ELF, does it use `binlog!` (and link with `binlog.x`)?",
+        )?;
+        Ok(Table::new(section.data()?.to_vec()))
+    }
+
+    /// The format string at `index`.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        let tail = self.strings.get(index..)?;
+        let end = tail.iter().position(|b| *b == 0)?;
+        std::str::from_utf8(&tail[..end]).ok()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    U32(u32),
+    I32(i32),
+    U64(u64),
+    I64(i64),
+    Bool(bool),
+    Char(char),
+    F32(f32),
+    Str(String),
+}
+
+impl Value {
+    fn fmt_hex(&self, f: &mut fmt::Formatter<'_>, alternate: bool) -> fmt::Result {
+        match (self, alternate) {
+            (Value::U32(v), false) => write!(f, "{:x}", v),
+            (Value::U32(v), true) => write!(f, "{:#x}", v),
+            (Value::I32(v), false) => write!(f, "{:x}", v),
+            (Value::I32(v), true) => write!(f, "{:#x}", v),
+            (Value::U64(v), false) => write!(f, "{:x}", v),
+            (Value::U64(v), true) => write!(f, "{:#x}", v),
+            (Value::I64(v), false) => write!(f, "{:x}", v),
+            (Value::I64(v), true) => write!(f, "{:#x}", v),
+            _ => write!(f, "{}", self),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::U32(v) => write!(f, "{}", v),
+            Value::I32(v) => write!(f, "{}", v),
+            Value::U64(v) => write!(f, "{}", v),
+            Value::I64(v) => write!(f, "{}", v),
+            Value::Bool(v) => write!(f, "{}", v),
+            Value::Char(v) => write!(f, "{}", v),
+            Value::F32(v) => write!(f, "{}", v),
+            Value::Str(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// A decoded frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub index: usize,
+    pub args: Vec<Value>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The frame ends within a value.
+    Truncated,
+    UnknownTag(u8),
+    InvalidChar(u32),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(f, "truncated frame"),
+            DecodeError::UnknownTag(tag) => write!(f, "unknown argument tag {}", tag),
+            DecodeError::InvalidChar(c) => write!(f, "invalid char {:#x}", c),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        let (first, rest) = self.bytes.split_first().ok_or(DecodeError::Truncated)?;
+        self.bytes = rest;
+        Ok(*first)
+    }
+
+    fn take(&mut self, n: usize) -> Result<&[u8], DecodeError> {
+        if self.bytes.len() < n {
+            return Err(DecodeError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        Ok(value)
+    }
+
+    fn zigzag(&mut self) -> Result<i64, DecodeError> {
+        let v = self.varint()?;
+        Ok((v >> 1) as i64 ^ -((v & 1) as i64))
+    }
+
+    fn value(&mut self) -> Result<Value, DecodeError> {
+        Ok(match self.byte()? {
+            U32 => Value::U32(self.varint()? as u32),
+            I32 => Value::I32(self.zigzag()? as i32),
+            U64 => Value::U64(self.varint()?),
+            I64 => Value::I64(self.zigzag()?),
+            FALSE => Value::Bool(false),
+            TRUE => Value::Bool(true),
+            CHAR => {
+                let c = self.varint()? as u32;
+                Value::Char(char::from_u32(c).ok_or(DecodeError::InvalidChar(c))?)
+            }
+            F32 => {
+                let b = self.take(4)?;
+                Value::F32(f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            }
+            STR => {
+                let len = self.varint()? as usize;
+                Value::Str(String::from_utf8_lossy(self.take(len)?).into_owned())
+            }
+            tag => return Err(DecodeError::UnknownTag(tag)),
+        })
+    }
+}
+
+impl Frame {
+    /// Decodes the payload of a frame (without the length byte).
+    pub fn decode(payload: &[u8]) -> Result<Frame, DecodeError> {
+        let mut r = Reader { bytes: payload };
+        let index = r.varint()? as usize;
+        let mut args = Vec::new();
+        while !r.bytes.is_empty() {
+            args.push(r.value()?);
+        }
+        Ok(Frame { index, args })
+    }
+
+    /// The message, formatted by `table`.
+    pub fn format(&self, table: &Table) -> String {
+        match table.get(self.index) {
+            Some(fmt) => format(fmt, &self.args),
+            None => format!("<unknown format string {}> {:?}", self.index, self.args),
+        }
+    }
+}
+
+/// Formats `args` by the placeholders (`{}`, `{:?}`, `{:x}`, `{:#x}`) of
+/// `fmt`, missing arguments (dropped by the target) show as `<?>`.
+pub fn format(fmt: &str, args: &[Value]) -> String {
+    struct Hex<'a>(&'a Value, bool);
+    impl fmt::Display for Hex<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0.fmt_hex(f, self.1)
+        }
+    }
+
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut rest = fmt;
+    while let Some(i) = rest.find(['{', '}']) {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            out.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+        let end = match rest.find('}') {
+            Some(end) if rest.starts_with('{') => end,
+            _ => {
+                out.push_str(&rest[..1]);
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let spec = &rest[1..end];
+        rest = &rest[end + 1..];
+        match args.next() {
+            Some(Value::Str(s)) if spec == ":?" => out.push_str(&format!("{:?}", s)),
+            Some(v) if spec == ":x" => out.push_str(&Hex(v, false).to_string()),
+            Some(v) if spec == ":#x" => out.push_str(&Hex(v, true).to_string()),
+            Some(v) => out.push_str(&v.to_string()),
+            None => out.push_str("<?>"),
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Splits a byte stream into frames.
+#[derive(Default)]
+pub struct Decoder {
+    buffer: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Decoder::default()
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// The next complete frame, if any.
+    pub fn next_frame(&mut self) -> Option<Result<Frame, DecodeError>> {
+        let len = *self.buffer.first()? as usize;
+        if self.buffer.len() < 1 + len {
+            return None;
+        }
+        let frame = Frame::decode(&self.buffer[1..1 + len]);
+        self.buffer.drain(..1 + len);
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames of every argument type, as encoded by the firmware (checked
+    /// by the tests of `src/binlog.rs`).
+    const FRAMES: &[u8] = include_bytes!("../fixtures/binlog.bin");
+
+    fn frames() -> Vec<Frame> {
+        let mut decoder = Decoder::new();
+        let mut frames = Vec::new();
+        // as read from RTT, in arbitrary pieces
+        for chunk in FRAMES.chunks(3) {
+            decoder.push(chunk);
+            while let Some(frame) = decoder.next_frame() {
+                frames.push(frame.unwrap());
+            }
+        }
+        frames
+    }
+
+    #[test]
+    fn decode_every_type() {
+        let frames = frames();
+        let args: Vec<(usize, Vec<Value>)> =
+            frames.into_iter().map(|f| (f.index, f.args)).collect();
+        assert_eq!(
+            args,
+            [
+                (
+                    0,
+                    vec![
+                        Value::U32(7),
+                        Value::U32(300),
+                        Value::U32(u32::MAX),
+                        Value::U32(0)
+                    ]
+                ),
+                (
+                    12,
+                    vec![
+                        Value::I32(-1),
+                        Value::I32(-300),
+                        Value::I32(i32::MIN),
+                        Value::I32(5)
+                    ]
+                ),
+                (200, vec![Value::U64(u64::MAX), Value::I64(i64::MIN)]),
+                (
+                    3,
+                    vec![
+                        Value::Bool(true),
+                        Value::Bool(false),
+                        Value::Char('é'),
+                        Value::Char('x')
+                    ]
+                ),
+                (
+                    4,
+                    vec![
+                        Value::F32(1.5),
+                        Value::Str("hi".into()),
+                        Value::Str(String::new())
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated_frames() {
+        let mut rest = FRAMES;
+        while let Some((&len, tail)) = rest.split_first() {
+            let (payload, next) = tail.split_at(len as usize);
+            // cut within the last argument
+            let cut = &payload[..payload.len() - 1];
+            if !(payload.ends_with(&[FALSE]) || payload.ends_with(&[STR, 0])) {
+                assert_eq!(
+                    Frame::decode(cut),
+                    Err(DecodeError::Truncated),
+                    "{:x?}",
+                    payload
+                );
+            }
+            rest = next;
+        }
+        assert_eq!(Frame::decode(&[]), Err(DecodeError::Truncated));
+        // an incomplete frame is not returned (yet)
+        let mut decoder = Decoder::new();
+        decoder.push(&FRAMES[..5]);
+        assert!(decoder.next_frame().is_none());
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert_eq!(Frame::decode(&[0, 9]), Err(DecodeError::UnknownTag(9)));
+        // a surrogate
+        assert_eq!(
+            Frame::decode(&[0, CHAR, 0x80, 0xb0, 0x03]),
+            Err(DecodeError::InvalidChar(0xd800))
+        );
+    }
+
+    #[test]
+    fn format_with_table() {
+        let table = Table::new(b"t1 {} {:x}\0missed {:#x}, {:?} {}\0".to_vec());
+        assert_eq!(table.get(0), Some("t1 {} {:x}"));
+        assert_eq!(table.get(11), Some("missed {:#x}, {:?} {}"));
+        let frame = Frame {
+            index: 0,
+            args: vec![Value::I32(-2), Value::U32(255)],
+        };
+        assert_eq!(frame.format(&table), "t1 -2 ff");
+        // arguments dropped by the target
+        let frame = Frame {
+            index: 11,
+            args: vec![Value::U64(16), Value::Str("a".into())],
+        };
+        assert_eq!(frame.format(&table), "missed 0x10, \"a\" <?>");
+    }
+
+    #[test]
+    fn unknown_index() {
+        let table = Table::new(b"a\0".to_vec());
+        assert_eq!(table.get(2), None);
+        let frame = Frame {
+            index: 200,
+            args: vec![Value::Bool(true)],
+        };
+        assert_eq!(
+            frame.format(&table),
+            "<unknown format string 200> [Bool(true)]"
+        );
+    }
+}
@@ -0,0 +1,596 @@
+//! ceilings.rs
+//!
+//! Verification of the SRP resource ceilings in the compiled code.
+//!
+//! A `lock` raises the system ceiling by writing BASEPRI (`msr basepri`,
+//! or `msr basepri_max`), the value being the ceiling of the resource in
+//! the hardware encoding. The disassembly (`objdump -d`) is scanned for
+//! such writes, each function is attributed to the task(s) it implements,
+//! and the ceilings are compared to those derived from the task model
+//! (`model.rs`): each write must match the ceiling of a resource the task
+//! locks, i.e., a resource with a ceiling above the task priority. `spawn`
+//! and `schedule` lock the queues of RTIC (see `queue_ceilings`), those
+//! ceilings are expected as well.
+//!
+//! The encoding depends on the number of NVIC priority bits (4 on the
+//! STM32F4), a logical priority `p` is written as `(2^bits - p) << (8 -
+//! bits)`, e.g. `0xe0` for priority 2. Writing 0 unmasks all interrupts
+//! (restoring the thread level). At the end of a lock, RTIC restores the
+//! priority of the task with an immediate, such writes (the priority of
+//! the task) are accepted.
+
+use std::{collections::BTreeSet, fmt};
+
+use crate::model::{App, Kind, Task};
+
+/// NVIC priority bits of the STM32F4.
+pub const NVIC_PRIO_BITS: u8 = 4;
+
+/// A BASEPRI write with an immediate value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BasepriWrite {
+    pub address: u32,
+    /// The raw register value.
+    pub value: u8,
+}
+
+/// A function of the disassembly, with its BASEPRI writes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Function {
+    pub address: u32,
+    /// Demangled name, without hash.
+    pub name: String,
+    pub writes: Vec<BasepriWrite>,
+    /// BASEPRI writes with a value unknown to the scan (e.g., restored
+    /// from a register read by `mrs`).
+    pub restores: usize,
+}
+
+/// The logical priority of a raw BASEPRI value, `None` for 0 (unmasked).
+pub fn logical(value: u8, bits: u8) -> Option<u8> {
+    match value >> (8 - bits) {
+        0 => None,
+        hw => Some(((1u16 << bits) - hw as u16) as u8),
+    }
+}
+
+/// The raw BASEPRI value of a logical priority.
+pub fn hardware(priority: u8, bits: u8) -> u8 {
+    (((1u16 << bits) - priority as u16) << (8 - bits)) as u8
+}
+
+/// Scans the output of `objdump -d` for BASEPRI writes.
+///
+/// Immediate values are tracked per register through `mov` instructions,
+/// within each function, in address order (branches are not followed,
+/// compilers load the ceiling just before the `msr`).
+pub fn scan(disassembly: &str) -> Vec<Function> {
+    let mut functions: Vec<Function> = Vec::new();
+    let mut regs = [None; 16];
+    for line in disassembly.lines() {
+        // function header, `08000194 <name>:`
+        if let Some((address, name)) = header(line) {
+            // mapping symbols (llvm-objdump), `$t` code, `$d` data, are
+            // within the function
+            if name.starts_with('$') {
+                continue;
+            }
+            functions.push(Function {
+                address,
+                name,
+                writes: Vec::new(),
+                restores: 0,
+            });
+            regs = [None; 16];
+            continue;
+        }
+        let (function, (address, mnemonic, operands)) =
+            match (functions.last_mut(), instruction(line)) {
+                (Some(f), Some(i)) => (f, i),
+                _ => continue,
+            };
+        let ops: Vec<&str> = operands.split(',').map(str::trim).collect();
+        let base = mnemonic.split('.').next().unwrap_or(mnemonic);
+        match (base, ops.as_slice()) {
+            // GNU objdump prints `BASEPRI`, llvm-objdump `basepri`
+            ("msr", [sysreg, reg]) if sysreg.to_ascii_lowercase().starts_with("basepri") => {
+                match register(reg).and_then(|r| regs[r]) {
+                    Some(value) => function.writes.push(BasepriWrite {
+                        address,
+                        value: value as u8,
+                    }),
+                    None => function.restores += 1,
+                }
+            }
+            ("mov" | "movs" | "movw", [rd, imm]) if imm.starts_with('#') => {
+                if let Some(r) = register(rd) {
+                    regs[r] = immediate(imm);
+                }
+            }
+            // stores and comparisons only read their first operand
+            (m, _) if m.starts_with("str") || ["cmp", "cmn", "tst", "teq"].contains(&m) => {}
+            (_, [rd, ..]) => {
+                // any other write to a register invalidates its value
+                if let Some(r) = register(rd) {
+                    regs[r] = None;
+                }
+            }
+            _ => {}
+        }
+    }
+    functions
+}
+
+fn header(line: &str) -> Option<(u32, String)> {
+    let (address, rest) = line.trim_end().split_once(' ')?;
+    let name = rest.strip_prefix('<')?.strip_suffix(">:")?;
+    let address = u32::from_str_radix(address, 16).ok()?;
+    let name = format!("{:#}", rustc_demangle::demangle(name));
+    Some((address, name))
+}
+
+/// An instruction line, ` 8000196: 80 b5  push {r7, lr}`, as address,
+/// mnemonic and operands (raw bytes may be omitted).
+fn instruction(line: &str) -> Option<(u32, &str, &str)> {
+    let (address, rest) = line.split_once(':')?;
+    let address = u32::from_str_radix(address.trim(), 16).ok()?;
+    let mut fields = rest
+        .split('\t')
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .skip_while(|f| is_raw(f));
+    let mnemonic = fields.next()?;
+    // drop comments (`@ ...`, `; ...`)
+    let operands = fields.next().unwrap_or("");
+    let operands = operands.split(&['@', ';'][..]).next().unwrap_or("").trim();
+    Some((address, mnemonic, operands))
+}
+
+/// Raw instruction bytes, e.g. `80 b5` (llvm) or `b580` (GNU).
+fn is_raw(field: &str) -> bool {
+    field
+        .split(' ')
+        .all(|w| w.len() % 2 == 0 && w.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn register(op: &str) -> Option<usize> {
+    match op {
+        "sb" => Some(9),
+        "sl" => Some(10),
+        "fp" => Some(11),
+        "ip" => Some(12),
+        "sp" => Some(13),
+        "lr" => Some(14),
+        "pc" => Some(15),
+        _ => op.strip_prefix('r')?.parse().ok().filter(|&r| r < 16),
+    }
+}
+
+fn immediate(op: &str) -> Option<u32> {
+    let imm = op.strip_prefix('#')?;
+    match imm.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => imm.parse().ok(),
+    }
+}
+
+/// The tasks a function belongs to: the task function itself (and its
+/// closures, e.g. lock bodies), the handler of a hardware task, or a
+/// dispatcher (running the software tasks, possibly inlined). `init` is
+/// not considered, it runs with interrupts disabled.
+pub fn tasks_of<'a>(app: &'a App, function: &str) -> Vec<&'a str> {
+    let segments: Vec<&str> = function.split("::").collect();
+    let dispatcher = app.dispatchers.iter().any(|d| d == function);
+    app.tasks
+        .iter()
+        .filter(|t| t.kind != Kind::Init)
+        .filter(|t| {
+            segments.contains(&t.name.as_str())
+                || t.binds.as_deref() == Some(function)
+                || (dispatcher && t.kind == Kind::Software)
+        })
+        .map(|t| t.name.as_str())
+        .collect()
+}
+
+/// The ceilings of the queues locked by `spawn` and `schedule` in `task`
+/// (RTIC 0.5):
+///
+/// - the free queue of the spawned (scheduled) task, shared by the tasks
+///   spawning (scheduling) it,
+/// - the ready queue of its priority level, shared by the spawners (of
+///   the tasks at that level) and the timer queue handler (if any of them
+///   is scheduled),
+/// - the timer queue, shared by the schedulers and its handler, running
+///   at the highest priority of the scheduled tasks.
+///
+/// `init` is not considered, it runs with interrupts disabled.
+pub fn queue_ceilings(app: &App, task: &Task) -> BTreeSet<u8> {
+    let mut ceilings = BTreeSet::new();
+    if task.kind == Kind::Init {
+        return ceilings;
+    }
+    let senders = || app.tasks.iter().filter(|t| t.kind != Kind::Init);
+    let priority = |name: &str| app.task(name).map_or(0, |t| t.priority);
+    let scheduled = |name: &str| {
+        app.tasks
+            .iter()
+            .any(|t| t.schedule.iter().any(|s| s == name))
+    };
+    let timer_queue = app
+        .tasks
+        .iter()
+        .flat_map(|t| &t.schedule)
+        .map(|s| priority(s))
+        .max()
+        .unwrap_or(0);
+
+    let free_queue = |name: &str| {
+        senders()
+            .filter(|t| t.spawn.iter().chain(&t.schedule).any(|s| s == name))
+            .map(|t| t.priority)
+            .max()
+            .unwrap_or(0)
+    };
+    let ready_queue = |level: u8| {
+        let at_level = |s: &String| priority(s) == level;
+        let spawners = senders()
+            .filter(|t| t.spawn.iter().any(at_level))
+            .map(|t| t.priority);
+        let handler = app
+            .tasks
+            .iter()
+            .filter(|t| t.kind == Kind::Software && t.priority == level && scheduled(&t.name))
+            .map(|_| timer_queue);
+        spawners.chain(handler).max().unwrap_or(0)
+    };
+    let timer_queue_ceiling = senders()
+        .filter(|t| !t.schedule.is_empty())
+        .map(|t| t.priority)
+        .chain(Some(timer_queue))
+        .max()
+        .unwrap_or(0);
+
+    for s in &task.spawn {
+        ceilings.insert(free_queue(s));
+        ceilings.insert(ready_queue(priority(s)));
+    }
+    for s in &task.schedule {
+        ceilings.insert(free_queue(s));
+        ceilings.insert(timer_queue_ceiling);
+    }
+    ceilings
+}
+
+/// Outcome of the check of a single task.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskCheck {
+    pub task: String,
+    pub priority: u8,
+    /// Ceilings of the resources (and queues) locked by the task (above its
+    /// priority).
+    pub expected: BTreeSet<u8>,
+    /// Ceilings written in the functions of the task.
+    pub found: BTreeSet<u8>,
+}
+
+/// A BASEPRI write not matching any resource ceiling of its task(s).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    pub function: String,
+    pub tasks: Vec<String>,
+    pub write: BasepriWrite,
+    pub ceiling: u8,
+}
+
+/// The ceilings of an application compared to its code.
+pub struct Check {
+    pub bits: u8,
+    pub tasks: Vec<TaskCheck>,
+    pub mismatches: Vec<Mismatch>,
+    /// Writes in functions not attributed to any task.
+    pub unattributed: Vec<(String, BasepriWrite)>,
+}
+
+impl Check {
+    pub fn new(app: &App, functions: &[Function], bits: u8) -> Self {
+        let ceilings = app.ceilings();
+        let mut tasks: Vec<TaskCheck> = app
+            .tasks
+            .iter()
+            .filter(|t| t.kind != Kind::Init)
+            .map(|t| TaskCheck {
+                task: t.name.clone(),
+                priority: t.priority,
+                expected: t
+                    .resources
+                    .iter()
+                    .filter_map(|r| ceilings.get(r).copied())
+                    .chain(queue_ceilings(app, t))
+                    .filter(|&c| c > t.priority)
+                    .collect(),
+                found: BTreeSet::new(),
+            })
+            .collect();
+
+        let mut mismatches = Vec::new();
+        let mut unattributed = Vec::new();
+        for f in functions {
+            let owners = tasks_of(app, &f.name);
+            for &write in &f.writes {
+                let ceiling = match logical(write.value, bits) {
+                    Some(c) => c,
+                    None => continue,
+                };
+                if owners.is_empty() {
+                    unattributed.push((f.name.clone(), write));
+                    continue;
+                }
+                let mut matched = false;
+                let mut restore = false;
+                for t in tasks
+                    .iter_mut()
+                    .filter(|t| owners.contains(&t.task.as_str()))
+                {
+                    if t.expected.contains(&ceiling) {
+                        t.found.insert(ceiling);
+                        matched = true;
+                    }
+                    // the end of a lock, restoring the priority of the task
+                    restore |= t.priority == ceiling;
+                }
+                if !matched && !restore {
+                    mismatches.push(Mismatch {
+                        function: f.name.clone(),
+                        tasks: owners.iter().map(|t| t.to_string()).collect(),
+                        write,
+                        ceiling,
+                    });
+                }
+            }
+        }
+        Check {
+            bits,
+            tasks,
+            mismatches,
+            unattributed,
+        }
+    }
+
+    /// No write contradicts the model.
+    pub fn success(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+fn list(set: &BTreeSet<u8>) -> String {
+    if set.is_empty() {
+        "-".into()
+    } else {
+        set.iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<12} {:>4} {:<12} {:<12} status",
+            "task", "prio", "expected", "found"
+        )?;
+        for t in &self.tasks {
+            let missing: BTreeSet<u8> = t.expected.difference(&t.found).copied().collect();
+            writeln!(
+                f,
+                "{:<12} {:>4} {:<12} {:<12} {}",
+                t.task,
+                t.priority,
+                list(&t.expected),
+                list(&t.found),
+                if !missing.is_empty() {
+                    format!("not found {} (unused, or not inlined)", list(&missing))
+                } else {
+                    "ok".into()
+                }
+            )?;
+        }
+        for m in &self.mismatches {
+            writeln!(
+                f,
+                "error: {} ({}) writes BASEPRI {:#04x} (ceiling {}) at {:#010x}, not a ceiling of its resources",
+                m.function,
+                m.tasks.join(", "),
+                m.write.value,
+                m.ceiling,
+                m.write.address
+            )?;
+        }
+        for (function, w) in &self.unattributed {
+            writeln!(
+                f,
+                "note: {} writes BASEPRI {:#04x} (ceiling {}) at {:#010x}, not attributed to a task",
+                function,
+                w.value,
+                logical(w.value, self.bits).unwrap_or(0),
+                w.address
+            )?;
+        }
+        write!(
+            f,
+            "ceiling check: {}",
+            if self.success() { "ok" } else { "FAILED" }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn fixture() -> Vec<Function> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/timing_exam.objdump");
+        scan(&std::fs::read_to_string(path).unwrap())
+    }
+
+    fn write(address: u32, value: u8) -> BasepriWrite {
+        BasepriWrite { address, value }
+    }
+
+    #[test]
+    fn encoding() {
+        assert_eq!(hardware(2, 4), 0xe0);
+        assert_eq!(hardware(3, 4), 0xd0);
+        assert_eq!(hardware(1, 3), 0xe0);
+        assert_eq!(logical(0, 4), None);
+        // the unimplemented low bits are ignored
+        assert_eq!(logical(0xd5, 4), Some(3));
+        for bits in 1..=8 {
+            for p in 1..1u16 << bits {
+                let p = p as u8;
+                assert_eq!(logical(hardware(p, bits), bits), Some(p), "{} bits", bits);
+            }
+        }
+    }
+
+    #[test]
+    fn scan_llvm_objdump() {
+        let functions = fixture();
+        let function = |name: &str| functions.iter().find(|f| f.name == name).unwrap();
+        // mapping symbols (`$t`, `$d`) are not functions
+        assert!(functions.iter().all(|f| !f.name.starts_with('$')));
+
+        // `movs` and `mov.w` immediates, priority 1 (0xf0), the timer queue
+        // (0xd0), and unmasked (0)
+        let t1 = function("timing_exam::t1");
+        assert_eq!(t1.address, 0x0800_0468);
+        assert_eq!(
+            t1.writes,
+            [
+                write(0x0800_04cc, 0xf0),
+                write(0x0800_04f0, 0),
+                write(0x0800_04fe, 0xd0),
+                write(0x0800_064c, 0),
+            ]
+        );
+        assert_eq!(t1.restores, 1);
+
+        // the dispatcher restores BASEPRI read by `mrs`
+        let exti0 = function("EXTI0");
+        assert!(exti0.writes.is_empty());
+        assert_eq!(exti0.restores, 1);
+
+        // the code after `$d`/`$t` is part of the timer queue handler
+        assert_eq!(function("SysTick").restores, 1);
+
+        // `init` runs with interrupts disabled
+        assert!(function("main").writes.is_empty());
+    }
+
+    #[test]
+    fn scan_gnu_objdump() {
+        let functions = scan(
+            "08000194 <EXTI0>:\n \
+             8000194:\t20d0      \tmovs\tr0, #208\t; 0xd0\n \
+             8000196:\tf380 8811 \tmsr\tBASEPRI, r0\n \
+             800019a:\tf380 8812 \tmsr\tBASEPRI_MAX, r0\n",
+        );
+        assert_eq!(functions.len(), 1);
+        assert_eq!(
+            functions[0].writes,
+            [write(0x0800_0196, 0xd0), write(0x0800_019a, 0xd0)]
+        );
+    }
+
+    #[test]
+    fn check_timing_exam() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples/timing_exam.rs");
+        let app = App::load(&path).unwrap();
+        let check = Check::new(&app, &fixture(), NVIC_PRIO_BITS);
+
+        // t2 locks R2, t1 and t2 the timer queue (`schedule`), both at 3,
+        // the writes of their own priorities (and of t3) are restores
+        for (task, expected) in [("t1", vec![3]), ("t2", vec![3]), ("t3", vec![])] {
+            let t = check.tasks.iter().find(|t| t.task == task).unwrap();
+            assert_eq!(t.expected, expected.into_iter().collect(), "{}", task);
+            assert_eq!(t.found, t.expected, "{}", task);
+        }
+        assert!(check.success(), "{}", check);
+        assert!(check.unattributed.is_empty());
+    }
+
+    fn app() -> App {
+        App::parse(
+            r#"
+            #[rtic::app(device = stm32f4::stm32f411, monotonic = rtic::cyccnt::CYCCNT)]
+            const APP: () = {
+                struct Resources {
+                    #[init(0)]
+                    x: u32,
+                }
+
+                #[init(schedule = [t1, t3])]
+                fn init(_: init::Context) {}
+
+                #[task(binds = EXTI0, priority = 2, resources = [x], spawn = [t1])]
+                fn h(_: h::Context) {}
+
+                #[task(priority = 1, resources = [x], schedule = [t1])]
+                fn t1(_: t1::Context) {}
+
+                #[task(priority = 3, schedule = [t3])]
+                fn t3(_: t3::Context) {}
+
+                extern "C" {
+                    fn EXTI1();
+                    fn EXTI2();
+                }
+            };
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn queues() {
+        let app = app();
+        let ceilings = |task| queue_ceilings(&app, app.task(task).unwrap());
+        // the free queue of t1 is shared by t1 and h, the timer queue
+        // handler runs at 3 (t3)
+        assert_eq!(ceilings("t1"), BTreeSet::from([2, 3]));
+        // the ready queue of priority 1 is shared by h and the handler
+        assert_eq!(ceilings("h"), BTreeSet::from([2, 3]));
+        assert_eq!(ceilings("t3"), BTreeSet::from([3]));
+        assert!(ceilings("init").is_empty());
+    }
+
+    #[test]
+    fn restores() {
+        let app = app();
+        let function = |name: &str, writes: Vec<BasepriWrite>| Function {
+            address: 0,
+            name: name.into(),
+            writes,
+            restores: 0,
+        };
+        let functions = [
+            // x (2), the timer queue (3), and back to the priority of t1
+            function(
+                "app::t1",
+                vec![write(0x10, 0xe0), write(0x14, 0xd0), write(0x18, 0xf0)],
+            ),
+            // priority 1 restores t1, not h (priority 2)
+            function("app::h", vec![write(0x20, 0xd0), write(0x24, 0xf0)]),
+        ];
+        let check = Check::new(&app, &functions, 4);
+        let t1 = check.tasks.iter().find(|t| t.task == "t1").unwrap();
+        assert_eq!(t1.found, BTreeSet::from([2, 3]));
+        assert_eq!(check.mismatches.len(), 1);
+        assert_eq!(check.mismatches[0].tasks, ["h"]);
+        assert_eq!(check.mismatches[0].write, write(0x24, 0xf0));
+        assert_eq!(check.mismatches[0].ceiling, 1);
+    }
+}
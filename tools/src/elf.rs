@@ -0,0 +1,84 @@
+//! elf.rs
+//!
+//! Symbol lookup in the firmware ELF.
+
+use std::{fs, io, path::Path};
+
+use object::{Object, ObjectSymbol, SymbolKind};
+
+pub struct Symbol {
+    pub address: u32,
+    pub size: u32,
+    pub name: String,
+}
+
+/// Function symbols sorted by address, and data symbols (statics).
+pub struct Symbols {
+    symbols: Vec<Symbol>,
+    data: Vec<Symbol>,
+}
+
+impl Symbols {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        let file = object::File::parse(&*data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+        let collect = |kind| -> Vec<Symbol> {
+            let mut symbols: Vec<_> = file
+                .symbols()
+                .filter(|s| s.kind() == kind && s.size() > 0)
+                .filter_map(|s| {
+                    Some(Symbol {
+                        // clear the thumb bit
+                        address: s.address() as u32 & !1,
+                        size: s.size() as u32,
+                        name: format!("{:#}", rustc_demangle::demangle(s.name().ok()?)),
+                    })
+                })
+                .collect();
+            symbols.sort_by_key(|s| s.address);
+            symbols
+        };
+        Ok(Symbols {
+            symbols: collect(SymbolKind::Text),
+            data: collect(SymbolKind::Data),
+        })
+    }
+
+    /// The function containing `pc`.
+    pub fn lookup(&self, pc: u32) -> Option<&Symbol> {
+        let i = self.symbols.partition_point(|s| s.address <= pc);
+        let s = self.symbols.get(i.checked_sub(1)?)?;
+        if pc < s.address + s.size {
+            Some(s)
+        } else {
+            None
+        }
+    }
+
+    /// The address of the function named `name`.
+    pub fn address(&self, name: &str) -> Option<u32> {
+        find(&self.symbols, name).map(|s| s.address)
+    }
+
+    /// The static named `name`, e.g., `T1_MAX_RP`.
+    pub fn variable(&self, name: &str) -> Option<&Symbol> {
+        find(&self.data, name)
+    }
+
+    /// Formats `pc` as `symbol+offset`.
+    pub fn describe(&self, pc: u32) -> String {
+        match self.lookup(pc) {
+            Some(s) => format!("{}+{:#x}", s.name, pc - s.address),
+            None => format!("{:#010x}", pc),
+        }
+    }
+}
+
+fn find<'a>(symbols: &'a [Symbol], name: &str) -> Option<&'a Symbol> {
+    let suffix = format!("::{}", name);
+    symbols
+        .iter()
+        .find(|s| s.name == name || s.name.ends_with(&suffix))
+}
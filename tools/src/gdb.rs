@@ -0,0 +1,444 @@
+//! gdb.rs
+//!
+//! Minimal client for the GDB Remote Serial Protocol (RSP).
+//!
+//! Only what is needed to drive a Cortex-M target through `openocd` (or a
+//! simulator stub) is implemented: continue, interrupt, register and memory
+//! access, and monitor commands.
+//!
+//! https://sourceware.org/gdb/onlinedocs/gdb/Remote-Protocol.html
+
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+/// Register number of the program counter (ARM target description).
+pub const PC: u8 = 15;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// Malformed or unexpected packet.
+    Protocol(String),
+    /// Error reply (`E NN`) from the stub.
+    Target(String),
+    /// The stub did not reply in time.
+    Timeout,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "i/o error: {}", e),
+            Error::Protocol(s) => write!(f, "protocol error: {}", s),
+            Error::Target(s) => write!(f, "target error: {}", s),
+            Error::Timeout => write!(f, "timeout waiting for the gdb stub"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Error::Timeout,
+            _ => Error::Io(e),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Reason for the target to stop.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    /// Halted by a signal (5 = SIGTRAP for breakpoints).
+    Signal(u8),
+    /// The program exited (e.g. by semihosting `exit`) with a status.
+    Exited(u8),
+    /// The program was terminated by a signal.
+    Terminated(u8),
+}
+
+pub struct Client {
+    stream: TcpStream,
+    buf: Vec<u8>,
+    pos: usize,
+    no_ack: bool,
+    /// Console output (`O` packets) received while running.
+    pub console: String,
+}
+
+impl Client {
+    /// Connects to a stub, e.g., `localhost:3333` for `openocd`.
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        let mut client = Client {
+            stream,
+            buf: Vec::new(),
+            pos: 0,
+            no_ack: false,
+            console: String::new(),
+        };
+        client.set_timeout(Some(Duration::from_secs(5)))?;
+        client.command("qSupported:swbreak+;hwbreak+")?;
+        if client.command("QStartNoAckMode")? == "OK" {
+            client.no_ack = true;
+        }
+        Ok(client)
+    }
+
+    /// Sets the timeout for replies, `None` waits forever.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.stream.set_read_timeout(timeout)?;
+        Ok(())
+    }
+
+    /// Sends a packet and returns the reply.
+    pub fn command(&mut self, cmd: &str) -> Result<String> {
+        self.send_packet(cmd)?;
+        let reply = self.recv_packet()?;
+        if reply.len() == 3 && reply.starts_with('E') {
+            Err(Error::Target(format!("`{}` replied {}", cmd, reply)))
+        } else {
+            Ok(reply)
+        }
+    }
+
+    /// Queries the current stop reason.
+    pub fn status(&mut self) -> Result<Stop> {
+        self.send_packet("?")?;
+        self.wait_stop()
+    }
+
+    /// Continues execution and waits until the target stops.
+    pub fn cont(&mut self) -> Result<Stop> {
+        self.send_packet("c")?;
+        self.wait_stop()
+    }
+
+    /// Interrupts (halts) the running target.
+    pub fn interrupt(&mut self) -> Result<Stop> {
+        self.stream.write_all(&[0x03])?;
+        self.wait_stop()
+    }
+
+    /// Runs a monitor command (e.g., `reset init` for `openocd`).
+    pub fn monitor(&mut self, cmd: &str) -> Result<String> {
+        self.send_packet(&format!("qRcmd,{}", to_hex(cmd.as_bytes())))?;
+        let mut output = String::new();
+        loop {
+            let reply = self.recv_packet()?;
+            match reply.as_bytes() {
+                [b'O', b'K'] => return Ok(output),
+                [b'O', hex @ ..] => output.push_str(&String::from_utf8_lossy(&from_hex(hex)?)),
+                [] => return Err(Error::Protocol("monitor commands not supported".into())),
+                _ if reply.starts_with('E') => {
+                    return Err(Error::Target(format!("monitor `{}` replied {}", cmd, reply)))
+                }
+                _ => return Ok(output),
+            }
+        }
+    }
+
+    pub fn read_memory(&mut self, addr: u32, len: usize) -> Result<Vec<u8>> {
+        let reply = self.command(&format!("m{:x},{:x}", addr, len))?;
+        let data = from_hex(reply.as_bytes())?;
+        if data.len() != len {
+            return Err(Error::Protocol(format!(
+                "read of {} bytes at {:#010x} returned {} bytes",
+                len,
+                addr,
+                data.len()
+            )));
+        }
+        Ok(data)
+    }
+
+    pub fn read_u32(&mut self, addr: u32) -> Result<u32> {
+        let b = self.read_memory(addr, 4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub fn read_u16(&mut self, addr: u32) -> Result<u16> {
+        let b = self.read_memory(addr, 2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    pub fn write_memory(&mut self, addr: u32, data: &[u8]) -> Result<()> {
+        self.expect_ok(&format!("M{:x},{:x}:{}", addr, data.len(), to_hex(data)))
+    }
+
+    pub fn write_u32(&mut self, addr: u32, value: u32) -> Result<()> {
+        self.write_memory(addr, &value.to_le_bytes())
+    }
+
+    /// Reads a (32 bit) core register, falls back to `g` if the stub does
+    /// not implement `p`.
+    pub fn read_register(&mut self, n: u8) -> Result<u32> {
+        let reply = self.command(&format!("p{:x}", n))?;
+        let hex = if reply.is_empty() {
+            let all = self.command("g")?;
+            let start = n as usize * 8;
+            all.get(start..start + 8)
+                .ok_or_else(|| Error::Protocol(format!("register {} not in `g` reply", n)))?
+                .to_string()
+        } else {
+            reply
+        };
+        let b = from_hex(hex.as_bytes())?;
+        if b.len() < 4 {
+            return Err(Error::Protocol(format!("short register value `{}`", hex)));
+        }
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub fn write_register(&mut self, n: u8, value: u32) -> Result<()> {
+        self.expect_ok(&format!("P{:x}={}", n, to_hex(&value.to_le_bytes())))
+    }
+
+    /// Detaches, leaving the target running.
+    pub fn detach(&mut self) -> Result<()> {
+        self.expect_ok("D")
+    }
+
+    fn expect_ok(&mut self, cmd: &str) -> Result<()> {
+        match self.command(cmd)?.as_str() {
+            "OK" => Ok(()),
+            reply => Err(Error::Protocol(format!("`{}` replied `{}`", cmd, reply))),
+        }
+    }
+
+    fn wait_stop(&mut self) -> Result<Stop> {
+        loop {
+            let reply = self.recv_packet()?;
+            let bytes = reply.as_bytes();
+            match bytes.first() {
+                Some(b'O') if bytes.len() > 1 => {
+                    let text = from_hex(&bytes[1..])?;
+                    self.console.push_str(&String::from_utf8_lossy(&text));
+                }
+                Some(b'S') | Some(b'T') => return Ok(Stop::Signal(hex_u8(&bytes[1..])?)),
+                Some(b'W') => return Ok(Stop::Exited(hex_u8(&bytes[1..])?)),
+                Some(b'X') => return Ok(Stop::Terminated(hex_u8(&bytes[1..])?)),
+                _ => return Err(Error::Protocol(format!("unexpected stop reply `{}`", reply))),
+            }
+        }
+    }
+
+    fn send_packet(&mut self, data: &str) -> Result<()> {
+        let mut packet = Vec::with_capacity(data.len() + 4);
+        packet.push(b'$');
+        for &b in data.as_bytes() {
+            // `#`, `$`, `}` and `*` must be escaped
+            if let b'#' | b'$' | b'}' | b'*' = b {
+                packet.push(b'}');
+                packet.push(b ^ 0x20);
+            } else {
+                packet.push(b);
+            }
+        }
+        let checksum = packet[1..].iter().fold(0u8, |c, &b| c.wrapping_add(b));
+        packet.extend_from_slice(format!("#{:02x}", checksum).as_bytes());
+
+        loop {
+            self.stream.write_all(&packet)?;
+            if self.no_ack {
+                return Ok(());
+            }
+            match self.read_byte()? {
+                b'+' => return Ok(()),
+                b'-' => continue,
+                b => {
+                    return Err(Error::Protocol(format!(
+                        "expected ack, got `{}`",
+                        b as char
+                    )))
+                }
+            }
+        }
+    }
+
+    fn recv_packet(&mut self) -> Result<String> {
+        loop {
+            // skip acks and anything else up to the start of a packet
+            while self.read_byte()? != b'$' {}
+
+            let mut data = Vec::new();
+            let mut checksum = 0u8;
+            loop {
+                let b = self.read_byte()?;
+                if b == b'#' {
+                    break;
+                }
+                checksum = checksum.wrapping_add(b);
+                match b {
+                    b'}' => {
+                        let e = self.read_byte()?;
+                        checksum = checksum.wrapping_add(e);
+                        data.push(e ^ 0x20);
+                    }
+                    // run length encoding, the count is `n - 29`
+                    b'*' => {
+                        let n = self.read_byte()?;
+                        checksum = checksum.wrapping_add(n);
+                        let last = *data
+                            .last()
+                            .ok_or_else(|| Error::Protocol("run length without data".into()))?;
+                        for _ in 0..(n as usize).saturating_sub(29) {
+                            data.push(last);
+                        }
+                    }
+                    _ => data.push(b),
+                }
+            }
+            let expected = hex_u8(&[self.read_byte()?, self.read_byte()?])?;
+
+            if expected != checksum && !self.no_ack {
+                self.stream.write_all(b"-")?;
+                continue;
+            }
+            if !self.no_ack {
+                self.stream.write_all(b"+")?;
+            }
+            return String::from_utf8(data)
+                .map_err(|_| Error::Protocol("packet is not valid utf-8".into()));
+        }
+    }
+
+    fn read_byte(&mut self) -> Result<u8> {
+        if self.pos == self.buf.len() {
+            self.buf.resize(4096, 0);
+            let n = self.stream.read(&mut self.buf)?;
+            if n == 0 {
+                return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+            }
+            self.buf.truncate(n);
+            self.pos = 0;
+        }
+        let b = self.buf[self.pos];
+        self.pos += 1;
+        Ok(b)
+    }
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &[u8]) -> Result<Vec<u8>> {
+    hex.chunks(2).map(hex_u8).collect()
+}
+
+fn hex_u8(hex: &[u8]) -> Result<u8> {
+    std::str::from_utf8(hex.get(..2).unwrap_or(hex))
+        .ok()
+        .and_then(|s| u8::from_str_radix(s, 16).ok())
+        .ok_or_else(|| Error::Protocol(format!("bad hex `{}`", String::from_utf8_lossy(hex))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// A client, without the handshake, and the stub end of the connection.
+    fn pair(no_ack: bool) -> (Client, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stub, _) = listener.accept().unwrap();
+        stub.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let client = Client {
+            stream,
+            buf: Vec::new(),
+            pos: 0,
+            no_ack,
+            console: String::new(),
+        };
+        client
+            .stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        (client, stub)
+    }
+
+    fn read(stub: &mut TcpStream, n: usize) -> String {
+        let mut buf = vec![0; n];
+        stub.read_exact(&mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn send() {
+        let (mut client, mut stub) = pair(true);
+        client.send_packet("OK").unwrap();
+        client.send_packet("g").unwrap();
+        assert_eq!(read(&mut stub, 6 + 5), "$OK#9a$g#67");
+        // `}` is escaped as `}]`, the checksum covers the escaped data
+        client.send_packet("X}").unwrap();
+        assert_eq!(read(&mut stub, 7), "$X}]#32");
+    }
+
+    #[test]
+    fn send_retransmits_on_nack() {
+        let (mut client, mut stub) = pair(false);
+        stub.write_all(b"-+").unwrap();
+        client.send_packet("c").unwrap();
+        assert_eq!(read(&mut stub, 10), "$c#63$c#63");
+
+        stub.write_all(b"x").unwrap();
+        assert!(matches!(client.send_packet("c"), Err(Error::Protocol(_))));
+    }
+
+    #[test]
+    fn receive() {
+        let (mut client, mut stub) = pair(false);
+        // leading ack skipped, the reply acked
+        stub.write_all(b"+$OK#9a").unwrap();
+        assert_eq!(client.recv_packet().unwrap(), "OK");
+        assert_eq!(read(&mut stub, 1), "+");
+
+        // a bad checksum is nacked, and the retransmission accepted
+        stub.write_all(b"$OK#00$OK#9a").unwrap();
+        assert_eq!(client.recv_packet().unwrap(), "OK");
+        assert_eq!(read(&mut stub, 2), "-+");
+    }
+
+    #[test]
+    fn receive_escaped_and_run_length() {
+        let (mut client, mut stub) = pair(true);
+        // `}]` is `}`
+        stub.write_all(b"$}]#da").unwrap();
+        assert_eq!(client.recv_packet().unwrap(), "}");
+        // `0* ` is `0` repeated 32 - 29 times more
+        stub.write_all(b"$0* #7a").unwrap();
+        assert_eq!(client.recv_packet().unwrap(), "0000");
+        stub.write_all(b"$*!#4b").unwrap();
+        assert!(matches!(client.recv_packet(), Err(Error::Protocol(_))));
+    }
+
+    #[test]
+    fn replies() {
+        // checksums are not verified without acks
+        let (mut client, mut stub) = pair(true);
+        // console output, then the stop reply
+        stub.write_all(b"$O6869#2c$T05#b9").unwrap();
+        assert_eq!(client.cont().unwrap(), Stop::Signal(5));
+        assert_eq!(client.console, "hi");
+        assert_eq!(read(&mut stub, 5), "$c#63");
+
+        stub.write_all(b"$W01#b8").unwrap();
+        assert_eq!(client.status().unwrap(), Stop::Exited(1));
+
+        stub.write_all(b"$E01#a6").unwrap();
+        assert!(matches!(client.command("m0,4"), Err(Error::Target(_))));
+
+        stub.write_all(b"$78563412#a4").unwrap();
+        assert_eq!(client.read_u32(0x2000_0000).unwrap(), 0x1234_5678);
+    }
+}
@@ -0,0 +1,181 @@
+//! harness.rs
+//!
+//! Automated breakpoint sessions.
+//!
+//! The examples mark points of interest with `asm::bkpt()`. Instead of
+//! `(gdb) c` and `(gdb) x 0xe0001004` by hand, the harness continues the
+//! target, and records the PC and the cycle counter (CYCCNT) at each stop.
+
+use std::{fmt, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    elf::Symbols,
+    gdb::{self, Client, Stop},
+};
+
+/// Address of the DWT cycle counter (CYCCNT).
+pub const CYCCNT: u32 = 0xe000_1004;
+
+/// Encoding of `bkpt #imm` (Thumb), `0xbeXX`.
+const BKPT_MASK: u16 = 0xff00;
+const BKPT: u16 = 0xbe00;
+
+pub struct Config {
+    /// Maximum number of breakpoints to record.
+    pub max_stops: usize,
+    /// Time to wait for the target to stop at each breakpoint.
+    pub timeout: Duration,
+    /// Step over `bkpt` instructions when continuing. `openocd` does this
+    /// on its own, simulator stubs typically do not.
+    pub skip_bkpt: bool,
+    /// Names of the breakpoints, in order of arrival.
+    pub labels: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            max_stops: 16,
+            timeout: Duration::from_secs(5),
+            skip_bkpt: false,
+            labels: Vec::new(),
+        }
+    }
+}
+
+/// A single breakpoint hit.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sample {
+    pub label: String,
+    pub pc: u32,
+    pub location: String,
+    /// `true` if stopped at a `bkpt` instruction.
+    pub bkpt: bool,
+    pub cyccnt: u32,
+    /// Cycles since the previous breakpoint.
+    pub delta: u32,
+}
+
+/// The breakpoints recorded by a session.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Report {
+    pub samples: Vec<Sample>,
+    /// Exit status if the target exited (e.g., by semihosting under QEMU,
+    /// openocd does not report it to a connected client).
+    pub exit: Option<u8>,
+}
+
+impl Report {
+    /// The sample labeled `label`.
+    pub fn get(&self, label: &str) -> Option<&Sample> {
+        self.samples.iter().find(|s| s.label == label)
+    }
+
+    /// Cycles between the samples labeled `from` and `to`.
+    pub fn between(&self, from: &str, to: &str) -> Option<u32> {
+        Some(self.get(to)?.cyccnt.wrapping_sub(self.get(from)?.cyccnt))
+    }
+}
+
+/// Continues the (halted) target until `max_stops` breakpoints are
+/// recorded or the target exits.
+pub fn run(client: &mut Client, symbols: Option<&Symbols>, config: &Config) -> gdb::Result<Report> {
+    client.set_timeout(Some(config.timeout))?;
+    let mut report = Report::default();
+    let mut previous = None;
+
+    while report.samples.len() < config.max_stops {
+        if config.skip_bkpt {
+            step_over_bkpt(client)?;
+        }
+        match client.cont()? {
+            Stop::Signal(_) => {}
+            Stop::Exited(status) | Stop::Terminated(status) => {
+                report.exit = Some(status);
+                break;
+            }
+        }
+
+        let pc = client.read_register(gdb::PC)?;
+        let cyccnt = client.read_u32(CYCCNT)?;
+        let bkpt = is_bkpt(client, pc)?;
+        let index = report.samples.len();
+        report.samples.push(Sample {
+            label: config
+                .labels
+                .get(index)
+                .cloned()
+                .unwrap_or_else(|| index.to_string()),
+            pc,
+            location: symbols.map_or_else(|| format!("{:#010x}", pc), |s| s.describe(pc)),
+            bkpt,
+            cyccnt,
+            delta: previous.map_or(0, |p: u32| cyccnt.wrapping_sub(p)),
+        });
+        previous = Some(cyccnt);
+    }
+    Ok(report)
+}
+
+/// Continues the (halted) target for `duration` and halts it, unless it
+/// stops on its own before.
+pub fn run_for(client: &mut Client, duration: Duration) -> gdb::Result<Stop> {
+    client.set_timeout(Some(duration))?;
+    match client.cont() {
+        Err(gdb::Error::Timeout) => client.interrupt(),
+        stop => stop,
+    }
+}
+
+fn is_bkpt(client: &mut Client, pc: u32) -> gdb::Result<bool> {
+    Ok(client.read_u16(pc)? & BKPT_MASK == BKPT)
+}
+
+fn step_over_bkpt(client: &mut Client) -> gdb::Result<()> {
+    let pc = client.read_register(gdb::PC)?;
+    if is_bkpt(client, pc)? {
+        client.write_register(gdb::PC, pc + 2)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .samples
+            .iter()
+            .map(|s| s.location.len())
+            .max()
+            .unwrap_or(0)
+            .max(8);
+        writeln!(
+            f,
+            "{:<8} {:<10} {:<width$} {:>10} {:>10}",
+            "label",
+            "pc",
+            "location",
+            "cyccnt",
+            "delta",
+            width = width
+        )?;
+        for s in &self.samples {
+            writeln!(
+                f,
+                "{:<8} {:#010x} {:<width$} {:>10} {:>10}{}",
+                s.label,
+                s.pc,
+                s.location,
+                s.cyccnt,
+                s.delta,
+                if s.bkpt { "" } else { "  (not a bkpt)" },
+                width = width
+            )?;
+        }
+        if let Some(status) = self.exit {
+            writeln!(f, "target exited with status {}", status)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+//! latency.rs
+//!
+//! Report of the interrupt latency characterization (`examples/latency.rs`).
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// The cases, in order of the `LATENCY` table of the example.
+pub const CASES: [&str; 8] = [
+    "idle",
+    "basepri",
+    "tail-chain",
+    "late-arrival, systick",
+    "1 wait state",
+    "2 wait states",
+    "3 wait states",
+    "3 wait states, caches",
+];
+
+/// Name of the table in the example.
+pub const SYMBOL: &str = "LATENCY";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Case {
+    pub name: String,
+    pub min: u32,
+    pub max: u32,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Report {
+    pub cases: Vec<Case>,
+}
+
+impl Report {
+    /// Decodes the `LATENCY` table (`[[u32; 2]; CASES]`, little endian).
+    pub fn decode(table: &[u8]) -> Self {
+        let words: Vec<u32> = table
+            .chunks_exact(4)
+            .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+            .collect();
+        Report {
+            cases: CASES
+                .iter()
+                .zip(words.chunks_exact(2))
+                .map(|(name, w)| Case {
+                    name: name.to_string(),
+                    min: w[0],
+                    max: w[1],
+                })
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "| {:<24} | {:>6} | {:>6} |", "case", "min", "max")?;
+        writeln!(f, "|{:-<26}|{:->8}|{:->8}|", "", "", "")?;
+        for c in &self.cases {
+            if c.min > c.max {
+                // never updated
+                writeln!(f, "| {:<24} | {:>6} | {:>6} |", c.name, "-", "-")?;
+            } else {
+                writeln!(f, "| {:<24} | {:>6} | {:>6} |", c.name, c.min, c.max)?;
+            }
+        }
+        Ok(())
+    }
+}
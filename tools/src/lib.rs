@@ -0,0 +1,19 @@
+//! lib.rs
+//!
+//! Host side tooling for the RTIC examples.
+
+pub mod analysis;
+pub mod bench;
+pub mod binlog;
+pub mod ceilings;
+pub mod elf;
+pub mod gdb;
+pub mod harness;
+pub mod latency;
+pub mod model;
+pub mod priorities;
+pub mod regress;
+pub mod sensitivity;
+pub mod sim;
+pub mod target_test;
+pub mod task_set;
@@ -0,0 +1,475 @@
+//! model.rs
+//!
+//! The task model of an RTIC application, extracted from its source.
+//!
+//! The `#[rtic::app]` item (`const APP: () = { .. }` in RTIC 0.5, or
+//! `mod app { .. }`) is parsed, and its tasks (`init`, `idle`, hardware and
+//! software tasks), their priorities, bindings, `spawn`/`schedule` targets
+//! and resources are collected, along with the resources and the
+//! dispatchers (`extern "C"` interrupts).
+//!
+//! Only the source is inspected, nothing is compiled or expanded, so
+//! priorities must be literals (as in all the examples).
+
+use std::{collections::BTreeMap, error::Error, fmt, fs, path::Path};
+
+use quote::ToTokens;
+use serde::{Deserialize, Serialize};
+use syn::{
+    punctuated::Punctuated, spanned::Spanned, Attribute, Expr, ExprArray, ExprLit, ForeignItem,
+    Item, Lit, Meta, MetaNameValue, Token,
+};
+
+/// An RTIC application.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct App {
+    pub device: Option<String>,
+    pub monotonic: Option<String>,
+    pub peripherals: bool,
+    pub resources: Vec<Resource>,
+    /// `init`, `idle` and the tasks, in source order.
+    pub tasks: Vec<Task>,
+    /// Interrupts used to dispatch software tasks.
+    pub dispatchers: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Resource {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    /// Initialized by `init` (late), rather than by `#[init(..)]`.
+    pub late: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Init,
+    Idle,
+    /// Bound to an interrupt (`binds`).
+    Hardware,
+    /// Spawned or scheduled, run by a dispatcher.
+    Software,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Task {
+    pub name: String,
+    pub kind: Kind,
+    /// Logical priority (0 for `init` and `idle`).
+    pub priority: u8,
+    pub binds: Option<String>,
+    /// Message queue capacity (software tasks).
+    pub capacity: Option<u32>,
+    pub resources: Vec<String>,
+    pub spawn: Vec<String>,
+    pub schedule: Vec<String>,
+    /// Line of the task attribute in the source.
+    pub line: usize,
+    /// Where the priority is set in the source (not kept in JSON).
+    #[serde(skip)]
+    pub priority_source: Option<PrioritySource>,
+}
+
+/// A position in the source, the line (1-based) and column (0-based, in
+/// characters).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl From<proc_macro2::LineColumn> for Location {
+    fn from(lc: proc_macro2::LineColumn) -> Self {
+        Location {
+            line: lc.line,
+            column: lc.column,
+        }
+    }
+}
+
+/// Where the priority of a task is set in its attribute.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrioritySource {
+    /// The value of the `priority` argument, from start to end.
+    Value(Location, Location),
+    /// No `priority` argument, the arguments start here (after the `(`).
+    Arguments(Location),
+    /// No arguments, the attribute path (`#[task]`) ends here.
+    Path(Location),
+}
+
+impl App {
+    /// Extracts the model from the application source.
+    pub fn parse(src: &str) -> syn::Result<Self> {
+        let file = syn::parse_file(src)?;
+        for item in file.items {
+            let (attrs, items) = match item {
+                Item::Const(c) => match *c.expr {
+                    Expr::Block(b) => (
+                        c.attrs,
+                        b.block
+                            .stmts
+                            .into_iter()
+                            .filter_map(|s| match s {
+                                syn::Stmt::Item(item) => Some(item),
+                                _ => None,
+                            })
+                            .collect(),
+                    ),
+                    _ => continue,
+                },
+                Item::Mod(m) => match m.content {
+                    Some((_, items)) => (m.attrs, items),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            if let Some(attr) = attrs.iter().find(|a| is_app(a)) {
+                return App::from_items(attr, items);
+            }
+        }
+        Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "no `#[rtic::app]` item found",
+        ))
+    }
+
+    /// Loads the model from the application source, or from JSON (as
+    /// written by the `model` binary) for `.json` files.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let src = fs::read_to_string(path)?;
+        if path.extension().is_some_and(|e| e == "json") {
+            return Ok(serde_json::from_str(&src)?);
+        }
+        App::parse(&src).map_err(|e| {
+            let start = e.span().start();
+            format!(
+                "{}:{}:{}: {}",
+                path.display(),
+                start.line,
+                start.column + 1,
+                e
+            )
+            .into()
+        })
+    }
+
+    pub fn task(&self, name: &str) -> Option<&Task> {
+        self.tasks.iter().find(|t| t.name == name)
+    }
+
+    /// The ceiling of each resource, the highest priority of the tasks
+    /// using it (`init` excluded, it runs with interrupts disabled).
+    pub fn ceilings(&self) -> BTreeMap<String, u8> {
+        let mut ceilings = BTreeMap::new();
+        for task in self.tasks.iter().filter(|t| t.kind != Kind::Init) {
+            for r in &task.resources {
+                let c = ceilings.entry(r.clone()).or_insert(0);
+                *c = (*c).max(task.priority);
+            }
+        }
+        ceilings
+    }
+
+    fn from_items(attr: &Attribute, items: Vec<Item>) -> syn::Result<Self> {
+        let mut app = App::default();
+        for arg in args(attr)? {
+            match key(&arg).as_str() {
+                "device" => app.device = Some(path(&arg.value)?),
+                "monotonic" => app.monotonic = Some(path(&arg.value)?),
+                "peripherals" => app.peripherals = bool(&arg.value)?,
+                _ => {}
+            }
+        }
+
+        for item in items {
+            match item {
+                Item::Struct(s) if s.ident == "Resources" => {
+                    for field in s.fields {
+                        app.resources.push(Resource {
+                            name: field
+                                .ident
+                                .as_ref()
+                                .map(|i| i.to_string())
+                                .unwrap_or_default(),
+                            ty: tokens(&field.ty),
+                            late: !field.attrs.iter().any(|a| a.path().is_ident("init")),
+                        });
+                    }
+                }
+                Item::Fn(f) => {
+                    if let Some((kind, attr)) = f.attrs.iter().find_map(|a| {
+                        let kind = match a.path().get_ident()?.to_string().as_str() {
+                            "init" => Kind::Init,
+                            "idle" => Kind::Idle,
+                            "task" => Kind::Software,
+                            _ => return None,
+                        };
+                        Some((kind, a))
+                    }) {
+                        app.tasks
+                            .push(Task::parse(f.sig.ident.to_string(), kind, attr)?);
+                    }
+                }
+                Item::ForeignMod(m) => {
+                    for item in m.items {
+                        if let ForeignItem::Fn(f) = item {
+                            app.dispatchers.push(f.sig.ident.to_string());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(app)
+    }
+}
+
+impl Task {
+    fn parse(name: String, mut kind: Kind, attr: &Attribute) -> syn::Result<Self> {
+        let mut task = Task {
+            name,
+            kind,
+            priority: if kind == Kind::Software { 1 } else { 0 },
+            binds: None,
+            capacity: None,
+            resources: Vec::new(),
+            spawn: Vec::new(),
+            schedule: Vec::new(),
+            line: attr.span().start().line,
+            priority_source: Some(match &attr.meta {
+                Meta::List(list) => {
+                    PrioritySource::Arguments(list.delimiter.span().open().end().into())
+                }
+                meta => PrioritySource::Path(meta.path().span().end().into()),
+            }),
+        };
+        for arg in args(attr)? {
+            match key(&arg).as_str() {
+                "priority" => {
+                    task.priority = int(&arg.value)?;
+                    let span = arg.value.span();
+                    task.priority_source = Some(PrioritySource::Value(
+                        span.start().into(),
+                        span.end().into(),
+                    ));
+                }
+                "binds" => {
+                    task.binds = Some(path(&arg.value)?);
+                    kind = Kind::Hardware;
+                }
+                "capacity" => task.capacity = Some(int(&arg.value)?),
+                // `shared` as of RTIC 1 (`local` resources are not shared)
+                "resources" | "shared" => task.resources = list(&arg.value)?,
+                "spawn" => task.spawn = list(&arg.value)?,
+                "schedule" => task.schedule = list(&arg.value)?,
+                _ => {}
+            }
+        }
+        task.kind = kind;
+        if kind == Kind::Software && task.capacity.is_none() {
+            task.capacity = Some(1);
+        }
+        Ok(task)
+    }
+}
+
+impl fmt::Display for App {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<12} {:<9} {:>4} {:<12} resources",
+            "task", "kind", "prio", "binds"
+        )?;
+        for t in &self.tasks {
+            writeln!(
+                f,
+                "{:<12} {:<9} {:>4} {:<12} {}",
+                t.name,
+                format!("{:?}", t.kind).to_lowercase(),
+                t.priority,
+                t.binds.as_deref().unwrap_or("-"),
+                t.resources.join(", ")
+            )?;
+        }
+        write!(f, "dispatchers: {}", self.dispatchers.join(", "))
+    }
+}
+
+fn is_app(attr: &Attribute) -> bool {
+    let segments: Vec<_> = attr
+        .path()
+        .segments
+        .iter()
+        .map(|s| s.ident.to_string())
+        .collect();
+    segments == ["rtic", "app"] || segments == ["app"]
+}
+
+/// The `key = value` arguments of an attribute, none for a bare attribute.
+fn args(attr: &Attribute) -> syn::Result<Vec<MetaNameValue>> {
+    match attr.meta {
+        syn::Meta::Path(_) => Ok(Vec::new()),
+        _ => Ok(attr
+            .parse_args_with(Punctuated::<MetaNameValue, Token![,]>::parse_terminated)?
+            .into_iter()
+            .collect()),
+    }
+}
+
+fn key(arg: &MetaNameValue) -> String {
+    tokens(&arg.path)
+}
+
+fn path(value: &Expr) -> syn::Result<String> {
+    match value {
+        Expr::Path(p) => Ok(tokens(p)),
+        _ => Err(syn::Error::new(value.span(), "expected a path")),
+    }
+}
+
+fn int<N>(value: &Expr) -> syn::Result<N>
+where
+    N: std::str::FromStr,
+    N::Err: fmt::Display,
+{
+    match value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(i), ..
+        }) => i.base10_parse(),
+        _ => Err(syn::Error::new(value.span(), "expected an integer literal")),
+    }
+}
+
+fn bool(value: &Expr) -> syn::Result<bool> {
+    match value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Bool(b), ..
+        }) => Ok(b.value),
+        _ => Err(syn::Error::new(value.span(), "expected `true` or `false`")),
+    }
+}
+
+/// A list of names, `[a, &b]` (shared access by reference in RTIC 0.5).
+fn list(value: &Expr) -> syn::Result<Vec<String>> {
+    let elems = match value {
+        Expr::Array(ExprArray { elems, .. }) => elems,
+        _ => return Err(syn::Error::new(value.span(), "expected a list `[..]`")),
+    };
+    elems
+        .iter()
+        .map(|e| match e {
+            Expr::Reference(r) => path(&r.expr),
+            e => path(e),
+        })
+        .collect()
+}
+
+/// Source text of a syntax tree, with the token spacing tidied up.
+fn tokens(t: &impl ToTokens) -> String {
+    let mut s = t.to_token_stream().to_string();
+    for (from, to) in [
+        (" :: ", "::"),
+        (":: ", "::"),
+        (" < ", "<"),
+        ("< ", "<"),
+        (" <", "<"),
+        (" >", ">"),
+        ("& ", "&"),
+        (" ,", ","),
+        (" ;", ";"),
+    ] {
+        s = s.replace(from, to);
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing_exam() -> App {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples/timing_exam.rs");
+        App::load(&path).unwrap()
+    }
+
+    #[test]
+    fn parse_timing_exam() {
+        let app = timing_exam();
+        assert_eq!(app.device.as_deref(), Some("stm32f411"));
+        assert_eq!(app.monotonic.as_deref(), Some("rtic::cyccnt::CYCCNT"));
+        assert!(!app.peripherals);
+        assert_eq!(app.dispatchers, ["EXTI0", "EXTI1", "EXTI2"]);
+
+        let resources: Vec<_> = app.resources.iter().map(|r| (&*r.name, r.late)).collect();
+        assert_eq!(resources, [("R1", false), ("R2", false)]);
+
+        let init = app.task("init").unwrap();
+        assert_eq!((init.kind, init.priority), (Kind::Init, 0));
+        assert_eq!(init.schedule, ["t1", "t2", "t3"]);
+
+        let tasks: Vec<_> = app
+            .tasks
+            .iter()
+            .filter(|t| t.kind == Kind::Software)
+            .map(|t| {
+                (
+                    &*t.name,
+                    t.priority,
+                    t.resources.clone(),
+                    t.schedule.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            tasks,
+            [
+                ("t1", 1, vec![], vec!["t1".to_string()]),
+                ("t2", 2, vec!["R1".into(), "R2".into()], vec!["t2".into()]),
+                ("t3", 3, vec!["R2".into()], vec!["t3".into()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn ceilings() {
+        let ceilings = timing_exam().ceilings();
+        assert_eq!(ceilings["R1"], 2);
+        assert_eq!(ceilings["R2"], 3);
+    }
+
+    #[test]
+    fn parse_hardware_task() {
+        let app = App::parse(
+            r#"
+            #[rtic::app(device = stm32f411, peripherals = true)]
+            mod app {
+                #[task(binds = EXTI0, priority = 2, resources = [x], spawn = [log])]
+                fn exti0(_: exti0::Context) {}
+
+                #[task(capacity = 4)]
+                fn log(_: log::Context) {}
+            }
+            "#,
+        )
+        .unwrap();
+        assert!(app.peripherals);
+        let exti0 = app.task("exti0").unwrap();
+        assert_eq!(exti0.kind, Kind::Hardware);
+        assert_eq!(exti0.binds.as_deref(), Some("EXTI0"));
+        assert_eq!(
+            (exti0.priority, exti0.spawn.clone()),
+            (2, vec!["log".into()])
+        );
+        let log = app.task("log").unwrap();
+        // priority defaults to 1
+        assert_eq!(
+            (log.kind, log.priority, log.capacity),
+            (Kind::Software, 1, Some(4))
+        );
+
+        assert!(App::parse("fn main() {}").is_err());
+    }
+}
@@ -0,0 +1,499 @@
+//! priorities.rs
+//!
+//! Priority assignment of task sets (see `task_set.rs`).
+//!
+//! - Deadline monotonic: the shorter the deadline, the higher the priority,
+//!   tasks of equal deadlines sharing a priority. Optimal without blocking,
+//!   for deadlines within the periods.
+//! - Audsley's algorithm: from the lowest priority up, each level is given
+//!   to a task schedulable there (by the response time analysis of
+//!   `analysis.rs`), with all unassigned tasks at higher priorities. The
+//!   SRP blocking of a task only depends on which tasks have lower
+//!   priorities (the ceilings of the resources it, or any unassigned task,
+//!   uses are at least its priority), and its interference on which tasks
+//!   have higher priorities (and which lower, by their releases), not on
+//!   their order, so the assignment is optimal: if no task is schedulable
+//!   at a level, no priority ordering is.
+//!
+//! RTIC priorities range from 1 (0 is `idle`) to the number of NVIC
+//! priority levels. With fewer levels than tasks, tasks share levels:
+//! deadline monotonic merges the longest deadlines into the lowest level,
+//! Audsley's algorithm adds tasks to a level as long as they (and the tasks
+//! already there) stay schedulable.
+
+use std::{collections::BTreeMap, fmt};
+
+use crate::{
+    analysis,
+    model::{App, PrioritySource},
+    task_set::{Task, TaskSet},
+};
+
+/// Priorities, by task name.
+pub type Priorities = BTreeMap<String, u8>;
+
+/// The task set with the given priorities.
+pub fn apply(set: &TaskSet, priorities: &Priorities) -> TaskSet {
+    let mut set = set.clone();
+    for t in &mut set.tasks {
+        if let Some(&p) = priorities.get(&t.name) {
+            t.priority = p;
+        }
+    }
+    set
+}
+
+pub fn deadline_monotonic(set: &TaskSet, levels: u8) -> Priorities {
+    let mut deadlines: Vec<u32> = set.tasks.iter().map(|t| t.deadline).collect();
+    deadlines.sort_unstable_by(|a, b| b.cmp(a));
+    deadlines.dedup();
+    // the longest deadlines share the lowest level if needed
+    let merged = deadlines.len().saturating_sub(levels as usize);
+    set.tasks
+        .iter()
+        .map(|t| {
+            let rank = deadlines.iter().position(|&d| d == t.deadline).unwrap();
+            (t.name.clone(), (rank.saturating_sub(merged) + 1) as u8)
+        })
+        .collect()
+}
+
+/// Audsley's optimal priority assignment, `None` if no assignment is
+/// schedulable within `levels`.
+pub fn audsley(set: &TaskSet, levels: u8) -> Option<Priorities> {
+    // candidates in deadline monotonic order, longest deadline first
+    let mut unassigned: Vec<&Task> = set.tasks.iter().collect();
+    unassigned.sort_by(|a, b| b.deadline.cmp(&a.deadline).then(b.period.cmp(&a.period)));
+    let mut priorities = Priorities::new();
+
+    for level in 1..=levels {
+        let mut group: Vec<&str> = Vec::new();
+        let mut i = 0;
+        while i < unassigned.len() {
+            let levels_left = (levels - level) as usize;
+            let needed = group.is_empty() || unassigned.len() > levels_left;
+            if !needed {
+                break;
+            }
+            let candidate = unassigned[i];
+            if schedulable_at(set, &priorities, &group, candidate, level) {
+                group.push(&candidate.name);
+                unassigned.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+        if group.is_empty() {
+            return None;
+        }
+        for t in group {
+            priorities.insert(t.to_string(), level);
+        }
+        if unassigned.is_empty() {
+            return Some(priorities);
+        }
+    }
+    None
+}
+
+/// The candidate, and the tasks of the group, are schedulable at `level`,
+/// the unassigned tasks above.
+fn schedulable_at(
+    set: &TaskSet,
+    assigned: &Priorities,
+    group: &[&str],
+    candidate: &Task,
+    level: u8,
+) -> bool {
+    let mut trial = set.clone();
+    for t in &mut trial.tasks {
+        t.priority = match assigned.get(&t.name) {
+            Some(&p) => p,
+            None if t.name == candidate.name || group.contains(&t.name.as_str()) => level,
+            None => level + 1,
+        };
+    }
+    trial
+        .tasks
+        .iter()
+        .filter(|t| t.priority == level)
+        .all(|t| analysis::response_time(&trial, t).is_some())
+}
+
+/// The assignments of a task set, with their response times.
+pub struct Assignments<'a> {
+    pub set: &'a TaskSet,
+    pub levels: u8,
+    pub deadline_monotonic: Priorities,
+    pub audsley: Option<Priorities>,
+}
+
+impl<'a> Assignments<'a> {
+    pub fn new(set: &'a TaskSet, levels: u8) -> Self {
+        Assignments {
+            set,
+            levels,
+            deadline_monotonic: deadline_monotonic(set, levels),
+            audsley: audsley(set, levels),
+        }
+    }
+
+    /// The schedulable assignment, deadline monotonic if schedulable (as
+    /// the simplest), Audsley's otherwise.
+    pub fn suggested(&self) -> Option<&Priorities> {
+        if analysis::schedulable(&apply(self.set, &self.deadline_monotonic)) {
+            Some(&self.deadline_monotonic)
+        } else {
+            self.audsley.as_ref()
+        }
+    }
+}
+
+fn response_time(set: &TaskSet, task: &str) -> String {
+    match analysis::response_time(set, set.task(task).unwrap()) {
+        Some(r) => r.to_string(),
+        None => "MISS".into(),
+    }
+}
+
+fn verdict(set: &TaskSet) -> &'static str {
+    if analysis::schedulable(set) {
+        "schedulable"
+    } else {
+        "NOT schedulable"
+    }
+}
+
+impl fmt::Display for Assignments<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dm = apply(self.set, &self.deadline_monotonic);
+        let audsley = self.audsley.as_ref().map(|p| apply(self.set, p));
+        writeln!(
+            f,
+            "{:<8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8}",
+            "task", "deadline", "current", "rt", "dm", "rt", "audsley", "rt"
+        )?;
+        for t in &self.set.tasks {
+            writeln!(
+                f,
+                "{:<8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8}",
+                t.name,
+                t.deadline,
+                t.priority,
+                response_time(self.set, &t.name),
+                self.deadline_monotonic[&t.name],
+                response_time(&dm, &t.name),
+                match &self.audsley {
+                    Some(p) => p[&t.name].to_string(),
+                    None => "-".into(),
+                },
+                match &audsley {
+                    Some(set) => response_time(set, &t.name),
+                    None => "-".into(),
+                },
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "current:            {}", verdict(self.set))?;
+        writeln!(f, "deadline monotonic: {}", verdict(&dm))?;
+        write!(
+            f,
+            "audsley:            {}",
+            match &audsley {
+                Some(set) => verdict(set),
+                None => "no schedulable assignment",
+            }
+        )?;
+        write!(f, " (priority levels: {})", self.levels)
+    }
+}
+
+/// A task of the application with a changed priority.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change {
+    pub task: String,
+    /// The changed line (of the `#[task]` attribute).
+    pub line: usize,
+    pub old: String,
+    pub new: String,
+}
+
+/// The changes of the `#[task]` attributes of the application source,
+/// setting `priority = N` (added if missing), where the model locates the
+/// priority (`Task::priority_source`). Tasks not in the application are
+/// returned as errors.
+pub fn changes(app: &App, src: &str, priorities: &Priorities) -> Result<Vec<Change>, String> {
+    let lines: Vec<&str> = src.lines().collect();
+    let mut changes = Vec::new();
+    for (task, &priority) in priorities {
+        let t = app
+            .task(task)
+            .ok_or_else(|| format!("task `{}` not found in the application", task))?;
+        if t.priority == priority {
+            continue;
+        }
+        let source = t
+            .priority_source
+            .ok_or_else(|| format!("task `{}` has no source location", task))?;
+        let line = match source {
+            PrioritySource::Value(start, _) => start.line,
+            PrioritySource::Arguments(at) | PrioritySource::Path(at) => at.line,
+        };
+        let old = lines
+            .get(line.wrapping_sub(1))
+            .ok_or_else(|| format!("line {} of task `{}` out of range", line, task))?;
+        let new = set_priority(old, source, priority).ok_or_else(|| {
+            format!(
+                "the priority of task `{}` is not on line {}: {}",
+                task,
+                line,
+                old.trim()
+            )
+        })?;
+        changes.push(Change {
+            task: task.clone(),
+            line,
+            old: old.to_string(),
+            new,
+        });
+    }
+    changes.sort_by_key(|c| c.line);
+    Ok(changes)
+}
+
+/// Sets `priority = N` in a line of a `#[task(..)]` attribute, at `source`.
+fn set_priority(line: &str, source: PrioritySource, priority: u8) -> Option<String> {
+    // columns count characters
+    let byte = |column: usize| match line.char_indices().nth(column) {
+        Some((i, _)) => Some(i),
+        None if column == line.chars().count() => Some(line.len()),
+        None => None,
+    };
+    let (start, end, new) = match source {
+        PrioritySource::Value(start, end) if start.line == end.line => {
+            (byte(start.column)?, byte(end.column)?, priority.to_string())
+        }
+        PrioritySource::Value(..) => return None,
+        PrioritySource::Arguments(at) => {
+            let at = byte(at.column)?;
+            let rest = line[at..].trim_start();
+            let new = if rest.is_empty() {
+                // the arguments continue on the next line
+                format!("priority = {},", priority)
+            } else if rest.starts_with(')') {
+                format!("priority = {}", priority)
+            } else {
+                format!("priority = {}, ", priority)
+            };
+            (at, at, new)
+        }
+        PrioritySource::Path(at) => {
+            let at = byte(at.column)?;
+            (at, at, format!("(priority = {})", priority))
+        }
+    };
+    Some(format!("{}{}{}", &line[..start], new, &line[end..]))
+}
+
+/// The changes as a unified diff, without context (`patch <path>`).
+pub fn diff(path: &str, changes: &[Change]) -> String {
+    let mut diff = format!("--- {}\n+++ {}\n", path, path);
+    for c in changes {
+        diff.push_str(&format!(
+            "@@ -{} +{} @@\n-{}\n+{}\n",
+            c.line, c.line, c.old, c.new
+        ));
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn load(name: &str) -> TaskSet {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("task_sets")
+            .join(name);
+        TaskSet::load(&path).unwrap()
+    }
+
+    fn priorities(p: &[(&str, u8)]) -> Priorities {
+        p.iter().map(|&(t, p)| (t.to_string(), p)).collect()
+    }
+
+    #[test]
+    fn deadline_monotonic_levels() {
+        let set = load("exam.json");
+        let dm = |levels| deadline_monotonic(&set, levels);
+        assert_eq!(dm(16), priorities(&[("t1", 2), ("t2", 1), ("t3", 3)]));
+        assert_eq!(dm(3), dm(16));
+        // the longest deadlines share the lowest level
+        assert_eq!(dm(2), priorities(&[("t1", 1), ("t2", 1), ("t3", 2)]));
+        assert_eq!(dm(1), priorities(&[("t1", 1), ("t2", 1), ("t3", 1)]));
+    }
+
+    #[test]
+    fn audsley_levels() {
+        let set = load("exam.json");
+        assert_eq!(
+            audsley(&set, 16),
+            Some(priorities(&[("t1", 2), ("t2", 1), ("t3", 3)]))
+        );
+        assert_eq!(
+            audsley(&set, 2),
+            Some(priorities(&[("t1", 1), ("t2", 1), ("t3", 2)]))
+        );
+        // t3 misses with t1 and t2 at its level
+        assert_eq!(audsley(&set, 1), None);
+    }
+
+    #[test]
+    fn audsley_beats_merged_deadline_monotonic() {
+        let set = load("exam_measured.json");
+        assert!(!analysis::schedulable(&set));
+
+        // 16 levels, both fix the priorities
+        let a = Assignments::new(&set, 16);
+        assert_eq!(a.audsley.as_ref(), Some(&a.deadline_monotonic));
+        assert_eq!(a.suggested(), Some(&a.deadline_monotonic));
+
+        // 2 levels, t1 misses sharing its level with t2, not with t3
+        let a = Assignments::new(&set, 2);
+        assert_eq!(
+            a.deadline_monotonic,
+            priorities(&[("t1", 1), ("t2", 1), ("t3", 2)])
+        );
+        assert!(!analysis::schedulable(&apply(&set, &a.deadline_monotonic)));
+        let expected = priorities(&[("t1", 2), ("t2", 1), ("t3", 2)]);
+        assert_eq!(a.audsley.as_ref(), Some(&expected));
+        assert!(analysis::schedulable(&apply(&set, &expected)));
+        assert_eq!(a.suggested(), Some(&expected));
+    }
+
+    #[test]
+    fn changes_timing_exam() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples/timing_exam.rs");
+        let src = std::fs::read_to_string(&path).unwrap();
+        let app = App::parse(&src).unwrap();
+        let p = priorities(&[("t1", 2), ("t2", 1), ("t3", 3)]);
+        let planned = changes(&app, &src, &p).unwrap();
+        let edits: Vec<_> = planned.iter().map(|c| (&*c.task, c.new.trim())).collect();
+        assert_eq!(
+            edits,
+            [
+                ("t1", "#[task(schedule = [t1], priority = 2)]"),
+                (
+                    "t2",
+                    "#[task(schedule = [t2], resources = [R1, R2], priority = 1)]"
+                ),
+            ]
+        );
+        assert!(diff("timing_exam.rs", &planned).starts_with("--- timing_exam.rs\n"));
+
+        let unknown = priorities(&[("t4", 1)]);
+        assert!(changes(&app, &src, &unknown).is_err());
+    }
+
+    fn planned(src: &str, p: &[(&str, u8)]) -> Vec<(usize, String)> {
+        let app = App::parse(src).unwrap();
+        changes(&app, src, &priorities(p))
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.line, c.new))
+            .collect()
+    }
+
+    #[test]
+    fn changes_added() {
+        let src = r#"
+            #[rtic::app(device = stm32f4::stm32f411)]
+            const APP: () = {
+                #[task]
+                fn a(_: a::Context) {}
+
+                #[task(capacity = 4)]
+                fn b(_: b::Context) {}
+
+                #[task()]
+                fn c(_: c::Context) {}
+            };
+        "#;
+        assert_eq!(
+            planned(src, &[("a", 2), ("b", 3), ("c", 2)]),
+            [
+                (4, "                #[task(priority = 2)]".into()),
+                (
+                    7,
+                    "                #[task(priority = 3, capacity = 4)]".into()
+                ),
+                (10, "                #[task(priority = 2)]".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn changes_multi_line() {
+        let src = r#"
+            #[rtic::app(device = stm32f4::stm32f411)]
+            const APP: () = {
+                #[task(
+                    binds = EXTI0,
+                    resources = [low_priority],
+                    priority = 2,
+                )]
+                fn a(_: a::Context) {}
+
+                #[task(
+                    schedule = [b],
+                )]
+                fn b(_: b::Context) {}
+            };
+        "#;
+        // the `priority` argument, not the resource containing "priority"
+        assert_eq!(
+            planned(src, &[("a", 3), ("b", 2)]),
+            [
+                (7, "                    priority = 3,".into()),
+                (11, "                #[task(priority = 2,".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn changes_identifiers() {
+        let src = r#"
+            #[rtic::app(device = stm32f4::stm32f411)]
+            const APP: () = {
+                #[task(resources = [priority_queue], priority = 1)]
+                fn a(_: a::Context) {}
+
+                #[task(resources = [priority_queue])]
+                fn b(_: b::Context) {}
+            };
+        "#;
+        assert_eq!(
+            planned(src, &[("a", 12), ("b", 2)]),
+            [
+                (
+                    4,
+                    "                #[task(resources = [priority_queue], priority = 12)]".into()
+                ),
+                (
+                    7,
+                    "                #[task(priority = 2, resources = [priority_queue])]".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn changes_without_source() {
+        // a model read from JSON has no source locations
+        let app = App::parse("#[rtic::app(device = x)] mod app { #[task] fn a(_: a::Context) {} }")
+            .unwrap();
+        let app: App = serde_json::from_str(&serde_json::to_string(&app).unwrap()).unwrap();
+        assert!(changes(&app, "", &priorities(&[("a", 2)])).is_err());
+    }
+}
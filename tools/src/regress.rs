@@ -0,0 +1,176 @@
+//! regress.rs
+//!
+//! Timing regression checks against recorded baselines.
+//!
+//! A baseline file lists, per example and build configuration, the
+//! breakpoints to record (see `harness`) and the timing properties to
+//! check, each with an expected value and a tolerance (in cycles).
+
+use std::{fmt, fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::harness::Report;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Baselines {
+    pub cases: Vec<Case>,
+}
+
+/// An example in a given build configuration.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Case {
+    pub example: String,
+    #[serde(default)]
+    pub release: bool,
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Labels of the breakpoints, in order of arrival.
+    pub breakpoints: Vec<String>,
+    /// Time to let the target run after the last breakpoint, before
+    /// halting it to read symbols.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settle_ms: Option<u64>,
+    pub checks: Vec<Check>,
+}
+
+/// A measured timing property.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Check {
+    pub name: String,
+    #[serde(flatten)]
+    pub source: Source,
+    pub expected: u32,
+    #[serde(default)]
+    pub tolerance: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Source {
+    /// Cycles between two breakpoints.
+    Between { from: String, to: String },
+    /// Value of a `u32` static (e.g., `T1_MAX_RP`) after the last breakpoint
+    /// (and the settle time).
+    Symbol { symbol: String },
+}
+
+#[derive(Clone, Debug)]
+pub enum Outcome {
+    Pass(u32),
+    Fail(u32),
+    /// The value could not be measured (e.g., a breakpoint was not reached).
+    Missing(String),
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        matches!(self, Outcome::Pass(_))
+    }
+}
+
+impl Baselines {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        serde_json::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn store(&self, path: &Path) -> io::Result<()> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        fs::write(path, json)
+    }
+}
+
+impl Case {
+    /// Describes the build, e.g., `timing_task --release --features nightly`.
+    pub fn build_args(&self) -> Vec<String> {
+        let mut args = vec!["--example".to_string(), self.example.clone()];
+        if self.release {
+            args.push("--release".into());
+        }
+        if !self.features.is_empty() {
+            args.push("--features".into());
+            args.push(self.features.join(","));
+        }
+        args
+    }
+}
+
+impl Check {
+    /// Measures the property, `read_symbol` reads a `u32` static from the
+    /// (halted) target.
+    pub fn measure(
+        &self,
+        report: &Report,
+        read_symbol: &mut dyn FnMut(&str) -> Option<u32>,
+    ) -> Result<u32, String> {
+        match &self.source {
+            Source::Between { from, to } => report
+                .between(from, to)
+                .ok_or_else(|| format!("breakpoint `{}` or `{}` not reached", from, to)),
+            Source::Symbol { symbol } => {
+                read_symbol(symbol).ok_or_else(|| format!("could not read `{}`", symbol))
+            }
+        }
+    }
+
+    pub fn evaluate(&self, measured: Result<u32, String>) -> Outcome {
+        match measured {
+            Ok(v) if v.max(self.expected) - v.min(self.expected) <= self.tolerance => {
+                Outcome::Pass(v)
+            }
+            Ok(v) => Outcome::Fail(v),
+            Err(e) => Outcome::Missing(e),
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Pass(v) => write!(f, "ok      {}", v),
+            Outcome::Fail(v) => write!(f, "FAILED  {}", v),
+            Outcome::Missing(e) => write!(f, "FAILED  {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_in_baselines() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("baselines.json");
+        let baselines = Baselines::load(&path).unwrap();
+        let task = baselines
+            .cases
+            .iter()
+            .find(|c| c.example == "timing_task")
+            .unwrap();
+        assert!(matches!(
+            &task.checks[0].source,
+            Source::Between { from, to } if from == "start" && to == "entry"
+        ));
+        for case in &baselines.cases {
+            if case.breakpoints.is_empty() {
+                assert!(case.settle_ms.is_some(), "{}", case.example);
+            }
+        }
+    }
+
+    #[test]
+    fn tolerance() {
+        let check = Check {
+            name: "timed_loop".into(),
+            source: Source::Symbol { symbol: "X".into() },
+            expected: 40_001,
+            tolerance: 4,
+        };
+        assert!(check.evaluate(Ok(39_997)).passed());
+        assert!(check.evaluate(Ok(40_005)).passed());
+        assert!(!check.evaluate(Ok(40_006)).passed());
+        assert!(!check.evaluate(Err("not reached".into())).passed());
+    }
+}
@@ -0,0 +1,314 @@
+//! sensitivity.rs
+//!
+//! Sensitivity analysis of task sets: by how much the execution time of
+//! each task, and the critical sections of each resource, may change while
+//! the set stays schedulable (by the response time analysis of
+//! `analysis.rs`).
+//!
+//! The slack is found by binary search, schedulability being monotonic in
+//! the execution times. The WCET of a task changes outside its critical
+//! sections (at the end of its trace), a critical section changes at its
+//! end, delaying the rest of the task (so enclosing sections and the WCET
+//! grow with it), all the sections of the resource alike. A schedulable
+//! set has a positive slack, the growth tolerated. An unschedulable set has
+//! a negative slack, the reduction needed, if any suffices (execution
+//! within critical sections or inner ones is never removed).
+
+use std::fmt;
+
+use crate::{
+    analysis,
+    task_set::{TaskSet, Trace},
+};
+
+/// The slack of a task (WCET), or a resource (critical sections).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Slack {
+    /// The task, or the resource.
+    pub id: String,
+    /// The WCET of the task, or the longest critical section.
+    pub base: u32,
+    /// The change of the execution time keeping the set schedulable, the
+    /// largest growth (positive) or the smallest reduction (negative).
+    /// `None` if no reduction makes the set schedulable.
+    pub slack: Option<i64>,
+}
+
+impl Slack {
+    /// The slack relative to the base time, in percent.
+    pub fn percent(&self) -> Option<f64> {
+        match (self.slack, self.base) {
+            (Some(s), b) if b > 0 => Some(s as f64 * 100.0 / b as f64),
+            _ => None,
+        }
+    }
+}
+
+/// The slack of all tasks and resources.
+pub struct Sensitivity {
+    pub schedulable: bool,
+    pub tasks: Vec<Slack>,
+    pub resources: Vec<Slack>,
+}
+
+impl Sensitivity {
+    pub fn new(set: &TaskSet) -> Self {
+        let schedulable = analysis::schedulable(set);
+        // growing any execution time beyond the longest deadline misses
+        let limit = set.tasks.iter().map(|t| t.deadline).max().unwrap_or(0) as i64;
+
+        let tasks = set
+            .tasks
+            .iter()
+            .enumerate()
+            .map(|(i, t)| {
+                let adjust = |delta: i64| {
+                    let mut set = set.clone();
+                    let trace = &mut set.tasks[i].trace;
+                    trace.end = (trace.end as i64 + delta) as u32;
+                    set
+                };
+                Slack {
+                    id: t.name.clone(),
+                    base: t.wcet(),
+                    slack: search(schedulable, tail(&t.trace) as i64, limit, adjust),
+                }
+            })
+            .collect();
+
+        let resources = set
+            .ceilings()
+            .keys()
+            .map(|&r| {
+                let sections = set.tasks.iter().flat_map(|t| t.trace.sections());
+                let base = sections.filter(|&(id, _)| id == r).map(|(_, h)| h).max();
+                let reducible = set
+                    .tasks
+                    .iter()
+                    .flat_map(|t| sections_of(&t.trace, r))
+                    .map(tail)
+                    .min()
+                    .unwrap_or(0);
+                let adjust = |delta: i64| {
+                    let mut set = set.clone();
+                    for t in &mut set.tasks {
+                        stretch(&mut t.trace, r, delta, 0);
+                    }
+                    set
+                };
+                Slack {
+                    id: r.to_string(),
+                    base: base.unwrap_or(0),
+                    slack: search(schedulable, reducible as i64, limit, adjust),
+                }
+            })
+            .collect();
+
+        Sensitivity {
+            schedulable,
+            tasks,
+            resources,
+        }
+    }
+}
+
+/// The largest growth in `0..=limit` keeping the set schedulable, or the
+/// smallest reduction in `0..=reducible` making it schedulable.
+fn search(
+    schedulable: bool,
+    reducible: i64,
+    limit: i64,
+    adjust: impl Fn(i64) -> TaskSet,
+) -> Option<i64> {
+    let ok = |delta| analysis::schedulable(&adjust(delta));
+    if schedulable {
+        // invariant: ok(lo), !ok(hi)
+        let (mut lo, mut hi) = (0, limit + 1);
+        while hi - lo > 1 {
+            let mid = (lo + hi) / 2;
+            if ok(mid) {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        Some(lo)
+    } else {
+        if !ok(-reducible) {
+            return None;
+        }
+        // invariant: !ok(-lo), ok(-hi)
+        let (mut lo, mut hi) = (0, reducible);
+        while hi - lo > 1 {
+            let mid = (lo + hi) / 2;
+            if ok(-mid) {
+                hi = mid;
+            } else {
+                lo = mid;
+            }
+        }
+        Some(-hi)
+    }
+}
+
+/// Execution at the end of a trace, after its inner sections.
+fn tail(trace: &Trace) -> u32 {
+    trace.end - trace.inner.last().map_or(trace.start, |t| t.end)
+}
+
+fn sections_of<'a>(trace: &'a Trace, resource: &str) -> Vec<&'a Trace> {
+    let mut sections = Vec::new();
+    for t in &trace.inner {
+        if t.id == resource {
+            sections.push(t);
+        }
+        sections.extend(sections_of(t, resource));
+    }
+    sections
+}
+
+/// Changes the critical sections of `resource` by `delta` at their end,
+/// shifting the rest of the trace, returns the shift of the end.
+fn stretch(trace: &mut Trace, resource: &str, delta: i64, mut shift: i64) -> i64 {
+    trace.start = (trace.start as i64 + shift) as u32;
+    for inner in &mut trace.inner {
+        shift = stretch(inner, resource, delta, shift);
+        if inner.id == resource {
+            shift += delta;
+            inner.end = (inner.end as i64 + delta) as u32;
+        }
+    }
+    trace.end = (trace.end as i64 + shift) as u32;
+    shift
+}
+
+fn row(f: &mut fmt::Formatter<'_>, s: &Slack) -> fmt::Result {
+    writeln!(
+        f,
+        "{:<8} {:>8} {:>10} {:>9}",
+        s.id,
+        s.base,
+        match s.slack {
+            Some(slack) => format!("{:+}", slack),
+            None => "-".to_string(),
+        },
+        match s.percent() {
+            Some(p) => format!("{:+.1}%", p),
+            None => "-".to_string(),
+        }
+    )
+}
+
+impl fmt::Display for Sensitivity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<8} {:>8} {:>10} {:>9}", "task", "wcet", "slack", "%")?;
+        for s in &self.tasks {
+            row(f, s)?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<8} {:>8} {:>10} {:>9}",
+            "resource", "section", "slack", "%"
+        )?;
+        for s in &self.resources {
+            row(f, s)?;
+        }
+        if self.schedulable {
+            write!(f, "slack: the growth tolerated, in cycles")
+        } else {
+            write!(
+                f,
+                "slack: the reduction needed to be schedulable, in cycles (`-` if none suffices)"
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn load(name: &str) -> TaskSet {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("task_sets")
+            .join(name);
+        TaskSet::load(&path).unwrap()
+    }
+
+    fn slacks(slacks: &[Slack]) -> Vec<(&str, Option<i64>)> {
+        slacks.iter().map(|s| (&*s.id, s.slack)).collect()
+    }
+
+    #[test]
+    fn exam() {
+        // t1 completes exactly at its deadline
+        let s = Sensitivity::new(&load("exam.json"));
+        assert!(s.schedulable);
+        assert_eq!(
+            slacks(&s.tasks),
+            [("t1", Some(0)), ("t2", Some(0)), ("t3", Some(0))]
+        );
+        assert_eq!(slacks(&s.resources), [("R1", Some(0)), ("R2", Some(0))]);
+    }
+
+    #[test]
+    fn growth() {
+        let mut set = load("exam.json");
+        // beyond 100_000, t1 suffers a third instance of t3 (130_000)
+        set.tasks[0].deadline = 150_000;
+        set.tasks[0].period = 150_000;
+        let s = Sensitivity::new(&set);
+        assert!(s.schedulable);
+        assert!(s.tasks.iter().all(|t| t.slack.unwrap() > 0));
+        assert!(s.resources.iter().all(|r| r.slack.unwrap() > 0));
+
+        // the largest growth
+        let t1 = s.tasks[0].slack.unwrap() as u32;
+        set.tasks[0].trace.end += t1;
+        assert!(analysis::schedulable(&set));
+        set.tasks[0].trace.end += 1;
+        assert!(!analysis::schedulable(&set));
+    }
+
+    #[test]
+    fn measured() {
+        let set = load("exam_measured.json");
+        let s = Sensitivity::new(&set);
+        assert!(!s.schedulable);
+        assert!(s.tasks.iter().all(|t| t.slack.unwrap() < 0));
+        assert!(s.resources.iter().all(|r| r.slack.unwrap() < 0));
+        assert_eq!(
+            slacks(&s.tasks),
+            [
+                ("t1", Some(-1_550)),
+                ("t2", Some(-1_550)),
+                ("t3", Some(-775))
+            ]
+        );
+        assert_eq!(
+            slacks(&s.resources),
+            [("R1", Some(-775)), ("R2", Some(-517))]
+        );
+        assert!(s.tasks[2].percent().unwrap() < 0.0);
+
+        // the smallest reduction
+        let mut set = set;
+        set.tasks[2].trace.end -= 774;
+        assert!(!analysis::schedulable(&set));
+        set.tasks[2].trace.end -= 1;
+        assert!(analysis::schedulable(&set));
+    }
+
+    #[test]
+    fn infeasible() {
+        // t1 misses even without execution time of its own
+        let mut set = load("exam_measured.json");
+        set.tasks[0].deadline = 5_000;
+        let s = Sensitivity::new(&set);
+        assert!(s.tasks.iter().all(|t| t.slack.is_none()));
+        assert!(s.resources.iter().all(|r| r.slack.is_none()));
+        assert_eq!(s.tasks[0].percent(), None);
+    }
+}
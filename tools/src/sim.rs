@@ -0,0 +1,496 @@
+//! sim.rs
+//!
+//! Discrete-event simulation of a task set under RTIC's scheduling, fixed
+//! priorities with the Stack Resource Policy (SRP).
+//!
+//! The semantics follow RTIC on a single core:
+//!
+//! - A task instance (job) starts only if its priority is higher than both
+//!   the priority of the running job and the system ceiling (the highest
+//!   ceiling of the resources locked). So, once started, a job never
+//!   blocks, and jobs of equal priority run to completion in release
+//!   order.
+//! - Locking a resource raises the system ceiling to the resource ceiling
+//!   (BASEPRI), unlocking restores it, possibly letting pending jobs start.
+//! - Periodic releases (`schedule`) go through the timer queue, whose
+//!   handler runs at the timer priority and takes `release` cycles per
+//!   released job, which are ready once the handler completes. Starting a
+//!   job takes `dispatch` cycles. Without overhead, jobs are ready at their
+//!   release time.
+//!
+//! Response times are measured from the release time (`cx.scheduled`),
+//! as in `timing_exam.rs`.
+
+use std::{cmp::Reverse, collections::BTreeMap, fmt};
+
+use crate::task_set::{TaskSet, Trace};
+
+/// Name of the timer queue handler in the events.
+pub const TIMER_QUEUE: &str = "timer-queue";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    Release,
+    Start,
+    Preempted { by: String },
+    Resume,
+    Lock { resource: String, ceiling: u8 },
+    Unlock { resource: String },
+    Finish { response_time: u64, missed: bool },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub time: u64,
+    pub task: String,
+    pub kind: EventKind,
+}
+
+/// A completed job.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JobRecord {
+    pub task: String,
+    pub release: u64,
+    pub start: u64,
+    pub finish: u64,
+}
+
+impl JobRecord {
+    pub fn response_time(&self) -> u64 {
+        self.finish - self.release
+    }
+}
+
+/// Response times of a task over the simulation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskStats {
+    pub task: String,
+    pub jobs: usize,
+    pub max_response_time: u64,
+    /// Release of the job with the largest response time.
+    pub worst_release: u64,
+    pub misses: usize,
+    /// Release of the first job missing its deadline.
+    pub first_miss: Option<u64>,
+}
+
+pub struct Simulation {
+    /// Releases stop at this time, the simulation runs until idle.
+    pub until: u64,
+    pub events: Vec<Event>,
+    pub jobs: Vec<JobRecord>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Step {
+    Run(u64),
+    Lock(usize),
+    Unlock,
+}
+
+struct Job {
+    /// `None` for the timer queue handler.
+    task: Option<usize>,
+    priority: u8,
+    release: u64,
+    start: u64,
+    steps: Vec<Step>,
+    pc: usize,
+    /// Jobs made ready by a timer queue handler (task, release).
+    releases: Vec<(usize, u64)>,
+}
+
+fn flatten(trace: &Trace, resources: &[&str], steps: &mut Vec<Step>) {
+    let mut at = trace.start;
+    for inner in &trace.inner {
+        steps.push(Step::Run((inner.start - at) as u64));
+        let r = resources.iter().position(|&r| r == inner.id).unwrap();
+        steps.push(Step::Lock(r));
+        flatten(inner, resources, steps);
+        steps.push(Step::Unlock);
+        at = inner.end;
+    }
+    steps.push(Step::Run((trace.end - at) as u64));
+}
+
+impl Simulation {
+    /// Simulates the task set, with releases up to `until`.
+    pub fn run(set: &TaskSet, until: u64) -> Self {
+        let ceilings = set.ceilings();
+        let resources: Vec<&str> = ceilings.keys().copied().collect();
+        let name = |job: &Job| match job.task {
+            Some(t) => set.tasks[t].name.clone(),
+            None => TIMER_QUEUE.to_string(),
+        };
+        let new_job = |t: usize, release: u64| {
+            let mut steps = vec![Step::Run(set.overhead.dispatch as u64)];
+            flatten(&set.tasks[t].trace, &resources, &mut steps);
+            Job {
+                task: Some(t),
+                priority: set.tasks[t].priority,
+                release,
+                start: 0,
+                steps,
+                pc: 0,
+                releases: Vec::new(),
+            }
+        };
+
+        let mut sim = Simulation {
+            until,
+            events: Vec::new(),
+            jobs: Vec::new(),
+        };
+        let mut next: Vec<u64> = set.tasks.iter().map(|t| t.offset as u64).collect();
+        let mut jobs: Vec<Job> = Vec::new();
+        let mut ready: Vec<usize> = Vec::new();
+        let mut stack: Vec<usize> = Vec::new();
+        // locked resources, and their ceilings
+        let mut locked: Vec<(usize, u8)> = Vec::new();
+        let mut time = 0;
+
+        loop {
+            // releases
+            let mut released = Vec::new();
+            for (t, next) in next.iter_mut().enumerate() {
+                if *next == time && time < until {
+                    released.push((t, time));
+                    sim.event(time, &set.tasks[t].name, EventKind::Release);
+                    *next += set.tasks[t].period as u64;
+                }
+            }
+            if !released.is_empty() {
+                if set.overhead.release == 0 {
+                    for (t, release) in released {
+                        ready.push(jobs.len());
+                        jobs.push(new_job(t, release));
+                    }
+                } else {
+                    ready.push(jobs.len());
+                    jobs.push(Job {
+                        task: None,
+                        priority: set.timer_priority(),
+                        release: time,
+                        start: 0,
+                        steps: vec![Step::Run(
+                            set.overhead.release as u64 * released.len() as u64,
+                        )],
+                        pc: 0,
+                        releases: released,
+                    });
+                }
+            }
+
+            // the steps taking no time of the running job (completed before
+            // releases at the same instant take effect), and dispatch
+            let mut resumed = false;
+            loop {
+                if let Some(&top) = stack.last() {
+                    let job = &mut jobs[top];
+                    match job.steps.get(job.pc).copied() {
+                        Some(Step::Run(0)) => {
+                            job.pc += 1;
+                            continue;
+                        }
+                        Some(Step::Run(_)) => {}
+                        Some(Step::Lock(r)) => {
+                            job.pc += 1;
+                            let ceiling = ceilings[resources[r]];
+                            locked.push((r, ceiling));
+                            let resource = resources[r].to_string();
+                            sim.event(time, &name(job), EventKind::Lock { resource, ceiling });
+                            continue;
+                        }
+                        Some(Step::Unlock) => {
+                            job.pc += 1;
+                            let (r, _) = locked.pop().unwrap();
+                            let resource = resources[r].to_string();
+                            sim.event(time, &name(job), EventKind::Unlock { resource });
+                            continue;
+                        }
+                        None => {
+                            stack.pop();
+                            let job = &jobs[top];
+                            let response_time = time - job.release;
+                            let missed = match job.task {
+                                Some(t) => response_time > set.tasks[t].deadline as u64,
+                                None => false,
+                            };
+                            sim.event(
+                                time,
+                                &name(job),
+                                EventKind::Finish {
+                                    response_time,
+                                    missed,
+                                },
+                            );
+                            if job.task.is_some() {
+                                sim.jobs.push(JobRecord {
+                                    task: name(job),
+                                    release: job.release,
+                                    start: job.start,
+                                    finish: time,
+                                });
+                            }
+                            for (t, release) in job.releases.clone() {
+                                ready.push(jobs.len());
+                                jobs.push(new_job(t, release));
+                            }
+                            resumed = !stack.is_empty();
+                            continue;
+                        }
+                    }
+                }
+
+                // the highest priority job first, in release order
+                let ceiling = locked.iter().map(|&(_, c)| c).max().unwrap_or(0);
+                let running = stack.last().map_or(0, |&j| jobs[j].priority);
+                let candidate = ready
+                    .iter()
+                    .enumerate()
+                    .max_by_key(|&(_, &j)| (jobs[j].priority, Reverse(j)))
+                    .map(|(i, &j)| (i, j));
+                match candidate {
+                    Some((i, j)) if jobs[j].priority > running.max(ceiling) => {
+                        ready.remove(i);
+                        if let (Some(&top), false) = (stack.last(), resumed) {
+                            let by = name(&jobs[j]);
+                            sim.event(time, &name(&jobs[top]), EventKind::Preempted { by });
+                        }
+                        resumed = false;
+                        jobs[j].start = time;
+                        sim.event(time, &name(&jobs[j]), EventKind::Start);
+                        stack.push(j);
+                    }
+                    _ => {
+                        if let (Some(&top), true) = (stack.last(), resumed) {
+                            sim.event(time, &name(&jobs[top]), EventKind::Resume);
+                        }
+                        break;
+                    }
+                }
+            }
+
+            // advance to the next release, or the end of the running step
+            let release = next.iter().copied().filter(|&n| n < until).min();
+            match stack.last() {
+                Some(&top) => {
+                    let job = &mut jobs[top];
+                    if let Step::Run(left) = &mut job.steps[job.pc] {
+                        let dt = release.map_or(*left, |r| (*left).min(r - time));
+                        *left -= dt;
+                        time += dt;
+                    }
+                }
+                None => match release {
+                    Some(r) => time = r,
+                    None => break,
+                },
+            }
+        }
+        sim
+    }
+
+    fn event(&mut self, time: u64, task: &str, kind: EventKind) {
+        self.events.push(Event {
+            time,
+            task: task.to_string(),
+            kind,
+        });
+    }
+
+    /// Response time statistics, per task of the set.
+    pub fn stats(&self, set: &TaskSet) -> Vec<TaskStats> {
+        let mut stats: BTreeMap<&str, TaskStats> = BTreeMap::new();
+        for t in &set.tasks {
+            stats.insert(
+                &t.name,
+                TaskStats {
+                    task: t.name.clone(),
+                    jobs: 0,
+                    max_response_time: 0,
+                    worst_release: 0,
+                    misses: 0,
+                    first_miss: None,
+                },
+            );
+        }
+        for job in &self.jobs {
+            let s = stats.get_mut(job.task.as_str()).unwrap();
+            let rt = job.response_time();
+            s.jobs += 1;
+            if rt > s.max_response_time {
+                s.max_response_time = rt;
+                s.worst_release = job.release;
+            }
+            if rt > set.task(&job.task).unwrap().deadline as u64 {
+                s.misses += 1;
+                s.first_miss.get_or_insert(job.release);
+            }
+        }
+        set.tasks
+            .iter()
+            .map(|t| stats.remove(t.name.as_str()).unwrap())
+            .collect()
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>10}  {:<12} ", self.time, self.task)?;
+        match &self.kind {
+            EventKind::Release => write!(f, "released"),
+            EventKind::Start => write!(f, "started"),
+            EventKind::Preempted { by } => write!(f, "preempted by {}", by),
+            EventKind::Resume => write!(f, "resumed"),
+            EventKind::Lock { resource, ceiling } => {
+                write!(f, "locks {} (ceiling {})", resource, ceiling)
+            }
+            EventKind::Unlock { resource } => write!(f, "unlocks {}", resource),
+            EventKind::Finish {
+                response_time,
+                missed,
+            } => write!(
+                f,
+                "finished, response time {}{}",
+                response_time,
+                if *missed { ", DEADLINE MISS" } else { "" }
+            ),
+        }
+    }
+}
+
+/// The per task summary of a simulation.
+pub struct Summary<'a> {
+    pub set: &'a TaskSet,
+    pub stats: &'a [TaskStats],
+}
+
+impl fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<8} {:>4} {:>8} {:>8} {:>8} {:>5} {:>10} {:>10} {:>6}",
+            "task", "prio", "period", "deadline", "wcet", "jobs", "max rt", "at", "misses"
+        )?;
+        for (t, s) in self.set.tasks.iter().zip(self.stats) {
+            writeln!(
+                f,
+                "{:<8} {:>4} {:>8} {:>8} {:>8} {:>5} {:>10} {:>10} {:>6}{}",
+                t.name,
+                t.priority,
+                t.period,
+                t.deadline,
+                t.wcet(),
+                s.jobs,
+                s.max_response_time,
+                s.worst_release,
+                s.misses,
+                match s.first_miss {
+                    Some(release) => format!(" (first released at {})", release),
+                    None => String::new(),
+                }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn exam() -> TaskSet {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("task_sets/exam.json");
+        TaskSet::load(&path).unwrap()
+    }
+
+    fn job<'a>(sim: &'a Simulation, task: &str) -> &'a JobRecord {
+        sim.jobs.iter().find(|j| j.task == task).unwrap()
+    }
+
+    #[test]
+    fn exam_worst_case() {
+        let set = exam();
+        let sim = Simulation::run(&set, 200_000 + 2 * set.hyperperiod());
+        let stats: Vec<_> = sim
+            .stats(&set)
+            .into_iter()
+            .map(|s| {
+                (
+                    s.task,
+                    s.jobs,
+                    s.max_response_time,
+                    s.worst_release,
+                    s.misses,
+                )
+            })
+            .collect();
+        // all released at 200_000, t3 is never blocked by t2 (the analysis
+        // bounds it by 34_000)
+        assert_eq!(
+            stats,
+            [
+                ("t1".to_string(), 5, 100_000, 200_000, 0),
+                ("t2".to_string(), 2, 90_000, 200_000, 0),
+                ("t3".to_string(), 11, 30_000, 50_000, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn blocking() {
+        // t3 released while t2 holds R2 (ceiling 3), [12_000, 16_000]
+        let mut set = exam();
+        set.tasks[1].offset = 0;
+        set.tasks[2].offset = 13_000;
+        let sim = Simulation::run(&set, 13_001);
+        let t3 = job(&sim, "t3");
+        assert_eq!((t3.release, t3.start, t3.finish), (13_000, 16_000, 46_000));
+        let t2 = job(&sim, "t2");
+        assert_eq!((t2.start, t2.finish), (0, 60_000));
+
+        let t3_events: Vec<_> = sim
+            .events
+            .iter()
+            .filter(|e| e.task == "t3")
+            .map(|e| (e.time, e.kind.clone()))
+            .collect();
+        assert_eq!(
+            t3_events[..2],
+            [(13_000, EventKind::Release), (16_000, EventKind::Start)]
+        );
+        assert!(sim.events.contains(&Event {
+            time: 16_000,
+            task: "t2".into(),
+            kind: EventKind::Preempted { by: "t3".into() },
+        }));
+    }
+
+    #[test]
+    fn overhead() {
+        let mut set = exam();
+        set.tasks.truncate(1);
+        set.overhead.release = 100;
+        set.overhead.dispatch = 50;
+        let sim = Simulation::run(&set, 100_001);
+        // the timer queue handler, then the dispatch
+        let t1 = job(&sim, "t1");
+        assert_eq!(
+            (t1.release, t1.start, t1.finish),
+            (100_000, 100_100, 110_150)
+        );
+        let events: Vec<_> = sim.events.iter().map(|e| (e.time, &*e.task)).collect();
+        assert_eq!(
+            events,
+            [
+                (100_000, "t1"),
+                (100_000, TIMER_QUEUE),
+                (100_100, TIMER_QUEUE),
+                (100_100, "t1"),
+                (110_150, "t1"),
+            ]
+        );
+    }
+}
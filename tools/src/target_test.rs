@@ -0,0 +1,320 @@
+//! target_test.rs
+//!
+//! Aggregation of on-target test results, reported by the firmware
+//! (`src/testing.rs`) in the format of `cargo test`.
+
+use std::fmt;
+
+/// Outcome of a single test.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Failed(String),
+    Ignored,
+}
+
+/// A parsed report line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Line {
+    /// `running 4 tests`
+    Running(usize),
+    /// `test tests::spsc_fifo ... ok`
+    Test(String, Status),
+    /// `test result: ok. 4 passed; 0 failed; 0 ignored`
+    Result,
+}
+
+impl Line {
+    /// Parses a report line, other output is ignored (`None`).
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.starts_with("test result: ") {
+            return Some(Line::Result);
+        }
+        if let Some(n) = line.strip_prefix("running ").and_then(|rest| {
+            rest.strip_suffix(" tests")
+                .or_else(|| rest.strip_suffix(" test"))
+        }) {
+            return n.parse().ok().map(Line::Running);
+        }
+        let (name, status) = line.strip_prefix("test ")?.split_once(" ... ")?;
+        let status = match status {
+            "ok" => Status::Ok,
+            "ignored" => Status::Ignored,
+            "FAILED" => Status::Failed(String::new()),
+            _ => Status::Failed(status.strip_prefix("FAILED: ")?.to_string()),
+        };
+        Some(Line::Test(name.to_string(), status))
+    }
+}
+
+/// The tests of a single run (firmware image).
+#[derive(Clone, Debug, Default)]
+pub struct Run {
+    /// Where the output was read from.
+    pub source: String,
+    /// Number of tests announced.
+    pub total: usize,
+    pub tests: Vec<(String, Status)>,
+    /// The final `test result` line was seen.
+    pub complete: bool,
+    /// The last line reported a failure, unrecognized lines continue its
+    /// reason (panic messages span several lines).
+    failing: bool,
+}
+
+impl Run {
+    pub fn new(source: &str) -> Self {
+        Run {
+            source: source.to_string(),
+            ..Run::default()
+        }
+    }
+
+    /// Adds a line of output, returns true at the end of the run.
+    pub fn feed(&mut self, line: &str) -> bool {
+        let parsed = Line::parse(line);
+        if parsed.is_some() {
+            self.failing = false;
+        }
+        match parsed {
+            Some(Line::Running(n)) => self.total = n,
+            Some(Line::Test(name, status)) => {
+                self.failing = matches!(status, Status::Failed(_));
+                self.tests.push((name, status));
+            }
+            Some(Line::Result) => self.complete = true,
+            None if self.failing && !line.trim().is_empty() => {
+                if let Some((_, Status::Failed(reason))) = self.tests.last_mut() {
+                    if !reason.is_empty() {
+                        reason.push('\n');
+                    }
+                    reason.push_str(line.trim_end());
+                }
+            }
+            None => {}
+        }
+        self.complete
+    }
+
+    pub fn count(&self, f: impl Fn(&Status) -> bool) -> usize {
+        self.tests.iter().filter(|(_, s)| f(s)).count()
+    }
+
+    pub fn passed(&self) -> usize {
+        self.count(|s| *s == Status::Ok)
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(|s| matches!(s, Status::Failed(_)))
+    }
+
+    pub fn ignored(&self) -> usize {
+        self.count(|s| *s == Status::Ignored)
+    }
+
+    /// Tests announced but not run, e.g., after a panic.
+    pub fn not_run(&self) -> usize {
+        self.total.saturating_sub(self.tests.len())
+    }
+
+    /// All tests run (or ignored), none failed, and the run completed.
+    pub fn success(&self) -> bool {
+        self.complete && self.failed() == 0 && self.not_run() == 0
+    }
+}
+
+/// The runs of a session.
+pub struct Report<'a>(pub &'a [Run]);
+
+impl Report<'_> {
+    pub fn success(&self) -> bool {
+        !self.0.is_empty() && self.0.iter().all(Run::success)
+    }
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failures: Vec<_> = self
+            .0
+            .iter()
+            .flat_map(|run| {
+                run.tests
+                    .iter()
+                    .filter_map(move |(name, status)| match status {
+                        Status::Failed(reason) => Some((run, name, reason)),
+                        _ => None,
+                    })
+            })
+            .collect();
+        if !failures.is_empty() {
+            writeln!(f, "failures:")?;
+            for (run, name, reason) in failures {
+                let mut lines = reason.lines();
+                writeln!(
+                    f,
+                    "    {} {}: {}",
+                    run.source,
+                    name,
+                    lines.next().unwrap_or("")
+                )?;
+                for line in lines {
+                    writeln!(f, "        {}", line)?;
+                }
+            }
+            writeln!(f)?;
+        }
+
+        let mut total = [0; 4];
+        for run in self.0 {
+            let counts = [run.passed(), run.failed(), run.ignored(), run.not_run()];
+            writeln!(
+                f,
+                "{:<24} {} passed; {} failed; {} ignored; {} not run{}",
+                run.source,
+                counts[0],
+                counts[1],
+                counts[2],
+                counts[3],
+                if run.complete { "" } else { " (incomplete)" }
+            )?;
+            for (t, c) in total.iter_mut().zip(counts.iter()) {
+                *t += c;
+            }
+        }
+        write!(
+            f,
+            "test result: {}. {} passed; {} failed; {} ignored; {} not run",
+            if self.success() { "ok" } else { "FAILED" },
+            total[0],
+            total[1],
+            total[2],
+            total[3]
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(output: &str) -> Run {
+        let mut run = Run::new("test");
+        for line in output.lines() {
+            if run.feed(line) {
+                break;
+            }
+        }
+        run
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(Line::parse("running 4 tests"), Some(Line::Running(4)));
+        assert_eq!(Line::parse("running 1 test"), Some(Line::Running(1)));
+        assert_eq!(
+            Line::parse("test tests::spsc_fifo ... ok\r"),
+            Some(Line::Test("tests::spsc_fifo".into(), Status::Ok))
+        );
+        assert_eq!(
+            Line::parse("test tests::slow ... ignored"),
+            Some(Line::Test("tests::slow".into(), Status::Ignored))
+        );
+        assert_eq!(
+            Line::parse("test tests::queue ... FAILED: \"full\""),
+            Some(Line::Test(
+                "tests::queue".into(),
+                Status::Failed("\"full\"".into())
+            ))
+        );
+        assert_eq!(
+            Line::parse("test tests::queue ... FAILED"),
+            Some(Line::Test("tests::queue".into(), Status::Failed("".into())))
+        );
+        assert_eq!(
+            Line::parse("test result: FAILED. 1 passed; 1 failed; 1 ignored"),
+            Some(Line::Result)
+        );
+        assert_eq!(Line::parse("init"), None);
+        assert_eq!(Line::parse("running tests"), None);
+        assert_eq!(Line::parse("test tests::queue ... maybe"), None);
+    }
+
+    #[test]
+    fn passing() {
+        let run = run("init\n\
+                       running 3 tests\n\
+                       test tests::wraps ... ok\n\
+                       test tests::queue ... ok\n\
+                       test tests::slow ... ignored\n\
+                       test result: ok. 2 passed; 0 failed; 1 ignored\n\
+                       trailing output");
+        assert_eq!(run.total, 3);
+        assert_eq!((run.passed(), run.failed(), run.ignored()), (2, 0, 1));
+        assert_eq!(run.not_run(), 0);
+        assert!(run.complete);
+        assert!(run.success());
+    }
+
+    #[test]
+    fn failing() {
+        let run = run("running 3 tests\n\
+                       test tests::wraps ... ok\n\
+                       test tests::queue ... FAILED: \"full\"\n\
+                       test tests::slow ... ok\n\
+                       test result: FAILED. 2 passed; 1 failed; 0 ignored");
+        assert_eq!(run.tests[1].1, Status::Failed("\"full\"".into()));
+        assert_eq!((run.passed(), run.failed(), run.not_run()), (2, 1, 0));
+        assert!(!run.success());
+    }
+
+    #[test]
+    fn multi_line_panic() {
+        let run = run("running 3 tests\n\
+                       test tests::wraps ... ok\n\
+                       test tests::sum ... FAILED: panicked at src/lib.rs:10:5:\n\
+                       assertion `left == right` failed\n  left: 1\n right: 2\n\
+                       \n\
+                       test result: FAILED. 1 passed; 1 failed; 0 ignored");
+        assert_eq!(
+            run.tests[1].1,
+            Status::Failed(
+                "panicked at src/lib.rs:10:5:\n\
+                 assertion `left == right` failed\n  left: 1\n right: 2"
+                    .into()
+            )
+        );
+        // the panic ends the session
+        assert_eq!(run.not_run(), 1);
+        assert!(run.complete);
+        assert!(!run.success());
+
+        let report = Report(std::slice::from_ref(&run)).to_string();
+        assert!(report.starts_with(
+            "failures:\n    test tests::sum: panicked at src/lib.rs:10:5:\n        \
+             assertion `left == right` failed\n          left: 1\n         right: 2\n"
+        ));
+        assert!(report.ends_with("test result: FAILED. 1 passed; 1 failed; 0 ignored; 1 not run"));
+    }
+
+    #[test]
+    fn output_after_passing_is_ignored() {
+        let run = run("running 2 tests\n\
+                       test tests::a ... FAILED: \"full\"\n\
+                       test tests::b ... ok\n\
+                       debug output\n\
+                       test result: FAILED. 1 passed; 1 failed; 0 ignored");
+        assert_eq!(run.tests[0].1, Status::Failed("\"full\"".into()));
+        assert_eq!(run.tests[1].1, Status::Ok);
+    }
+
+    #[test]
+    fn incomplete() {
+        let run = run("running 2 tests\ntest tests::a ... ok");
+        assert!(!run.complete);
+        assert_eq!(run.not_run(), 1);
+        assert!(!run.success());
+        assert!(!Report(&[]).success());
+        assert!(Report(&[run]).to_string().contains("(incomplete)"));
+    }
+}
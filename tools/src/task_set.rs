@@ -0,0 +1,308 @@
+//! task_set.rs
+//!
+//! Task sets for scheduling analysis and simulation.
+//!
+//! A task set is described in JSON, each task by its priority, period
+//! (inter-arrival time), relative deadline, offset (first release) and a
+//! trace of its execution, as in the `srp_analysis` of the klee tutorial.
+//! The trace spans the execution of the task, with nested traces for the
+//! critical sections (by resource), e.g., for `t2` of `timing_exam.rs`:
+//!
+//! ```json
+//! { "id": "t2", "start": 0, "end": 30000, "inner": [
+//!     { "id": "R1", "start": 10000, "end": 20000, "inner": [
+//!         { "id": "R2", "start": 12000, "end": 16000 } ] },
+//!     { "id": "R1", "start": 22000, "end": 28000 } ] }
+//! ```
+//!
+//! Times are in clock cycles. Optionally, the run-time overhead of RTIC
+//! is given (see `Overhead`).
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fs,
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trace {
+    /// The task name (outermost trace), or the resource.
+    pub id: String,
+    pub start: u32,
+    pub end: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inner: Vec<Trace>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Task {
+    pub name: String,
+    pub priority: u8,
+    /// Inter-arrival time.
+    pub period: u32,
+    /// Relative deadline.
+    pub deadline: u32,
+    /// Time of the first release.
+    #[serde(default)]
+    pub offset: u32,
+    /// Message queue capacity (`#[task(capacity = N)]`).
+    #[serde(default = "one")]
+    pub capacity: u32,
+    /// Instances scheduled (`schedule`) but not yet started at once, 1 for
+    /// a task rescheduling itself (`cx.scheduled + PERIOD`).
+    #[serde(default = "one")]
+    pub pending: u32,
+    pub trace: Trace,
+}
+
+fn one() -> u32 {
+    1
+}
+
+/// Run-time overhead of the scheduler, in clock cycles.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Overhead {
+    /// Timer queue handling, per released task instance (`schedule`). The
+    /// timer queue handler runs at `timer_priority`.
+    pub release: u32,
+    /// From the release (or the end of the timer queue handler) to the
+    /// first instruction of the task.
+    pub dispatch: u32,
+    /// Priority of the timer queue handler, the highest task priority if
+    /// not given (as in RTIC 0.5).
+    pub timer_priority: Option<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskSet {
+    #[serde(default)]
+    pub overhead: Overhead,
+    /// Capacity of the timer queue, the sum of the task capacities if not
+    /// given (as in RTIC 0.5).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timer_queue: Option<u32>,
+    pub tasks: Vec<Task>,
+}
+
+impl Trace {
+    /// The critical sections of the trace (nested ones included), as
+    /// resource and hold time.
+    pub fn sections(&self) -> Vec<(&str, u32)> {
+        let mut sections = Vec::new();
+        for t in &self.inner {
+            sections.push((t.id.as_str(), t.end - t.start));
+            sections.extend(t.sections());
+        }
+        sections
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.end < self.start {
+            return Err(format!("trace `{}` ends before it starts", self.id));
+        }
+        let mut last = self.start;
+        for t in &self.inner {
+            if t.start < last || t.end > self.end {
+                return Err(format!(
+                    "trace `{}` [{}, {}] is not within `{}` [{}, {}], after its predecessor",
+                    t.id, t.start, t.end, self.id, self.start, self.end
+                ));
+            }
+            t.validate()?;
+            last = t.end;
+        }
+        Ok(())
+    }
+}
+
+impl Task {
+    /// Worst case execution time.
+    pub fn wcet(&self) -> u32 {
+        self.trace.end - self.trace.start
+    }
+
+    /// Resources used by the task.
+    pub fn resources(&self) -> BTreeSet<&str> {
+        self.trace.sections().into_iter().map(|(r, _)| r).collect()
+    }
+}
+
+impl TaskSet {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let set: TaskSet = serde_json::from_str(&fs::read_to_string(path)?)?;
+        set.validate()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(set)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mut names = BTreeSet::new();
+        for t in &self.tasks {
+            if !names.insert(&t.name) {
+                return Err(format!("task `{}` defined twice", t.name));
+            }
+            if t.priority == 0 {
+                return Err(format!("task `{}` has priority 0 (idle)", t.name));
+            }
+            if t.period == 0 || t.deadline == 0 {
+                return Err(format!("task `{}` has a zero period or deadline", t.name));
+            }
+            t.trace
+                .validate()
+                .map_err(|e| format!("task `{}`: {}", t.name, e))?;
+        }
+        Ok(())
+    }
+
+    pub fn task(&self, name: &str) -> Option<&Task> {
+        self.tasks.iter().find(|t| t.name == name)
+    }
+
+    /// The ceiling of each resource, the highest priority of the tasks
+    /// using it.
+    pub fn ceilings(&self) -> BTreeMap<&str, u8> {
+        let mut ceilings = BTreeMap::new();
+        for t in &self.tasks {
+            for r in t.resources() {
+                let c = ceilings.entry(r).or_insert(0);
+                *c = (*c).max(t.priority);
+            }
+        }
+        ceilings
+    }
+
+    /// The least common multiple of the periods.
+    pub fn hyperperiod(&self) -> u64 {
+        self.tasks.iter().fold(1, |h, t| {
+            let p = t.period as u64;
+            h / gcd(h, p) * p
+        })
+    }
+
+    /// Capacity of the timer queue.
+    pub fn timer_queue(&self) -> u32 {
+        self.timer_queue
+            .unwrap_or_else(|| self.tasks.iter().map(|t| t.capacity).sum())
+    }
+
+    /// Priority of the timer queue handler.
+    pub fn timer_priority(&self) -> u8 {
+        self.overhead
+            .timer_priority
+            .unwrap_or_else(|| self.tasks.iter().map(|t| t.priority).max().unwrap_or(1))
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::{Expr, Item, Lit};
+
+    fn load_exam() -> TaskSet {
+        TaskSet::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("task_sets/exam.json")).unwrap()
+    }
+
+    /// The `EXAM` task set of the firmware (`src/task_set.rs`), as name,
+    /// priority, period, deadline and WCET.
+    fn firmware_exam() -> Vec<(String, u32, u32, u32, u32)> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../src/task_set.rs");
+        let file = syn::parse_file(&fs::read_to_string(path).unwrap()).unwrap();
+        let array = file
+            .items
+            .iter()
+            .find_map(|item| match item {
+                Item::Const(c) if c.ident == "EXAM" => match &*c.expr {
+                    Expr::Array(a) => Some(a.elems.clone()),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .expect("no `EXAM` array");
+        array
+            .iter()
+            .map(|task| {
+                let Expr::Struct(task) = task else {
+                    panic!("not a `Task`")
+                };
+                let field = |name: &str| match task
+                    .fields
+                    .iter()
+                    .find(|f| f.member == syn::parse_str(name).unwrap())
+                    .map(|f| &f.expr)
+                {
+                    Some(Expr::Lit(l)) => l.lit.clone(),
+                    _ => panic!("no literal `{}`", name),
+                };
+                let int = |name| match field(name) {
+                    Lit::Int(i) => i.base10_parse().unwrap(),
+                    _ => panic!("`{}` is not an integer", name),
+                };
+                let name = match field("name") {
+                    Lit::Str(s) => s.value(),
+                    _ => panic!("`name` is not a string"),
+                };
+                (
+                    name,
+                    int("priority"),
+                    int("period"),
+                    int("deadline"),
+                    int("wcet"),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exam_matches_the_firmware() {
+        let exam: Vec<_> = load_exam()
+            .tasks
+            .iter()
+            .map(|t| {
+                (
+                    t.name.clone(),
+                    t.priority as u32,
+                    t.period,
+                    t.deadline,
+                    t.wcet(),
+                )
+            })
+            .collect();
+        assert_eq!(exam, firmware_exam());
+    }
+
+    #[test]
+    fn exam() {
+        let set = load_exam();
+        assert_eq!(set.hyperperiod(), 200_000);
+        let ceilings: Vec<_> = set.ceilings().into_iter().collect();
+        assert_eq!(ceilings, [("R1", 2), ("R2", 3)]);
+        assert_eq!(
+            set.task("t2").unwrap().trace.sections(),
+            [("R1", 10_000), ("R2", 4_000), ("R1", 6_000)]
+        );
+        assert_eq!(set.timer_priority(), 3);
+    }
+
+    #[test]
+    fn validate() {
+        let mut set = load_exam();
+        set.tasks[1].trace.inner[0].end = 31_000;
+        assert!(set.validate().is_err());
+        let mut set = load_exam();
+        set.tasks.push(set.tasks[0].clone());
+        assert_eq!(set.validate(), Err("task `t1` defined twice".into()));
+    }
+}